@@ -0,0 +1,189 @@
+//! Sprite drawing benchmark, run with `cargo bench --bench dxyn`
+//!
+//! Runs a rom that does nothing but draw 16x16 sprites in high resolution
+//! mode and records every DXYN it executes. The recorded draws are then
+//! replayed on the bit-packed `Framebuffer` and on a copy of the old
+//! framebuffer, which kept one byte per pixel and drew sprites a pixel at a
+//! time, so both sides do exactly the same work.
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use r_chip8::cpu::Cpu;
+use r_chip8::framebuffer::{Framebuffer, HIRES_HEIGHT, HIRES_WIDTH};
+use r_chip8::quirks::Quirks;
+
+const FRAMES: usize = 600;
+const INSTRUCTIONS_PER_FRAME: usize = 1000;
+
+/// 00FF, I = sprite, then draw at (V0, V1) and move by (5, 3) forever
+const ROM: [u8; 16] = [
+    0x00, 0xff, // hires
+    0xa2, 0x10, // LD I, 0x210
+    0x60, 0x00, // LD V0, 0
+    0x61, 0x00, // LD V1, 0
+    0xd0, 0x10, // DRW V0, V1, 0
+    0x70, 0x05, // ADD V0, 5
+    0x71, 0x03, // ADD V1, 3
+    0x12, 0x08, // JP 0x208
+];
+
+/// Checkerboard with a solid border
+const SPRITE: [u8; 32] = [
+    0xff, 0xff, 0xaa, 0xab, 0xd5, 0x55, 0xaa, 0xab, 0xd5, 0x55, 0xaa, 0xab, 0xd5, 0x55, 0xaa, 0xab,
+    0xd5, 0x55, 0xaa, 0xab, 0xd5, 0x55, 0xaa, 0xab, 0xd5, 0x55, 0xaa, 0xab, 0xd5, 0x55, 0xff, 0xff,
+];
+
+/// One executed DXYN with the registers it read
+#[derive(Clone, Copy)]
+struct Draw {
+    x: u8,
+    y: u8,
+    i: usize,
+    n: usize,
+}
+
+/// Framebuffer before the bit-packed rows: one byte per pixel holding a
+/// bitmask of the lit planes
+struct ByteFramebuffer {
+    pixels: [[u8; HIRES_WIDTH]; HIRES_HEIGHT],
+}
+
+impl ByteFramebuffer {
+    fn draw(&mut self, memory: &[u8], draw: Draw, clip: bool) -> bool {
+        let mut collision = false;
+        let (sprite_width, sprite_height) = if draw.n == 0 { (16, 16) } else { (8, draw.n) };
+        let x0 = draw.x as usize % HIRES_WIDTH;
+        let y0 = draw.y as usize % HIRES_HEIGHT;
+        // Only the first plane is selected by default
+        let plane_bit = 1;
+        for row in 0..sprite_height {
+            if clip && y0 + row >= HIRES_HEIGHT {
+                break;
+            }
+            let y = (y0 + row) % HIRES_HEIGHT;
+            let bits = sprite_row(memory, draw, row, sprite_width);
+            for bit in 0..sprite_width {
+                if clip && x0 + bit >= HIRES_WIDTH {
+                    break;
+                }
+                let x = (x0 + bit) % HIRES_WIDTH;
+                let pixel = ((bits >> (15 - bit)) & 1) as u8 * plane_bit;
+                if self.pixels[y][x] & pixel != 0 {
+                    collision = true;
+                }
+                self.pixels[y][x] ^= pixel;
+            }
+        }
+        collision
+    }
+}
+
+fn packed_draw(vram: &mut Framebuffer, memory: &[u8], draw: Draw, clip: bool) -> bool {
+    let mut collision = false;
+    let (sprite_width, sprite_height) = if draw.n == 0 { (16, 16) } else { (8, draw.n) };
+    let x0 = draw.x as usize % HIRES_WIDTH;
+    let y0 = draw.y as usize % HIRES_HEIGHT;
+    for row in 0..sprite_height {
+        if clip && y0 + row >= HIRES_HEIGHT {
+            break;
+        }
+        let y = (y0 + row) % HIRES_HEIGHT;
+        let bits = sprite_row(memory, draw, row, sprite_width);
+        collision |= vram.draw_sprite_row(0, x0, y, bits, sprite_width, clip);
+    }
+    collision
+}
+
+/// Sprite row left aligned to 16 bits
+fn sprite_row(memory: &[u8], draw: Draw, row: usize, sprite_width: usize) -> u16 {
+    if sprite_width == 16 {
+        (memory[draw.i + row * 2] as u16) << 8 | memory[draw.i + row * 2 + 1] as u16
+    } else {
+        (memory[draw.i + row] as u16) << 8
+    }
+}
+
+fn new_cpu() -> Cpu {
+    let mut cpu = Cpu::new(Quirks::schip());
+    cpu.read_data_to_memory(&ROM);
+    for (offset, &byte) in SPRITE.iter().enumerate() {
+        cpu.poke(0x210 + offset, byte);
+    }
+    cpu
+}
+
+/// Step through the rom and keep every DXYN, with the memory it read from
+fn record_draws() -> (Vec<Draw>, Vec<u8>) {
+    let mut cpu = new_cpu();
+    let mut draws = Vec::new();
+    for _ in 0..FRAMES * INSTRUCTIONS_PER_FRAME {
+        if let Some(opcode) = cpu.opcode_at(cpu.pc()).filter(|opcode| opcode & 0xf000 == 0xd000) {
+            let registers = cpu.registers();
+            draws.push(Draw {
+                x: registers[(opcode as usize >> 8) & 0xf],
+                y: registers[(opcode as usize >> 4) & 0xf],
+                i: cpu.i(),
+                n: opcode as usize & 0xf,
+            });
+        }
+        cpu.cycle([false; 16]).expect("benchmark rom crashed");
+    }
+    (draws, cpu.memory().to_vec())
+}
+
+fn time<F: FnMut() -> bool>(mut run: F) -> Duration {
+    // Best of a few runs, the first one also warms up the caches
+    (0..5)
+        .map(|_| {
+            let start = Instant::now();
+            black_box(run());
+            start.elapsed()
+        })
+        .min()
+        .unwrap()
+}
+
+fn report(name: &str, draws: usize, elapsed: Duration) {
+    println!(
+        "{:<28} {:>10.2?} {:>12.0} sprites/s",
+        name,
+        elapsed,
+        draws as f64 / elapsed.as_secs_f64()
+    );
+}
+
+fn main() {
+    let (draws, memory) = record_draws();
+    println!("{} frames, {} 16x16 sprites\n", FRAMES, draws.len());
+
+    for &clip in &[false, true] {
+        let suffix = if clip { "clipped" } else { "wrapped" };
+        let bytes = time(|| {
+            let mut vram = ByteFramebuffer { pixels: [[0; HIRES_WIDTH]; HIRES_HEIGHT] };
+            draws.iter().fold(false, |collision, &draw| vram.draw(&memory, draw, clip) | collision)
+        });
+        let packed = time(|| {
+            let mut vram = Framebuffer::new();
+            vram.set_hires(true);
+            draws.iter().fold(false, |collision, &draw| packed_draw(&mut vram, &memory, draw, clip) | collision)
+        });
+        report(&format!("byte per pixel, {}", suffix), draws.len(), bytes);
+        report(&format!("bit-packed rows, {}", suffix), draws.len(), packed);
+        println!("{:<28} {:>10.1}x\n", "speedup", bytes.as_secs_f64() / packed.as_secs_f64());
+    }
+
+    let whole_rom = time(|| {
+        let mut cpu = new_cpu();
+        (0..FRAMES).fold(false, |changed, _| {
+            cpu.run_frame([false; 16], INSTRUCTIONS_PER_FRAME).expect("benchmark rom crashed").vram_changed | changed
+        })
+    });
+    println!(
+        "{:<28} {:>10.2?} {:>12.0} frames/s at {} instructions per frame",
+        "whole rom on Cpu",
+        whole_rom,
+        FRAMES as f64 / whole_rom.as_secs_f64(),
+        INSTRUCTIONS_PER_FRAME
+    );
+}
@@ -0,0 +1,172 @@
+use crate::cpu::PROGRAM_START;
+use crate::instruction::Instruction;
+use std::collections::{BTreeMap, BTreeSet};
+
+/// How control gets from one instruction to another
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EdgeKind {
+    /// Next instruction in memory
+    Fallthrough,
+    /// JP addr
+    Jump,
+    /// CALL addr, the return site is a Fallthrough edge
+    Call,
+    /// Skip instruction taken, jumps over the next instruction
+    Skip,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Edge {
+    pub target: usize,
+    pub kind: EdgeKind,
+}
+
+/// Straight line run of instructions with a single entry at the start
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BasicBlock {
+    pub start: usize,
+    pub instructions: Vec<(usize, Instruction)>,
+    pub edges: Vec<Edge>,
+}
+
+/// Result of the recursive descent over a rom
+pub struct Analysis {
+    /// Start addresses of reachable instructions
+    pub code: BTreeSet<usize>,
+    /// Entry points of subroutines
+    pub subroutines: BTreeSet<usize>,
+    /// Addresses of Bnnn computed jumps whose targets aren't known
+    pub unresolved: BTreeSet<usize>,
+    /// Basic blocks by start address
+    pub blocks: BTreeMap<usize, BasicBlock>,
+    rom: Vec<u8>,
+}
+
+/// Decode the instruction starting at address, None past the end of the rom.
+/// `rom` is loaded at PROGRAM_START.
+pub fn instruction_at(rom: &[u8], address: usize) -> Option<Instruction> {
+    let offset = address.checked_sub(PROGRAM_START)?;
+    match (rom.get(offset), rom.get(offset + 1)) {
+        (Some(&byte1), Some(&byte2)) => Some(Instruction::decode((byte1 as u16) << 8 | byte2 as u16)),
+        _ => None,
+    }
+}
+
+/// Edges leaving the instruction at address.
+/// Computed jumps (Bnnn), returns and unknown opcodes have no known successor.
+pub fn successors(rom: &[u8], address: usize, instruction: Instruction) -> Vec<Edge> {
+    let next = address + instruction.size();
+    let edge = |target, kind| Edge { target, kind };
+    match instruction {
+        Instruction::Jp(nnn) => vec![edge(nnn as usize, EdgeKind::Jump)],
+        Instruction::Call(nnn) => vec![edge(nnn as usize, EdgeKind::Call), edge(next, EdgeKind::Fallthrough)],
+        Instruction::Ret | Instruction::Exit | Instruction::JpV0(_) => vec![],
+        Instruction::Sys(_) | Instruction::Unknown(_) => vec![],
+        _ if instruction.is_skip() => {
+            let skipped = instruction_at(rom, next).map(|i| i.size()).unwrap_or(2);
+            vec![edge(next, EdgeKind::Fallthrough), edge(next + skipped, EdgeKind::Skip)]
+        }
+        _ => vec![edge(next, EdgeKind::Fallthrough)],
+    }
+}
+
+/// Start addresses of all instructions reachable from PROGRAM_START by
+/// following jumps, calls and skips.
+pub fn reachable_code(rom: &[u8]) -> BTreeSet<usize> {
+    analyze(rom).code
+}
+
+/// Follow jumps, calls and skips from PROGRAM_START and split the reachable
+/// code into basic blocks.
+pub fn analyze(rom: &[u8]) -> Analysis {
+    let mut code = BTreeSet::new();
+    let mut subroutines = BTreeSet::new();
+    let mut unresolved = BTreeSet::new();
+    // Blocks start at the entry point, at every branch target and after every branch
+    let mut leaders = BTreeSet::new();
+    leaders.insert(PROGRAM_START);
+
+    let mut pending = vec![PROGRAM_START];
+    while let Some(address) = pending.pop() {
+        if code.contains(&address) {
+            continue;
+        }
+        let instruction = match instruction_at(rom, address) {
+            Some(instruction) => instruction,
+            None => continue,
+        };
+        code.insert(address);
+
+        if let Instruction::JpV0(_) = instruction {
+            unresolved.insert(address);
+        }
+        let edges = successors(rom, address, instruction);
+        let ends_block = !matches!(edges.as_slice(), [Edge { kind: EdgeKind::Fallthrough, .. }]);
+        for edge in edges.iter() {
+            if edge.kind == EdgeKind::Call {
+                subroutines.insert(edge.target);
+            }
+            if ends_block {
+                leaders.insert(edge.target);
+            }
+        }
+        if ends_block {
+            leaders.insert(address + instruction.size());
+        }
+        pending.extend(edges.iter().map(|edge| edge.target));
+    }
+
+    let mut blocks = BTreeMap::new();
+    for &start in leaders.iter().filter(|address| code.contains(address)) {
+        let mut block = BasicBlock { start, instructions: Vec::new(), edges: Vec::new() };
+        let mut address = start;
+        loop {
+            let instruction = instruction_at(rom, address).unwrap();
+            block.instructions.push((address, instruction));
+            let edges = successors(rom, address, instruction);
+            let next = address + instruction.size();
+            let falls_through = matches!(edges.as_slice(), [Edge { kind: EdgeKind::Fallthrough, .. }]);
+            if !falls_through || leaders.contains(&next) || !code.contains(&next) {
+                block.edges = edges;
+                break;
+            }
+            address = next;
+        }
+        blocks.insert(start, block);
+    }
+
+    Analysis { code, subroutines, unresolved, blocks, rom: rom.to_vec() }
+}
+
+impl Analysis {
+    /// Code/data map: true for every rom byte that belongs to a reachable instruction
+    pub fn code_map(&self) -> Vec<bool> {
+        let mut map = vec![false; self.rom.len()];
+        for &address in self.code.iter() {
+            let size = instruction_at(&self.rom, address).map(|i| i.size()).unwrap_or(2);
+            let offset = address - PROGRAM_START;
+            for byte in map.iter_mut().skip(offset).take(size) {
+                *byte = true;
+            }
+        }
+        map
+    }
+
+    /// Operand of the F000 NNNN long load at address, None for other instructions
+    pub fn long_address(&self, address: usize, instruction: Instruction) -> Option<u16> {
+        match instruction {
+            Instruction::LdILong => {
+                let offset = address - PROGRAM_START;
+                match (self.rom.get(offset + 2), self.rom.get(offset + 3)) {
+                    (Some(&byte1), Some(&byte2)) => Some((byte1 as u16) << 8 | byte2 as u16),
+                    _ => None,
+                }
+            }
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+#[path = "./analysis_tests.rs"]
+mod analysis_tests;
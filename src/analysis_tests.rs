@@ -0,0 +1,61 @@
+use super::*;
+
+// 200: LD V0, 0x01
+// 202: SE V0, 0x01
+// 204: JP 0x20A
+// 206: CALL 0x20E
+// 208: JP 0x206
+// 20A: JP V0, 0x300
+// 20C: data
+// 20E: RET
+const ROM: [u8; 16] = [
+    0x60, 0x01, 0x30, 0x01, 0x12, 0x0a, 0x22, 0x0e,
+    0x12, 0x06, 0xb3, 0x00, 0xff, 0xff, 0x00, 0xee,
+];
+
+#[test]
+fn test_reachable_code() {
+    let analysis = analyze(&ROM);
+    let code: Vec<usize> = analysis.code.iter().cloned().collect();
+    assert_eq!(code, vec![0x200, 0x202, 0x204, 0x206, 0x208, 0x20a, 0x20e]);
+    assert!(analysis.subroutines.contains(&0x20e));
+    assert!(analysis.unresolved.contains(&0x20a));
+}
+
+#[test]
+fn test_code_map() {
+    let map = analyze(&ROM).code_map();
+    assert_eq!(map.len(), ROM.len());
+    assert!(map[..12].iter().all(|&code| code));
+    assert!(!map[12] && !map[13]);
+    assert!(map[14] && map[15]);
+}
+
+#[test]
+fn test_basic_blocks() {
+    let analysis = analyze(&ROM);
+    let starts: Vec<usize> = analysis.blocks.keys().cloned().collect();
+    assert_eq!(starts, vec![0x200, 0x204, 0x206, 0x208, 0x20a, 0x20e]);
+
+    let first = &analysis.blocks[&0x200];
+    assert_eq!(first.instructions.len(), 2);
+    assert_eq!(first.edges, vec![
+        Edge { target: 0x204, kind: EdgeKind::Fallthrough },
+        Edge { target: 0x206, kind: EdgeKind::Skip },
+    ]);
+    assert_eq!(analysis.blocks[&0x206].edges, vec![
+        Edge { target: 0x20e, kind: EdgeKind::Call },
+        Edge { target: 0x208, kind: EdgeKind::Fallthrough },
+    ]);
+    assert!(analysis.blocks[&0x20a].edges.is_empty());
+    assert!(analysis.blocks[&0x20e].edges.is_empty());
+}
+
+#[test]
+fn test_skip_over_long_load() {
+    // SE V0, 0x00 skips the four byte F000 NNNN
+    let rom = [0x30, 0x00, 0xf0, 0x00, 0x12, 0x34, 0x00, 0xfd];
+    let analysis = analyze(&rom);
+    assert!(analysis.code.contains(&0x206));
+    assert!(!analysis.code.contains(&0x204));
+}
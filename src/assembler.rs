@@ -0,0 +1,457 @@
+use crate::cpu::PROGRAM_START;
+use crate::instruction::Instruction;
+use crate::rom_reader::MAX_ROM_SIZE;
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// INCLUDEs nested deeper than this are assumed to include each other
+const MAX_INCLUDE_DEPTH: usize = 16;
+
+/// Assembled program, `image` is meant to be loaded at PROGRAM_START
+pub struct Assembly {
+    pub image: Vec<u8>,
+    /// Label addresses by name
+    pub labels: BTreeMap<String, u16>,
+}
+
+impl Assembly {
+    /// Symbol file contents, one `address label` line per label sorted by address
+    pub fn symbol_file(&self) -> String {
+        let mut labels: Vec<(&String, &u16)> = self.labels.iter().collect();
+        labels.sort_by_key(|&(name, &address)| (address, name.clone()));
+        labels.iter().map(|(name, address)| format!("{:04X} {}\n", address, name)).collect()
+    }
+}
+
+/// Assembly failure with the source line it happened on
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AsmError {
+    pub file: String,
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.file, self.line, self.message)
+    }
+}
+
+impl Error for AsmError {}
+
+/// Assemble source text. INCLUDE paths are relative to the working directory.
+pub fn assemble(source: &str) -> Result<Assembly, AsmError> {
+    let mut assembler = Assembler::default();
+    assembler.read_source(source, "<source>", Path::new("."), 0)?;
+    assembler.finish()
+}
+
+/// Assemble a source file. INCLUDE paths are relative to the including file.
+pub fn assemble_file(path: &Path) -> Result<Assembly, AsmError> {
+    let file = path.display().to_string();
+    let source = fs::read_to_string(path).map_err(|error| AsmError {
+        file: file.clone(),
+        line: 0,
+        message: format!("can't read file: {}", error),
+    })?;
+    let mut assembler = Assembler::default();
+    assembler.read_source(&source, &file, path.parent().unwrap_or_else(|| Path::new(".")), 0)?;
+    assembler.finish()
+}
+
+enum Body {
+    Instruction { mnemonic: String, operands: Vec<String> },
+    /// DB (width 1) or DW (width 2)
+    Data { width: usize, values: Vec<String> },
+}
+
+struct Statement {
+    file: String,
+    line: usize,
+    body: Body,
+}
+
+/// `NAME EQU value`, evaluated in the second pass so it can use later labels
+struct Constant {
+    file: String,
+    line: usize,
+    name: String,
+    value: String,
+}
+
+/// First pass collects statements and constants and assigns addresses to
+/// labels, the second pass in `finish` evaluates constants and operands once
+/// every label is known.
+#[derive(Default)]
+struct Assembler {
+    statements: Vec<Statement>,
+    constants: Vec<Constant>,
+    symbols: BTreeMap<String, i64>,
+    labels: BTreeMap<String, u16>,
+    address: usize,
+}
+
+impl Assembler {
+    fn read_source(&mut self, source: &str, file: &str, dir: &Path, depth: usize) -> Result<(), AsmError> {
+        for (index, text) in source.lines().enumerate() {
+            let line = index + 1;
+            let error = |message: String| AsmError { file: file.to_string(), line, message };
+            self.read_line(text, file, line, dir, depth).map_err(error)?;
+        }
+        Ok(())
+    }
+
+    fn read_line(&mut self, text: &str, file: &str, line: usize, dir: &Path, depth: usize) -> Result<(), String> {
+        let mut text = strip_comment(text).trim();
+
+        if let Some(first) = text.split_whitespace().next() {
+            if let Some(label) = first.strip_suffix(':') {
+                self.define_label(label)?;
+                text = text[first.len()..].trim();
+            }
+        }
+        if text.is_empty() {
+            return Ok(());
+        }
+
+        let (word, rest) = match text.find(char::is_whitespace) {
+            Some(index) => (&text[..index], text[index..].trim()),
+            None => (text, ""),
+        };
+        // NAME EQU value and NAME = value define constants
+        if let Some(value) = rest.strip_prefix('=').or_else(|| strip_keyword(rest, "EQU")) {
+            self.check_new_symbol(word)?;
+            self.constants.push(Constant {
+                file: file.to_string(),
+                line,
+                name: word.to_string(),
+                value: value.trim().to_string(),
+            });
+            return Ok(());
+        }
+
+        let mnemonic = word.to_uppercase();
+        let operands: Vec<String> = if rest.is_empty() {
+            Vec::new()
+        } else {
+            rest.split(',').map(|operand| operand.trim().to_string()).collect()
+        };
+
+        let (size, body) = match mnemonic.as_str() {
+            "INCLUDE" => return self.include(rest.trim_matches('"'), dir, depth),
+            "DB" => (operands.len(), Body::Data { width: 1, values: operands }),
+            "DW" => (operands.len() * 2, Body::Data { width: 2, values: operands }),
+            _ => {
+                let long = mnemonic == "LD" && operands.get(1).is_some_and(|operand| strip_keyword(operand, "LONG").is_some());
+                (if long { 4 } else { 2 }, Body::Instruction { mnemonic, operands })
+            }
+        };
+        self.statements.push(Statement { file: file.to_string(), line, body });
+        self.address += size;
+        Ok(())
+    }
+
+    fn check_new_symbol(&self, name: &str) -> Result<(), String> {
+        check_name(name)?;
+        if self.symbols.contains_key(name) || self.constants.iter().any(|constant| constant.name == name) {
+            return Err(format!("`{}` is already defined", name));
+        }
+        Ok(())
+    }
+
+    fn define_label(&mut self, name: &str) -> Result<(), String> {
+        self.check_new_symbol(name)?;
+        let address = PROGRAM_START + self.address;
+        self.symbols.insert(name.to_string(), address as i64);
+        self.labels.insert(name.to_string(), address as u16);
+        Ok(())
+    }
+
+    fn include(&mut self, name: &str, dir: &Path, depth: usize) -> Result<(), String> {
+        if depth >= MAX_INCLUDE_DEPTH {
+            return Err("includes nested too deep".to_string());
+        }
+        let path: PathBuf = dir.join(name);
+        let source = fs::read_to_string(&path)
+            .map_err(|error| format!("can't include {}: {}", path.display(), error))?;
+        let file = path.display().to_string();
+        let include_dir = path.parent().unwrap_or_else(|| Path::new(".")).to_path_buf();
+        self.read_source(&source, &file, &include_dir, depth + 1)
+            .map_err(|error| error.to_string())
+    }
+
+    /// Evaluate the constants in whatever order their values become known.
+    /// Fails on the first one that refers to an unknown symbol or, through
+    /// other constants, to itself.
+    fn resolve_constants(&mut self) -> Result<(), AsmError> {
+        let mut pending: Vec<&Constant> = self.constants.iter().collect();
+        while !pending.is_empty() {
+            let count = pending.len();
+            let symbols = &mut self.symbols;
+            pending.retain(|constant| match evaluate(&constant.value, symbols) {
+                Ok(value) => {
+                    symbols.insert(constant.name.clone(), value);
+                    false
+                }
+                Err(_) => true,
+            });
+            if pending.len() == count {
+                let constant = pending[0];
+                let message = evaluate(&constant.value, &self.symbols).err().unwrap_or_default();
+                return Err(AsmError { file: constant.file.clone(), line: constant.line, message });
+            }
+        }
+        Ok(())
+    }
+
+    fn finish(mut self) -> Result<Assembly, AsmError> {
+        self.resolve_constants()?;
+        let mut image = Vec::with_capacity(self.address);
+        for statement in self.statements.iter() {
+            let error = |message: String| AsmError {
+                file: statement.file.clone(),
+                line: statement.line,
+                message,
+            };
+            match statement.body {
+                Body::Instruction { ref mnemonic, ref operands } => {
+                    let (instruction, long) = encode(mnemonic, operands, &self.symbols).map_err(error)?;
+                    let opcode = instruction.encode();
+                    image.extend_from_slice(&[(opcode >> 8) as u8, opcode as u8]);
+                    if let Some(word) = long {
+                        image.extend_from_slice(&[(word >> 8) as u8, word as u8]);
+                    }
+                }
+                Body::Data { width, ref values } => {
+                    for value in values.iter() {
+                        if width == 1 {
+                            image.push(byte(value, &self.symbols).map_err(error)?);
+                        } else {
+                            let word = ranged(value, &self.symbols, -0x8000, 0xFFFF).map_err(error)? as u16;
+                            image.extend_from_slice(&[(word >> 8) as u8, word as u8]);
+                        }
+                    }
+                }
+            }
+        }
+        if image.len() > MAX_ROM_SIZE {
+            let last = self.statements.last().unwrap();
+            return Err(AsmError {
+                file: last.file.clone(),
+                line: last.line,
+                message: format!("program is {} bytes, the limit is {}", image.len(), MAX_ROM_SIZE),
+            });
+        }
+        Ok(Assembly { image, labels: self.labels })
+    }
+}
+
+/// Instruction for a statement, plus the address word that follows LD I, LONG
+fn encode(mnemonic: &str, operands: &[String], symbols: &BTreeMap<String, i64>) -> Result<(Instruction, Option<u16>), String> {
+    let ops: Vec<&str> = operands.iter().map(|operand| operand.as_str()).collect();
+    let upper: Vec<String> = operands.iter().map(|operand| operand.to_uppercase()).collect();
+    let upper: Vec<&str> = upper.iter().map(|operand| operand.as_str()).collect();
+    let addr = |operand: &str| ranged(operand, symbols, 0, 0xFFF).map(|value| value as u16);
+    let kk = |operand: &str| byte(operand, symbols);
+    let nibble = |operand: &str| ranged(operand, symbols, 0, 0xF).map(|value| value as u8);
+
+    let instruction = match (mnemonic, ops.as_slice()) {
+        ("CLS", []) => Instruction::Cls,
+        ("RET", []) => Instruction::Ret,
+        ("SCR", []) => Instruction::Scr,
+        ("SCL", []) => Instruction::Scl,
+        ("EXIT", []) => Instruction::Exit,
+        ("LOW", []) => Instruction::Low,
+        ("HIGH", []) => Instruction::High,
+        ("AUDIO", []) => Instruction::Audio,
+        ("SCD", [n]) => Instruction::Scd(nibble(n)?),
+        ("SYS", [a]) => Instruction::Sys(addr(a)?),
+        ("JP", [a]) => Instruction::Jp(addr(a)?),
+        ("JP", [v0, a]) if register(v0) == Some(0) => Instruction::JpV0(addr(a)?),
+        ("CALL", [a]) => Instruction::Call(addr(a)?),
+        ("SE", [x, y]) => match (vx(x)?, register(y)) {
+            (x, Some(y)) => Instruction::SeVxVy { x, y },
+            (x, None) => Instruction::SeVxByte { x, kk: kk(y)? },
+        },
+        ("SNE", [x, y]) => match (vx(x)?, register(y)) {
+            (x, Some(y)) => Instruction::SneVxVy { x, y },
+            (x, None) => Instruction::SneVxByte { x, kk: kk(y)? },
+        },
+        ("SAVE", [range]) => {
+            let (x, y) = register_range(range)?;
+            Instruction::Save { x, y }
+        }
+        ("LOAD", [range]) => {
+            let (x, y) = register_range(range)?;
+            Instruction::Load { x, y }
+        }
+        ("ADD", [_, x]) if upper[0] == "I" => Instruction::AddIVx(vx(x)?),
+        ("ADD", [x, y]) => match (vx(x)?, register(y)) {
+            (x, Some(y)) => Instruction::AddVxVy { x, y },
+            (x, None) => Instruction::AddVxByte { x, kk: kk(y)? },
+        },
+        ("OR", [x, y]) => Instruction::Or { x: vx(x)?, y: vx(y)? },
+        ("AND", [x, y]) => Instruction::And { x: vx(x)?, y: vx(y)? },
+        ("XOR", [x, y]) => Instruction::Xor { x: vx(x)?, y: vx(y)? },
+        ("SUB", [x, y]) => Instruction::Sub { x: vx(x)?, y: vx(y)? },
+        ("SUBN", [x, y]) => Instruction::Subn { x: vx(x)?, y: vx(y)? },
+        // Vy defaults to Vx, which behaves the same with or without the shift quirk
+        ("SHR", [x]) => Instruction::Shr { x: vx(x)?, y: vx(x)? },
+        ("SHR", [x, y]) => Instruction::Shr { x: vx(x)?, y: vx(y)? },
+        ("SHL", [x]) => Instruction::Shl { x: vx(x)?, y: vx(x)? },
+        ("SHL", [x, y]) => Instruction::Shl { x: vx(x)?, y: vx(y)? },
+        ("RND", [x, byte]) => Instruction::Rnd { x: vx(x)?, kk: kk(byte)? },
+        ("DRW", [x, y, n]) => Instruction::Drw { x: vx(x)?, y: vx(y)?, n: nibble(n)? },
+        ("SKP", [x]) => Instruction::Skp(vx(x)?),
+        ("SKNP", [x]) => Instruction::Sknp(vx(x)?),
+        ("PLANE", [n]) => Instruction::Plane(nibble(n)?),
+        ("PITCH", [x]) => Instruction::Pitch(vx(x)?),
+        ("LD", [a, b]) => return load(a, b, &upper, symbols),
+        _ => return Err(format!("unknown instruction `{}` with {} operands", mnemonic, operands.len())),
+    };
+    Ok((instruction, None))
+}
+
+/// All the LD forms
+fn load(a: &str, b: &str, upper: &[&str], symbols: &BTreeMap<String, i64>) -> Result<(Instruction, Option<u16>), String> {
+    let instruction = match (upper[0], upper[1]) {
+        ("I", _) => {
+            if let Some(address) = strip_keyword(b, "LONG") {
+                let address = ranged(address, symbols, 0, 0xFFFF)? as u16;
+                return Ok((Instruction::LdILong, Some(address)));
+            }
+            Instruction::LdI(ranged(b, symbols, 0, 0xFFF)? as u16)
+        }
+        ("DT", _) => Instruction::LdDtVx(vx(b)?),
+        ("ST", _) => Instruction::LdStVx(vx(b)?),
+        ("F", _) => Instruction::LdFVx(vx(b)?),
+        ("HF", _) => Instruction::LdHfVx(vx(b)?),
+        ("B", _) => Instruction::LdBVx(vx(b)?),
+        ("[I]", _) => Instruction::LdMemVx(vx(b)?),
+        ("R", _) => Instruction::LdRVx(vx(b)?),
+        (_, "DT") => Instruction::LdVxDt(vx(a)?),
+        (_, "K") => Instruction::LdVxK(vx(a)?),
+        (_, "[I]") => Instruction::LdVxMem(vx(a)?),
+        (_, "R") => Instruction::LdVxR(vx(a)?),
+        _ => match (vx(a)?, register(b)) {
+            (x, Some(y)) => Instruction::LdVxVy { x, y },
+            (x, None) => Instruction::LdVxByte { x, kk: byte(b, symbols)? },
+        },
+    };
+    Ok((instruction, None))
+}
+
+/// Register index for V0-VF
+fn register(operand: &str) -> Option<u8> {
+    let mut chars = operand.chars();
+    match (chars.next(), chars.next(), chars.next()) {
+        (Some('V'), Some(digit), None) | (Some('v'), Some(digit), None) => digit.to_digit(16).map(|x| x as u8),
+        _ => None,
+    }
+}
+
+fn vx(operand: &str) -> Result<u8, String> {
+    register(operand).ok_or_else(|| format!("expected a register, found `{}`", operand))
+}
+
+/// `Vx - Vy` operand of SAVE and LOAD
+fn register_range(operand: &str) -> Result<(u8, u8), String> {
+    let mut parts = operand.splitn(2, '-');
+    match (parts.next(), parts.next()) {
+        (Some(x), Some(y)) => Ok((vx(x.trim())?, vx(y.trim())?)),
+        _ => Err(format!("expected a register range `Vx - Vy`, found `{}`", operand)),
+    }
+}
+
+/// Byte operand, negative values are stored as two's complement
+fn byte(operand: &str, symbols: &BTreeMap<String, i64>) -> Result<u8, String> {
+    ranged(operand, symbols, -0x80, 0xFF).map(|value| value as u8)
+}
+
+fn ranged(operand: &str, symbols: &BTreeMap<String, i64>, min: i64, max: i64) -> Result<i64, String> {
+    let value = evaluate(operand, symbols)?;
+    if value < min || value > max {
+        return Err(format!("`{}` = {:#X} is out of range", operand, value));
+    }
+    Ok(value)
+}
+
+/// Sum and difference of numbers and symbols, e.g. `sprites + 5`.
+/// Numbers are decimal, hex with 0x, # or $, binary with 0b or %.
+fn evaluate(expression: &str, symbols: &BTreeMap<String, i64>) -> Result<i64, String> {
+    let mut total = 0;
+    let mut sign = 1;
+    let mut term = String::new();
+    for c in expression.chars().chain(Some('+')) {
+        if c != '+' && c != '-' {
+            term.push(c);
+        } else if term.trim().is_empty() {
+            // Unary sign
+            if c == '-' {
+                sign = -sign;
+            }
+        } else {
+            total += sign * value(term.trim(), symbols)?;
+            term.clear();
+            sign = if c == '-' { -1 } else { 1 };
+        }
+    }
+    if !term.trim().is_empty() || expression.trim().is_empty() || expression.trim_end().ends_with(['+', '-']) {
+        return Err(format!("expected a value, found `{}`", expression));
+    }
+    Ok(total)
+}
+
+fn value(term: &str, symbols: &BTreeMap<String, i64>) -> Result<i64, String> {
+    let lower = term.to_lowercase();
+    let parsed = if let Some(hex) = lower.strip_prefix("0x").or_else(|| lower.strip_prefix('#')).or_else(|| lower.strip_prefix('$')) {
+        i64::from_str_radix(hex, 16).ok()
+    } else if let Some(binary) = lower.strip_prefix("0b").or_else(|| lower.strip_prefix('%')) {
+        i64::from_str_radix(binary, 2).ok()
+    } else if term.starts_with(|c: char| c.is_ascii_digit()) {
+        term.parse().ok()
+    } else {
+        return symbols.get(term).cloned().ok_or_else(|| format!("unknown symbol `{}`", term));
+    };
+    parsed.ok_or_else(|| format!("invalid number `{}`", term))
+}
+
+fn check_name(name: &str) -> Result<(), String> {
+    let valid_start = name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_');
+    let valid_rest = name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.');
+    if !valid_start || !valid_rest || register(name).is_some() {
+        return Err(format!("invalid name `{}`", name));
+    }
+    Ok(())
+}
+
+/// Text after a leading keyword, matched without case, e.g. the address of `LONG 0x1234`
+fn strip_keyword<'a>(text: &'a str, keyword: &str) -> Option<&'a str> {
+    let head = text.get(..keyword.len())?;
+    let rest = &text[keyword.len()..];
+    if head.eq_ignore_ascii_case(keyword) && rest.starts_with(char::is_whitespace) {
+        Some(rest.trim())
+    } else {
+        None
+    }
+}
+
+/// Line without its `;` comment, semicolons inside quotes are kept
+fn strip_comment(line: &str) -> &str {
+    let mut quoted = false;
+    for (index, c) in line.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            ';' if !quoted => return &line[..index],
+            _ => {}
+        }
+    }
+    line
+}
+
+#[cfg(test)]
+#[path = "./assembler_tests.rs"]
+mod assembler_tests;
@@ -0,0 +1,120 @@
+use super::*;
+use crate::disassembler::{disassemble, Syntax};
+
+#[test]
+fn test_assemble_instructions() {
+    let source = "
+        CLS
+        LD V1, 0x05     ; comment
+        ld va, vb
+        ADD I, V2
+        DRW V1, V2, 5
+        SHR V3
+        LD [I], V3
+        LD V4, K
+        SE V0, -1
+    ";
+    let assembly = assemble(source).unwrap();
+    assert_eq!(assembly.image, vec![
+        0x00, 0xe0, 0x61, 0x05, 0x8a, 0xb0, 0xf2, 0x1e, 0xd1, 0x25,
+        0x83, 0x36, 0xf3, 0x55, 0xf4, 0x0a, 0x30, 0xff,
+    ]);
+}
+
+#[test]
+fn test_assemble_extensions() {
+    let source = "
+        HIGH
+        SCD 4
+        LD HF, V1
+        LD R, V7
+        SAVE V1 - V4
+        PLANE 3
+        LD I, LONG 0x1234
+        PITCH V2
+    ";
+    let assembly = assemble(source).unwrap();
+    assert_eq!(assembly.image, vec![
+        0x00, 0xff, 0x00, 0xc4, 0xf1, 0x30, 0xf7, 0x75, 0x51, 0x42,
+        0xf3, 0x01, 0xf0, 0x00, 0x12, 0x34, 0xf2, 0x3a,
+    ]);
+}
+
+#[test]
+fn test_labels_and_constants() {
+    let source = "
+        SPEED EQU 3
+        COUNT = SPEED + 2
+        start:
+            LD V0, COUNT
+            CALL draw
+            JP start
+        draw: LD I, sprite
+            RET
+        sprite:
+            DB %11110000, 0x90, #F0
+            DW sprite + 1
+    ";
+    let assembly = assemble(source).unwrap();
+    assert_eq!(assembly.image, vec![
+        0x60, 0x05, 0x22, 0x06, 0x12, 0x00, 0xa2, 0x0a, 0x00, 0xee,
+        0xf0, 0x90, 0xf0, 0x02, 0x0b,
+    ]);
+    assert_eq!(assembly.labels["draw"], 0x206);
+    assert_eq!(assembly.symbol_file(), "0200 start\n0206 draw\n020A sprite\n");
+}
+
+#[test]
+fn test_constants_use_later_labels() {
+    let source = "
+        LAST = SPRITE_END - 1
+        SIZE EQU SPRITE_END - sprite
+        LD I, LAST
+        LD V0, SIZE
+        sprite: DB 0xF0, 0x90
+        SPRITE_END = sprite + 2
+    ";
+    let assembly = assemble(source).unwrap();
+    assert_eq!(assembly.image, vec![0xa2, 0x05, 0x60, 0x02, 0xf0, 0x90]);
+
+    let error = assemble("CLS\nA = B\nB = A").err().unwrap();
+    assert_eq!(error.line, 2);
+    assert_eq!(error.message, "unknown symbol `B`");
+    assert!(assemble("a = 1\na:").is_err());
+}
+
+#[test]
+fn test_assemble_errors() {
+    let error = assemble("CLS\nLD V0, missing").err().unwrap();
+    assert_eq!(error.line, 2);
+    assert_eq!(error.message, "unknown symbol `missing`");
+
+    assert_eq!(assemble("JP 0x1000").err().unwrap().message, "`0x1000` = 0x1000 is out of range");
+    assert!(assemble("FOO V1").is_err());
+    assert!(assemble("a:\na:").is_err());
+    assert!(assemble("LD V0, V1, V2").is_err());
+}
+
+#[test]
+fn test_include() {
+    let dir = std::env::temp_dir().join(format!("r_chip8_assembler_include_{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("main.asm"), "INCLUDE \"lib.asm\"\nCALL clear\n").unwrap();
+    fs::write(dir.join("lib.asm"), "clear: CLS\nRET\n").unwrap();
+
+    let assembly = assemble_file(&dir.join("main.asm")).unwrap();
+    assert_eq!(assembly.image, vec![0x00, 0xe0, 0x00, 0xee, 0x22, 0x00]);
+
+    fs::write(dir.join("loop.asm"), "INCLUDE \"loop.asm\"\n").unwrap();
+    assert!(assemble_file(&dir.join("loop.asm")).is_err());
+}
+
+#[test]
+fn test_disassembly_round_trip() {
+    let rom: Vec<u8> = vec![
+        0x00, 0xe0, 0xa2, 0x34, 0x3a, 0x05, 0x81, 0x27, 0xc1, 0x0f, 0xd1, 0x25,
+        0xf3, 0x65, 0x51, 0x23, 0xf0, 0x00, 0xab, 0xcd, 0xb3, 0x00, 0x00, 0xfd,
+    ];
+    let source: Vec<String> = disassemble(&rom, Syntax::Cowgod).into_iter().map(|line| line.text).collect();
+    assert_eq!(assemble(&source.join("\n")).unwrap().image, rom);
+}
@@ -0,0 +1,60 @@
+use r_chip8::assembler::assemble_file;
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::exit;
+
+const USAGE: &str = "Usage: asm <path_to_source_file> [options]
+
+Options:
+    -o <file>           Output rom, defaults to the source name with .ch8
+    --symbols <file>    Symbol file, defaults to the source name with .sym";
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    let mut source = None;
+    let mut output = None;
+    let mut symbols = None;
+
+    let mut args = args.iter().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-o" | "--output" => output = Some(PathBuf::from(value(arg, args.next()))),
+            "--symbols" => symbols = Some(PathBuf::from(value(arg, args.next()))),
+            _ if arg.starts_with('-') || source.is_some() => {
+                eprintln!("Unexpected argument: {}\n\n{}", arg, USAGE);
+                exit(1)
+            }
+            _ => source = Some(PathBuf::from(arg)),
+        }
+    }
+    let source = source.unwrap_or_else(|| {
+        eprintln!("Missing source file\n\n{}", USAGE);
+        exit(1)
+    });
+    let output = output.unwrap_or_else(|| source.with_extension("ch8"));
+    let symbols = symbols.unwrap_or_else(|| source.with_extension("sym"));
+
+    let assembly = assemble_file(&source).unwrap_or_else(|error| {
+        eprintln!("{}", error);
+        exit(1)
+    });
+    write(&output, &assembly.image);
+    write(&symbols, assembly.symbol_file().as_bytes());
+    println!("{} bytes written to {}", assembly.image.len(), output.display());
+}
+
+fn value(option: &str, value: Option<&String>) -> String {
+    value.cloned().unwrap_or_else(|| {
+        eprintln!("Missing value for {}\n\n{}", option, USAGE);
+        exit(1)
+    })
+}
+
+fn write(path: &Path, data: &[u8]) {
+    if let Err(error) = fs::write(path, data) {
+        eprintln!("Can't write {}: {}", path.display(), error);
+        exit(1)
+    }
+}
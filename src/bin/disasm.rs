@@ -0,0 +1,55 @@
+use r_chip8::analysis::analyze;
+use r_chip8::disassembler::{disassemble, to_dot, Syntax};
+use r_chip8::RomReader;
+
+use std::env;
+use std::process::exit;
+
+const USAGE: &str = "Usage: disasm <path_to_rom_file> [options]
+
+Options:
+    --octo          Print Octo syntax instead of Cowgod mnemonics
+    --reachable     Mark words reachable from 0x200 as code, the rest as data
+    --dot           Print the control flow graph in Graphviz DOT format";
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    let mut rom_filename = None;
+    let mut syntax = Syntax::Cowgod;
+    let mut mark_reachable = false;
+    let mut dot = false;
+
+    for arg in args.iter().skip(1) {
+        match arg.as_str() {
+            "--octo" => syntax = Syntax::Octo,
+            "--reachable" => mark_reachable = true,
+            "--dot" => dot = true,
+            _ if arg.starts_with("--") || rom_filename.is_some() => {
+                eprintln!("Unexpected argument: {}\n\n{}", arg, USAGE);
+                exit(1)
+            }
+            _ => rom_filename = Some(arg.clone()),
+        }
+    }
+    let rom_filename = rom_filename.unwrap_or_else(|| {
+        eprintln!("Missing rom file\n\n{}", USAGE);
+        exit(1)
+    });
+
+    let rom = RomReader::new(&rom_filename);
+    if dot {
+        print!("{}", to_dot(&analyze(&rom.data), syntax));
+        return;
+    }
+
+    for line in disassemble(&rom.data, syntax) {
+        let bytes: Vec<String> = line.bytes.iter().map(|byte| format!("{:02X}", byte)).collect();
+        if mark_reachable {
+            let kind = if line.reachable { "code" } else { "data" };
+            let note = if line.unresolved { "  ; computed jump, targets unknown" } else { "" };
+            println!("{:03X}  {:<11}  {}  {}{}", line.address, bytes.join(" "), kind, line.text, note);
+        } else {
+            println!("{:03X}  {:<11}  {}", line.address, bytes.join(" "), line.text);
+        }
+    }
+}
@@ -1,14 +1,96 @@
-use crate::font_set::FONT_SET;
-use crate::utils::RomReader;
-use std::process::exit;
-use sdl2::hint::set;
-use rand::Rng;
-use sdl2::rect::Point;
+use crate::font_set::{BIG_FONT_ADDRESS, BIG_FONT_SET, FONT_SET};
+use crate::framebuffer::{Framebuffer, HIRES_HEIGHT, PLANES};
+use crate::error::CpuError;
+use crate::instruction::Instruction;
+use crate::quirks::Quirks;
+use crate::random::Random;
+use crate::save_state::{self, StateError, StateReader, StateWriter};
+
+// XO-CHIP has a 64 KiB address space
+pub const MEMORY_SIZE: usize = 0x10000;
+pub const PROGRAM_START: usize = 0x200;
 
 pub struct CycleState<'a> {
     pub vram_changed: bool,
-    pub vram: &'a [[u8; 64]; 32],
+    pub vram: &'a Framebuffer,
     pub sound: bool,
+    pub audio_pattern: Option<AudioPattern>,
+    pub halted: bool,
+}
+
+/// Data memory access made by an instruction. Instruction fetches aren't
+/// included, consecutive bytes are merged into one access.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MemoryAccess {
+    pub address: usize,
+    pub length: usize,
+    pub kind: AccessKind,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AccessKind {
+    Read,
+    Write,
+}
+
+impl MemoryAccess {
+    pub fn contains(&self, address: usize) -> bool {
+        address >= self.address && address < self.address + self.length
+    }
+}
+
+/// XO-CHIP audio
+/// 128 one bit samples played in a loop while the sound timer is active.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AudioPattern {
+    pub buffer: [u8; 16],
+    pub pitch: u8,
+}
+
+impl Default for AudioPattern {
+    fn default() -> Self {
+        AudioPattern {
+            buffer: [0xF0; 16],
+            pitch: 64,
+        }
+    }
+}
+
+impl AudioPattern {
+    /// Playback rate of the pattern bits in Hz
+    pub fn sample_rate(&self) -> f32 {
+        4000.0 * 2f32.powf((self.pitch as f32 - 64.0) / 48.0)
+    }
+
+    /// Sample at position 0..128 as 0 or 1
+    pub fn bit(&self, position: usize) -> u8 {
+        let position = position % 128;
+        (self.buffer[position / 8] >> (7 - position % 8)) & 1
+    }
+}
+
+/// What to do when the interpreter meets an opcode it doesn't implement,
+/// including 0NNN machine code calls.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum UnknownOpcodePolicy {
+    /// Skip the instruction like a NOP
+    #[default]
+    Ignore,
+    /// Print the opcode and PC to stderr and skip the instruction
+    Log,
+    /// Stop with CpuError::UnknownOpcode
+    Halt,
+}
+
+impl UnknownOpcodePolicy {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "ignore" => Some(UnknownOpcodePolicy::Ignore),
+            "log" => Some(UnknownOpcodePolicy::Log),
+            "halt" => Some(UnknownOpcodePolicy::Halt),
+            _ => None,
+        }
+    }
 }
 
 enum PointerAction {
@@ -34,7 +116,7 @@ pub struct Cpu {
     /// http://devernay.free.fr/hacks/chip8/C8TECH10.HTM
     opcode: u16,
     // Opcode
-    memory: [u8; 4096],
+    memory: Vec<u8>,
     // Memory TODO: Remove pub
     v: [u8; 16],
     // CPU registers
@@ -42,7 +124,7 @@ pub struct Cpu {
     // Index register
     i: usize,
     // Progmemory counter,
-    pub vram: [[u8; 64]; 32],
+    pub vram: Framebuffer,
     pub vram_changed: bool,
     pub delay_timer: u8,
     pub sound_timer: u8,
@@ -51,22 +133,36 @@ pub struct Cpu {
     keys: [bool; 16],
     wait_for_input: bool,
     input_address: usize, // Stores address where opcode test_fx0a should store value after keypad is pressed
+    quirks: Quirks,
+    vblank_wait: bool, // Set by Dxyn when quirks.display_wait is on
+    rpl: [u8; 16], // SUPER-CHIP RPL user flags used by Fx75 and Fx85
+    halted: bool, // Set by 00FD, the cpu stops executing
+    plane: u8, // XO-CHIP bitplanes selected by Fn01, drawing and scrolling only touch these
+    audio_pattern: Option<AudioPattern>, // XO-CHIP audio, None until the rom sets a pattern or pitch
+    unknown_opcode_policy: UnknownOpcodePolicy,
+    memory_accesses: Vec<MemoryAccess>, // Data reads and writes of the last instruction
+    random: Random, // Source of CXKK random bytes
+}
+
+impl Default for Cpu {
+    fn default() -> Self {
+        Self::new(Quirks::default())
+    }
 }
 
 impl Cpu {
-    pub fn new() -> Self {
-        let mut memory = [0u8; 4096];
-        for i in 0..FONT_SET.len() {
-            memory[i] = FONT_SET[i];
-        }
+    pub fn new(quirks: Quirks) -> Self {
+        let mut memory = vec![0u8; MEMORY_SIZE];
+        memory[..FONT_SET.len()].copy_from_slice(&FONT_SET);
+        memory[BIG_FONT_ADDRESS..BIG_FONT_ADDRESS + BIG_FONT_SET.len()].copy_from_slice(&BIG_FONT_SET);
 
         Cpu {
             opcode: 0,
             memory,
             v: [0; 16],
-            pc: 0x200,
+            pc: PROGRAM_START,
             i: 0,
-            vram: [[0; 64]; 32],
+            vram: Framebuffer::new(),
             vram_changed: false,
             delay_timer: 0,
             sound_timer: 0,
@@ -75,24 +171,141 @@ impl Cpu {
             keys: [false; 16],
             wait_for_input: false,
             input_address: 0,
+            quirks,
+            vblank_wait: false,
+            rpl: [0; 16],
+            halted: false,
+            plane: 1,
+            audio_pattern: None,
+            unknown_opcode_policy: UnknownOpcodePolicy::default(),
+            memory_accesses: Vec::new(),
+            random: Random::from_entropy(),
         }
     }
 
+    pub fn set_unknown_opcode_policy(&mut self, policy: UnknownOpcodePolicy) {
+        self.unknown_opcode_policy = policy;
+    }
+
+    /// Make CXKK produce the same random bytes on every run
+    pub fn set_seed(&mut self, seed: u64) {
+        self.random = Random::new(seed);
+    }
+
     pub fn read_data_to_memory(&mut self, input: &[u8]) {
         for (i, &byte) in input.iter().enumerate() {
-            let address = 0x200 + 1;
-            if address < 4096 {
-                self.memory[0x200 + i] = byte;
+            let address = PROGRAM_START + i;
+            if address < MEMORY_SIZE {
+                self.memory[address] = byte;
             } else {
                 break;
             }
         }
     }
 
-    pub fn cycle(&mut self, keys: [bool;16]) -> CycleState {
+    /// Snapshot of the whole machine in the save state format, tagged with
+    /// the hash of the running rom. Quirks and other settings aren't saved.
+    pub fn save_state(&self, rom_hash: u64) -> Vec<u8> {
+        let mut writer = StateWriter::default();
+        writer.bytes(&save_state::header(rom_hash));
+        writer.u16(self.opcode);
+        writer.u32(self.memory.len() as u32);
+        writer.bytes(&self.memory);
+        writer.bytes(&self.v);
+        writer.u32(self.pc as u32);
+        writer.u32(self.i as u32);
+        writer.bool(self.vram.is_hires());
+        for plane in 0..PLANES {
+            for y in 0..HIRES_HEIGHT {
+                writer.u128(self.vram.plane_row(plane, y));
+            }
+        }
+        writer.u8(self.delay_timer);
+        writer.u8(self.sound_timer);
+        for &address in self.stack.iter() {
+            writer.u32(address as u32);
+        }
+        writer.u8(self.sp as u8);
+        for &key in self.keys.iter() {
+            writer.bool(key);
+        }
+        writer.bool(self.wait_for_input);
+        writer.u8(self.input_address as u8);
+        writer.bool(self.vblank_wait);
+        writer.bytes(&self.rpl);
+        writer.bool(self.halted);
+        writer.u8(self.plane);
+        writer.bool(self.audio_pattern.is_some());
+        let pattern = self.audio_pattern.unwrap_or_default();
+        writer.bytes(&pattern.buffer);
+        writer.u8(pattern.pitch);
+        writer.u64(self.random.state());
+        writer.into_bytes()
+    }
+
+    /// Restore a snapshot from `save_state`. States of another rom or
+    /// format version are rejected and leave the machine untouched.
+    pub fn load_state(&mut self, state: &[u8], rom_hash: u64) -> Result<(), StateError> {
+        let mut reader = StateReader::new(save_state::check_header(state, rom_hash)?);
+        let mut cpu = Cpu::new(self.quirks);
+        cpu.unknown_opcode_policy = self.unknown_opcode_policy;
+
+        cpu.opcode = reader.u16()?;
+        if reader.u32()? as usize != MEMORY_SIZE {
+            return Err(StateError::Corrupt);
+        }
+        cpu.memory.copy_from_slice(reader.bytes(MEMORY_SIZE)?);
+        cpu.v.copy_from_slice(reader.bytes(16)?);
+        cpu.pc = reader.u32()? as usize;
+        cpu.i = reader.u32()? as usize;
+        cpu.vram.set_hires(reader.bool()?);
+        for plane in 0..PLANES {
+            for y in 0..HIRES_HEIGHT {
+                cpu.vram.set_plane_row(plane, y, reader.u128()?);
+            }
+        }
+        cpu.delay_timer = reader.u8()?;
+        cpu.sound_timer = reader.u8()?;
+        for address in cpu.stack.iter_mut() {
+            *address = reader.u32()? as usize;
+        }
+        cpu.sp = reader.u8()? as usize;
+        for key in cpu.keys.iter_mut() {
+            *key = reader.bool()?;
+        }
+        cpu.wait_for_input = reader.bool()?;
+        cpu.input_address = reader.u8()? as usize;
+        cpu.vblank_wait = reader.bool()?;
+        cpu.rpl.copy_from_slice(reader.bytes(16)?);
+        cpu.halted = reader.bool()?;
+        cpu.plane = reader.u8()?;
+        let has_pattern = reader.bool()?;
+        let mut pattern = AudioPattern::default();
+        pattern.buffer.copy_from_slice(reader.bytes(16)?);
+        pattern.pitch = reader.u8()?;
+        cpu.audio_pattern = if has_pattern { Some(pattern) } else { None };
+        cpu.random = Random::from_state(reader.u64()?).ok_or(StateError::Corrupt)?;
+        reader.finish()?;
+
+        if cpu.sp > cpu.stack.len() || cpu.input_address >= cpu.v.len() {
+            return Err(StateError::Corrupt);
+        }
+        // Frontends redraw after a restore
+        cpu.vram_changed = true;
+        *self = cpu;
+        Ok(())
+    }
+
+    /// Execute a single instruction.
+    /// Timers are not touched, call `tick_timers` at 60 Hz or use `run_frame`.
+    /// On error the program counter is left at the faulting instruction.
+    pub fn cycle(&mut self, keys: [bool;16]) -> Result<CycleState<'_>, CpuError> {
         self.keys = keys;
         self.vram_changed = false;
-        if self.wait_for_input {
+        self.memory_accesses.clear();
+        if self.halted {
+            // 00FD exited the interpreter, nothing left to run
+        } else if self.wait_for_input {
             for i in 0..self.keys.len() {
                 if self.keys[i] {
                     self.wait_for_input = false;
@@ -100,180 +313,457 @@ impl Cpu {
                     break;
                 }
             }
+        } else if self.vblank_wait {
+            // Dxyn with display wait quirk stalls the cpu until the next tick
         } else {
-            if self.sound_timer > 0 {
-                self.sound_timer -=1
+            let opcode = self.fetch_and_decode_opcode()?; // Decode opcode and set to self.opcode
+            self.run_opcode(opcode)?;
+        }
+
+        Ok(self.state())
+    }
+
+    /// Decrement delay and sound timers. Has to be called at 60 Hz
+    /// independent of how many instructions are executed.
+    pub fn tick_timers(&mut self) {
+        if self.sound_timer > 0 {
+            self.sound_timer -= 1
+        }
+        if self.delay_timer > 0 {
+            self.delay_timer -= 1
+        }
+        self.vblank_wait = false;
+    }
+
+    /// Run one 60 Hz frame: execute `instructions_per_frame` instructions
+    /// and tick the timers once. vram_changed tells if any instruction
+    /// in the frame changed the display.
+    pub fn run_frame(&mut self, keys: [bool; 16], instructions_per_frame: usize) -> Result<CycleState<'_>, CpuError> {
+        let mut vram_changed = false;
+        for _ in 0..instructions_per_frame {
+            vram_changed |= self.cycle(keys)?.vram_changed;
+            if self.halted {
+                break;
             }
-            if self.delay_timer > 0 {
-                self.delay_timer -= 1
+        }
+        self.tick_timers();
+        self.vram_changed = vram_changed;
+
+        Ok(self.state())
+    }
+
+    /// Program counter
+    pub fn pc(&self) -> usize {
+        self.pc
+    }
+
+    /// Index register
+    pub fn i(&self) -> usize {
+        self.i
+    }
+
+    /// V0-VF
+    pub fn registers(&self) -> &[u8; 16] {
+        &self.v
+    }
+
+    /// Return addresses on the stack, oldest first
+    pub fn stack(&self) -> &[usize] {
+        &self.stack[..self.sp]
+    }
+
+    pub fn sp(&self) -> usize {
+        self.sp
+    }
+
+    /// The whole address space
+    pub fn memory(&self) -> &[u8] {
+        &self.memory
+    }
+
+    /// Opcode at address, None past the end of memory
+    pub fn opcode_at(&self, address: usize) -> Option<u16> {
+        match (self.memory.get(address), self.memory.get(address + 1)) {
+            (Some(&byte1), Some(&byte2)) => Some((byte1 as u16) << 8 | byte2 as u16),
+            _ => None,
+        }
+    }
+
+    /// Set the program counter, for debuggers
+    pub fn set_pc(&mut self, pc: usize) {
+        self.pc = pc;
+    }
+
+    pub fn set_i(&mut self, i: usize) {
+        self.i = i;
+    }
+
+    /// Set Vx, x is 0-F
+    pub fn set_register(&mut self, x: usize, value: u8) {
+        self.v[x] = value;
+    }
+
+    /// Set the stack depth, clamped to the 16 stack levels
+    pub fn set_sp(&mut self, sp: usize) {
+        self.sp = sp.min(self.stack.len());
+    }
+
+    /// Write a byte from outside the program, e.g. a debugger or cheat.
+    /// Returns false past the end of memory.
+    pub fn poke(&mut self, address: usize, value: u8) -> bool {
+        match self.memory.get_mut(address) {
+            Some(byte) => {
+                *byte = value;
+                true
             }
-            let opcode = self.fetch_and_decode_opcode(); // Decode opcode and set to self.opcode
-            self.run_opcode(opcode);
+            None => false,
         }
+    }
+
+    /// Memory read and written by the last cycle, e.g. Fx55 writes, Dxyn
+    /// sprite reads. Empty if the cycle didn't run an instruction.
+    pub fn memory_accesses(&self) -> &[MemoryAccess] {
+        &self.memory_accesses
+    }
 
+    /// Fx0A is waiting for a key press
+    pub fn is_waiting_for_key(&self) -> bool {
+        self.wait_for_input
+    }
+
+    /// State after the last cycle, without running anything
+    pub fn state(&self) -> CycleState<'_> {
         CycleState {
             vram_changed: self.vram_changed,
             vram: &self.vram,
-            sound: self.sound_timer > 0
+            sound: self.sound_timer > 0,
+            audio_pattern: self.audio_pattern,
+            halted: self.halted,
         }
     }
 
     /// Fetch and decode opcodes
     /// Since chip8 opcodes are two bytes long we are combining
     /// Two bytes from memory at pc and pc+1
-    fn fetch_and_decode_opcode(&mut self) -> u16 {
-        let byte1 = (self.memory[self.pc] as u16) << 8;
-        let byte2 = self.memory[self.pc + 1] as u16;
+    fn fetch_and_decode_opcode(&mut self) -> Result<u16, CpuError> {
+        let byte1 = (self.fetch(self.pc)? as u16) << 8;
+        let byte2 = self.fetch(self.pc + 1)? as u16;
         self.opcode = byte1 | byte2;
 
-        self.opcode
-    }
-
-    fn run_opcode(&mut self, opcode: u16) {
-        let nibbles = (
-            (opcode & 0xF000) >> 12,
-            (opcode & 0x0F00) >> 8,
-            (opcode & 0x00F0) >> 4,
-            (opcode & 0x000F)
-        );
-
-        let nnn = (opcode & 0x0FFF) as usize;
-        let kk = (opcode & 0x00FF) as u8;
-        let x = nibbles.1 as usize;
-        let y = nibbles.2 as usize;
-        let n = nibbles.3 as usize;
-
-        let pc_action = match nibbles {
-            (0x00, 0x00, 0x0e, 0x00) => self.op_00e0(), // CLS
-            (0x00, 0x00, 0x0e, 0x0e) => self.op_00ee(), // RET
-            (0x01, _, _, _) => self.op_1nnn(nnn), // JP
-            (0x02, _, _, _) => self.op_2nnn(nnn), // CALL
-            (0x03, _, _, _) => self.op_3xkk(x, kk), // SE Vx, byte
-            (0x04, _, _, _) => self.op_4xkk(x, kk), //SNE Vx, byte
-            (0x05, _, _, 0x00) => self.op_5xy0(x, y), //SE Vx, Vy
-            (0x06, _, _, _) => self.op_6xkk(x, kk), // LD Vx, byte
-            (0x07, _, _, _) => self.op_7xkk(x, kk), // ADD Vx, byte
-            (0x08, _, _, 0x00) => self.op_8xy0(x, y), // LD Vx, Vy
-            (0x08, _, _, 0x01) => self.op_8xy1(x, y), // OR Vx, Vy
-            (0x08, _, _, 0x02) => self.op_8xy2(x, y), // AND Vx, Vy
-            (0x08, _, _, 0x03) => self.op_8xy3(x, y), // XOR Vx, Vy
-            (0x08, _, _, 0x04) => self.op_8xy4(x, y), // ADD Vx, Vy
-            (0x08, _, _, 0x05) => self.op_8xy5(x, y), // SUB Vx, Vy
-            (0x08, _, _, 0x06) => self.op_8xy6(x), // SHR Vx {, Vy}
-            (0x08, _, _, 0x07) => self.op_8xy7(x, y), //  SUBN Vx, Vy
-            (0x08, _, _, 0x0E) => self.op_8xye(x), // SHL Vx {, Vy}
-            (0x09, _, _, 0x00) => self.op_9xy0(x, y), // SNE Vx, Vy
-            (0x0A, _, _, _) => self.op_annn(nnn), // LD I, addr
-            (0x0B, _, _, _) => self.op_bnnn(nnn), // JP V0, addr
-            (0x0C, _, _, _) => self.op_cxkk(x, kk), // RND Vx, byte
-            (0x0D, _, _, _) => self.op_dxyn(x, y, n), //  DRW Vx, Vy, nibble
-            (0x0E, _, 0x09, 0x0E) => self.op_ex9e(x), // SKP Vx
-            (0x0E, _, 0x0A, 0x01) => self.op_exa1(x), // SKNP Vx
-            (0x0f, _, 0x00, 0x07) => self.op_fx07(x), // LD Vx, DT
-            (0x0f, _, 0x00, 0x0A) => self.op_fx0a(x), // LD Vx, K
-            (0x0f, _, 0x01, 0x05) => self.op_fx15(x), // LD DT, Vx
-            (0x0f, _, 0x01, 0x08) => self.op_fx18(x), // LD ST, Vx
-            (0x0f, _, 0x01, 0x0E) => self.op_fx1e(x), // ADD I, Vx,
-            (0x0f, _, 0x02, 0x09) => self.op_fx29(x),
-            (0x0f, _, 0x03, 0x03) => self.op_fx33(x),
-            (0x0f, _, 0x05, 0x05) => self.op_fx55(x),
-            (0x0f, _, 0x06, 0x05) => self.op_fx65(x),
-            _ => PointerAction::Next
-        };
+        Ok(self.opcode)
+    }
+
+    /// Read part of an instruction, not reported as a memory access
+    fn fetch(&self, addr: usize) -> Result<u8, CpuError> {
+        match self.memory.get(addr) {
+            Some(&byte) => Ok(byte),
+            None => Err(CpuError::MemoryOutOfBounds { addr, pc: self.pc }),
+        }
+    }
+
+    fn read_memory(&mut self, addr: usize) -> Result<u8, CpuError> {
+        let byte = self.fetch(addr)?;
+        self.record_access(addr, AccessKind::Read);
+        Ok(byte)
+    }
+
+    fn write_memory(&mut self, addr: usize, value: u8) -> Result<(), CpuError> {
+        let pc = self.pc;
+        match self.memory.get_mut(addr) {
+            Some(byte) => {
+                *byte = value;
+                self.record_access(addr, AccessKind::Write);
+                Ok(())
+            }
+            None => Err(CpuError::MemoryOutOfBounds { addr, pc }),
+        }
+    }
+
+    /// Fail before touching anything if `length` bytes from `addr` don't fit
+    /// in memory, so an instruction never stops halfway through
+    fn check_range(&self, addr: usize, length: usize) -> Result<(), CpuError> {
+        if addr + length > self.memory.len() {
+            return Err(CpuError::MemoryOutOfBounds { addr: addr.max(self.memory.len()), pc: self.pc });
+        }
+        Ok(())
+    }
+
+    fn record_access(&mut self, address: usize, kind: AccessKind) {
+        if let Some(last) = self.memory_accesses.last_mut() {
+            if last.kind == kind && last.address + last.length == address {
+                last.length += 1;
+                return;
+            }
+        }
+        self.memory_accesses.push(MemoryAccess { address, length: 1, kind });
+    }
+
+    /// Key state for key index stored in a register
+    fn key(&self, key: u8) -> Result<bool, CpuError> {
+        match self.keys.get(key as usize) {
+            Some(&pressed) => Ok(pressed),
+            None => Err(CpuError::InvalidKey { key, pc: self.pc }),
+        }
+    }
+
+    fn run_opcode(&mut self, opcode: u16) -> Result<(), CpuError> {
+        let pc_action = self.execute(Instruction::decode(opcode))?;
 
         match pc_action {
             PointerAction::Next => self.pc += 2,
-            PointerAction::Skip => self.pc += 4,
+            PointerAction::Skip => self.pc += 2 + self.next_instruction_length(),
             PointerAction::Jump(address) => self.pc = address,
         }
+        Ok(())
+    }
+
+    /// Length in bytes of the instruction after the current one.
+    /// XO-CHIP F000 NNNN is four bytes long, skips have to jump over all of it.
+    fn next_instruction_length(&self) -> usize {
+        let next = self.pc + 2;
+        match (self.memory.get(next), self.memory.get(next + 1)) {
+            (Some(&byte1), Some(&byte2)) => Instruction::decode((byte1 as u16) << 8 | byte2 as u16).size(),
+            _ => 2,
+        }
+    }
+
+    fn execute(&mut self, instruction: Instruction) -> Result<PointerAction, CpuError> {
+        match instruction {
+            Instruction::Scd(n) => self.op_00cn(n as usize),
+            Instruction::Cls => self.op_00e0(),
+            Instruction::Ret => self.op_00ee(),
+            Instruction::Scr => self.op_00fb(),
+            Instruction::Scl => self.op_00fc(),
+            Instruction::Exit => self.op_00fd(),
+            Instruction::Low => self.op_00fe(),
+            Instruction::High => self.op_00ff(),
+            Instruction::Jp(nnn) => self.op_1nnn(nnn as usize),
+            Instruction::Call(nnn) => self.op_2nnn(nnn as usize),
+            Instruction::SeVxByte { x, kk } => self.op_3xkk(x as usize, kk),
+            Instruction::SneVxByte { x, kk } => self.op_4xkk(x as usize, kk),
+            Instruction::SeVxVy { x, y } => self.op_5xy0(x as usize, y as usize),
+            Instruction::Save { x, y } => self.op_5xy2(x as usize, y as usize),
+            Instruction::Load { x, y } => self.op_5xy3(x as usize, y as usize),
+            Instruction::LdVxByte { x, kk } => self.op_6xkk(x as usize, kk),
+            Instruction::AddVxByte { x, kk } => self.op_7xkk(x as usize, kk),
+            Instruction::LdVxVy { x, y } => self.op_8xy0(x as usize, y as usize),
+            Instruction::Or { x, y } => self.op_8xy1(x as usize, y as usize),
+            Instruction::And { x, y } => self.op_8xy2(x as usize, y as usize),
+            Instruction::Xor { x, y } => self.op_8xy3(x as usize, y as usize),
+            Instruction::AddVxVy { x, y } => self.op_8xy4(x as usize, y as usize),
+            Instruction::Sub { x, y } => self.op_8xy5(x as usize, y as usize),
+            Instruction::Shr { x, y } => self.op_8xy6(x as usize, y as usize),
+            Instruction::Subn { x, y } => self.op_8xy7(x as usize, y as usize),
+            Instruction::Shl { x, y } => self.op_8xye(x as usize, y as usize),
+            Instruction::SneVxVy { x, y } => self.op_9xy0(x as usize, y as usize),
+            Instruction::LdI(nnn) => self.op_annn(nnn as usize),
+            Instruction::JpV0(nnn) => self.op_bnnn((nnn >> 8) as usize, nnn as usize),
+            Instruction::Rnd { x, kk } => self.op_cxkk(x as usize, kk),
+            Instruction::Drw { x, y, n } => self.op_dxyn(x as usize, y as usize, n as usize),
+            Instruction::Skp(x) => self.op_ex9e(x as usize),
+            Instruction::Sknp(x) => self.op_exa1(x as usize),
+            Instruction::LdILong => self.op_f000(),
+            Instruction::Plane(n) => self.op_fn01(n as usize),
+            Instruction::Audio => self.op_f002(),
+            Instruction::LdVxDt(x) => self.op_fx07(x as usize),
+            Instruction::LdVxK(x) => self.op_fx0a(x as usize),
+            Instruction::LdDtVx(x) => self.op_fx15(x as usize),
+            Instruction::LdStVx(x) => self.op_fx18(x as usize),
+            Instruction::AddIVx(x) => self.op_fx1e(x as usize),
+            Instruction::LdFVx(x) => self.op_fx29(x as usize),
+            Instruction::LdHfVx(x) => self.op_fx30(x as usize),
+            Instruction::LdBVx(x) => self.op_fx33(x as usize),
+            Instruction::Pitch(x) => self.op_fx3a(x as usize),
+            Instruction::LdMemVx(x) => self.op_fx55(x as usize),
+            Instruction::LdVxMem(x) => self.op_fx65(x as usize),
+            Instruction::LdRVx(x) => self.op_fx75(x as usize),
+            Instruction::LdVxR(x) => self.op_fx85(x as usize),
+            Instruction::Sys(_) | Instruction::Unknown(_) => self.unknown_opcode(instruction.encode()),
+        }
+    }
+
+    /// Opcode not implemented by the interpreter, handled by unknown_opcode_policy
+    fn unknown_opcode(&mut self, opcode: u16) -> Result<PointerAction, CpuError> {
+        let error = CpuError::UnknownOpcode { opcode, pc: self.pc };
+        match self.unknown_opcode_policy {
+            UnknownOpcodePolicy::Ignore => Ok(PointerAction::Next),
+            UnknownOpcodePolicy::Log => {
+                eprintln!("Skipping {}", error);
+                Ok(PointerAction::Next)
+            }
+            UnknownOpcodePolicy::Halt => Err(error),
+        }
+    }
+
+    /// SCD nibble
+    /// Scroll display down by n pixels. (SUPER-CHIP)
+    fn op_00cn(&mut self, n: usize) -> Result<PointerAction, CpuError> {
+        self.vram.scroll_down(n, self.plane);
+        self.vram_changed = true;
+        Ok(PointerAction::Next)
     }
 
     /// CLS
-    /// Clear the display
-    fn op_00e0(&mut self) -> PointerAction {
-        self.vram = [[0; 64]; 32];
+    /// Clear the display. Only the selected XO-CHIP planes are cleared.
+    fn op_00e0(&mut self) -> Result<PointerAction, CpuError> {
+        self.vram.clear_planes(self.plane);
         self.vram_changed = true;
-        PointerAction::Next
+        Ok(PointerAction::Next)
     }
 
     /// RET - Return from a subroutine
     /// The interpreter sets the program counter to the address at the top of the stack,
     /// then subtracts 1 from the stack pointer.
-    fn op_00ee(&mut self) -> PointerAction {
+    fn op_00ee(&mut self) -> Result<PointerAction, CpuError> {
+        if self.sp == 0 {
+            return Err(CpuError::StackUnderflow { pc: self.pc });
+        }
         self.sp -= 1;
-        PointerAction::Jump(self.stack[self.sp])
+        Ok(PointerAction::Jump(self.stack[self.sp]))
+    }
+
+    /// SCR
+    /// Scroll display right by 4 pixels. (SUPER-CHIP)
+    fn op_00fb(&mut self) -> Result<PointerAction, CpuError> {
+        self.vram.scroll_right(4, self.plane);
+        self.vram_changed = true;
+        Ok(PointerAction::Next)
+    }
+
+    /// SCL
+    /// Scroll display left by 4 pixels. (SUPER-CHIP)
+    fn op_00fc(&mut self) -> Result<PointerAction, CpuError> {
+        self.vram.scroll_left(4, self.plane);
+        self.vram_changed = true;
+        Ok(PointerAction::Next)
+    }
+
+    /// EXIT
+    /// Exit the interpreter. (SUPER-CHIP)
+    /// The program counter stays on this instruction and the cpu halts.
+    fn op_00fd(&mut self) -> Result<PointerAction, CpuError> {
+        self.halted = true;
+        Ok(PointerAction::Jump(self.pc))
+    }
+
+    /// LOW
+    /// Disable high resolution mode, display is 64x32. (SUPER-CHIP)
+    fn op_00fe(&mut self) -> Result<PointerAction, CpuError> {
+        self.vram.set_hires(false);
+        self.vram_changed = true;
+        Ok(PointerAction::Next)
+    }
+
+    /// HIGH
+    /// Enable high resolution mode, display is 128x64. (SUPER-CHIP)
+    fn op_00ff(&mut self) -> Result<PointerAction, CpuError> {
+        self.vram.set_hires(true);
+        self.vram_changed = true;
+        Ok(PointerAction::Next)
     }
 
     /// JP addr
     /// Jump to location nnn.
     /// The interpreter sets the program counter to nnn.
-    fn op_1nnn(&mut self, nnn: usize) -> PointerAction {
-        PointerAction::Jump(nnn)
+    fn op_1nnn(&mut self, nnn: usize) -> Result<PointerAction, CpuError> {
+        Ok(PointerAction::Jump(nnn))
     }
 
     /// CALL addr
     /// The interpreter increments the stack pointer,
     /// then puts the current PC on the top of the stack. The PC is then set to nnn.
-    fn op_2nnn(&mut self, nnn: usize) -> PointerAction {
+    fn op_2nnn(&mut self, nnn: usize) -> Result<PointerAction, CpuError> {
+        if self.sp == self.stack.len() {
+            return Err(CpuError::StackOverflow { pc: self.pc });
+        }
         self.stack[self.sp] = self.pc + 2;
         self.sp += 1;
-        PointerAction::Jump(nnn)
+        Ok(PointerAction::Jump(nnn))
     }
 
     /// 3xkk - SE Vx, byte
     ///  Skip next instruction if Vx = kk.
     /// The interpreter compares register Vx to kk, and if they are equal, increments the program counter by 2.
-    fn op_3xkk(&mut self, x: usize, kk: u8) -> PointerAction {
-        PointerAction::skip_or_next((self.v[x] == kk))
+    fn op_3xkk(&mut self, x: usize, kk: u8) -> Result<PointerAction, CpuError> {
+        Ok(PointerAction::skip_or_next(self.v[x] == kk))
     }
 
     ///SNE Vx, byte
     /// Skip next instruction if Vx != kk.
     ///The interpreter compares register Vx to kk, and if they are not equal, increments the program counter by 2.
-    fn op_4xkk(&mut self, x: usize, kk: u8) -> PointerAction {
-        PointerAction::skip_or_next((self.v[x] != kk))
+    fn op_4xkk(&mut self, x: usize, kk: u8) -> Result<PointerAction, CpuError> {
+        Ok(PointerAction::skip_or_next(self.v[x] != kk))
     }
 
     /// SE Vx, Vy
     /// Skip next instruction if Vx = Vy.
     /// The interpreter compares register Vx to register Vy, and if they are equal, increments the program counter by 2.
-    fn op_5xy0(&mut self, x: usize, y: usize) -> PointerAction {
-        PointerAction::skip_or_next((self.v[x] == self.v[y]))
+    fn op_5xy0(&mut self, x: usize, y: usize) -> Result<PointerAction, CpuError> {
+        Ok(PointerAction::skip_or_next(self.v[x] == self.v[y]))
+    }
+
+    /// SAVE Vx - Vy
+    /// Store registers Vx through Vy in memory starting at I, I is not changed. (XO-CHIP)
+    /// The range can be given in either order.
+    fn op_5xy2(&mut self, x: usize, y: usize) -> Result<PointerAction, CpuError> {
+        let count = x.abs_diff(y);
+        self.check_range(self.i, count + 1)?;
+        for offset in 0..count + 1 {
+            let register = if x <= y { x + offset } else { x - offset };
+            self.write_memory(self.i + offset, self.v[register])?;
+        }
+        Ok(PointerAction::Next)
+    }
+
+    /// LOAD Vx - Vy
+    /// Read registers Vx through Vy from memory starting at I, I is not changed. (XO-CHIP)
+    /// The range can be given in either order.
+    fn op_5xy3(&mut self, x: usize, y: usize) -> Result<PointerAction, CpuError> {
+        let count = x.abs_diff(y);
+        self.check_range(self.i, count + 1)?;
+        for offset in 0..count + 1 {
+            let register = if x <= y { x + offset } else { x - offset };
+            self.v[register] = self.read_memory(self.i + offset)?;
+        }
+        Ok(PointerAction::Next)
     }
 
     /// LD Vx, byte
     /// Set Vx = kk.
     /// The interpreter puts the value kk into register Vx.
-    fn op_6xkk(&mut self, x: usize, kk: u8) -> PointerAction {
+    fn op_6xkk(&mut self, x: usize, kk: u8) -> Result<PointerAction, CpuError> {
         self.v[x] = kk;
-        PointerAction::Next
+        Ok(PointerAction::Next)
     }
 
     /// ADD Vx, byte
     ///  Set Vx = Vx + kk.
     ///  Adds the value kk to the value of register Vx, then stores the result in Vx.
-    fn op_7xkk(&mut self, x: usize, kk: u8) -> PointerAction {
+    fn op_7xkk(&mut self, x: usize, kk: u8) -> Result<PointerAction, CpuError> {
         // TODO: Not implemented correctly
         self.v[x] = (self.v[x] as u16 + kk as u16) as u8;
-        PointerAction::Next
+        Ok(PointerAction::Next)
     }
 
     /// LD Vx, Vy
     /// Set Vx = Vy.
     ///  Stores the value of register Vy in register Vx.
-    fn op_8xy0(&mut self, x: usize, y: usize) -> PointerAction {
+    fn op_8xy0(&mut self, x: usize, y: usize) -> Result<PointerAction, CpuError> {
         self.v[x] = self.v[y];
-        PointerAction::Next
+        Ok(PointerAction::Next)
     }
 
     /// OR Vx, Vy
     ///  Set Vx = Vx OR Vy.
     ///  Performs a bitwise OR on the values of Vx and Vy,
     /// then stores the result in Vx. A bitwise OR compares the corrseponding bits from two values, and if either bit is 1, then the same bit in the result is also 1. Otherwise, it is 0.
-    fn op_8xy1(&mut self, x: usize, y: usize) -> PointerAction {
+    fn op_8xy1(&mut self, x: usize, y: usize) -> Result<PointerAction, CpuError> {
         let value = self.v[x] | self.v[y];
         self.v[x] = value;
-        PointerAction::Next
+        if self.quirks.logic_resets_vf {
+            self.v[0x0F] = 0;
+        }
+        Ok(PointerAction::Next)
     }
 
     /// AND Vx, Vy
@@ -281,10 +771,13 @@ impl Cpu {
     ///  Performs a bitwise AND on the values of Vx and Vy,
     /// then stores the result in Vx. A bitwise AND compares the
     /// corrseponding bits from two values, and if both bits are 1, then the same bit in the result is also 1. Otherwise, it is 0.
-    fn op_8xy2(&mut self, x: usize, y: usize) -> PointerAction {
+    fn op_8xy2(&mut self, x: usize, y: usize) -> Result<PointerAction, CpuError> {
         let value = self.v[x] & self.v[y];
         self.v[x] = value;
-        PointerAction::Next
+        if self.quirks.logic_resets_vf {
+            self.v[0x0F] = 0;
+        }
+        Ok(PointerAction::Next)
     }
 
     /// XOR Vx, Vy
@@ -292,202 +785,304 @@ impl Cpu {
     ///  Performs a bitwise exclusive OR on the values of Vx and Vy,
     /// then stores the result in Vx. An exclusive OR compares the
     /// corrseponding bits from two values, and if the bits are not both the same, then the corresponding bit in the result is set to 1. Otherwise, it is 0.
-    fn op_8xy3(&mut self, x: usize, y: usize) -> PointerAction {
+    fn op_8xy3(&mut self, x: usize, y: usize) -> Result<PointerAction, CpuError> {
         let value = self.v[x] ^ self.v[y];
         self.v[x] = value;
-        PointerAction::Next
+        if self.quirks.logic_resets_vf {
+            self.v[0x0F] = 0;
+        }
+        Ok(PointerAction::Next)
     }
 
     /// ADD Vx, Vy
     ///  Set Vx = Vx + Vy, set VF = carry.
     ///  The values of Vx and Vy are added together. If the result is greater
     /// than 8 bits (i.e., > 255,) VF is set to 1, otherwise 0. Only the lowest 8 bits of the result are kept, and stored in Vx.
-    fn op_8xy4(&mut self, x: usize, y: usize) -> PointerAction {
-        // TODO: Validate this
-        let value = self.v[x] as u16 + self.v[y] as u16;
-        if value > 0xFF {
-            self.v[0x0F] = 1;
-        } else {
-            self.v[0x0F] = 0;
-        }
-        self.v[x] = value as u8;
-        PointerAction::Next
+    fn op_8xy4(&mut self, x: usize, y: usize) -> Result<PointerAction, CpuError> {
+        let (value, carry) = self.v[x].overflowing_add(self.v[y]);
+        // VF is written last so the flag wins when x is F
+        self.v[x] = value;
+        self.v[0x0F] = carry as u8;
+        Ok(PointerAction::Next)
     }
 
     /// SUB Vx, Vy
     /// Set Vx = Vx - Vy, set VF = NOT borrow.
-    /// If Vx > Vy, then VF is set to 1, otherwise 0. Then Vy is subtracted from Vx, and the results stored in Vx.
-    fn op_8xy5(&mut self, x: usize, y: usize) -> PointerAction {
-        self.v[0x0F] = if self.v[x] > self.v[y] { 1 } else { 0 };
+    /// If Vx >= Vy, then VF is set to 1, otherwise 0. Then Vy is subtracted from Vx, and the results stored in Vx.
+    fn op_8xy5(&mut self, x: usize, y: usize) -> Result<PointerAction, CpuError> {
+        let no_borrow = self.v[x] >= self.v[y];
         self.v[x] = self.v[x].wrapping_sub(self.v[y]);
-        PointerAction::Next
+        self.v[0x0F] = no_borrow as u8;
+        Ok(PointerAction::Next)
     }
 
     /// SHR Vx {, Vy}
     ///  Set Vx = Vx SHR 1.
     ///  If the least-significant bit of Vx is 1, then VF is set to 1, otherwise 0. Then Vx is divided by 2.
-    fn op_8xy6(&mut self, x: usize) -> PointerAction {
-        self.v[0x0F] = if self.v[x] & 1 == 1 { 1 } else { 0 };
+    /// With quirks.shift_uses_vy Vy is copied to Vx before shifting.
+    fn op_8xy6(&mut self, x: usize, y: usize) -> Result<PointerAction, CpuError> {
+        if self.quirks.shift_uses_vy {
+            self.v[x] = self.v[y];
+        }
+        let shifted_out = self.v[x] & 1;
         self.v[x] >>= 1;
-        PointerAction::Next
+        self.v[0x0F] = shifted_out;
+        Ok(PointerAction::Next)
     }
 
     /// SUBN Vx, Vy
     ///  Set Vx = Vy - Vx, set VF = NOT borrow.
-    ///  If Vy > Vx, then VF is set to 1, otherwise 0. Then Vx is subtracted from Vy, and the results stored in Vx.
-    fn op_8xy7(&mut self, x: usize, y: usize) -> PointerAction {
-        self.v[0x0F] = if self.v[x] < self.v[y] { 1 } else { 0 };
+    ///  If Vy >= Vx, then VF is set to 1, otherwise 0. Then Vx is subtracted from Vy, and the results stored in Vx.
+    fn op_8xy7(&mut self, x: usize, y: usize) -> Result<PointerAction, CpuError> {
+        let no_borrow = self.v[x] <= self.v[y];
         self.v[x] = self.v[y].wrapping_sub(self.v[x]);
-        PointerAction::Next
+        self.v[0x0F] = no_borrow as u8;
+        Ok(PointerAction::Next)
     }
 
     /// SHL Vx {, Vy}
     ///  Set Vx = Vx SHL 1.
     ///  If the most-significant bit of Vx is 1, then VF is set to 1, otherwise to 0. Then Vx is multiplied by 2.
-    fn op_8xye(&mut self, x: usize) -> PointerAction {
-        self.v[0x0F] = (self.v[x] & 0b10000000) >> 7;
+    /// With quirks.shift_uses_vy Vy is copied to Vx before shifting.
+    fn op_8xye(&mut self, x: usize, y: usize) -> Result<PointerAction, CpuError> {
+        if self.quirks.shift_uses_vy {
+            self.v[x] = self.v[y];
+        }
+        let shifted_out = (self.v[x] & 0b10000000) >> 7;
         self.v[x] <<= 1;
-        PointerAction::Next
+        self.v[0x0F] = shifted_out;
+        Ok(PointerAction::Next)
     }
 
     /// SNE Vx, Vy
     ///  Skip next instruction if Vx != Vy.
     ///  The values of Vx and Vy are compared, and if they are not equal, the program counter is increased by 2.
-    fn op_9xy0(&mut self, x: usize, y: usize) -> PointerAction {
-        PointerAction::skip_or_next((self.v[x] != self.v[y]))
+    fn op_9xy0(&mut self, x: usize, y: usize) -> Result<PointerAction, CpuError> {
+        Ok(PointerAction::skip_or_next(self.v[x] != self.v[y]))
     }
 
     /// LD I, addr
     /// Set I = nnn.
-    fn op_annn(&mut self, nnn: usize) -> PointerAction {
+    fn op_annn(&mut self, nnn: usize) -> Result<PointerAction, CpuError> {
         self.i = nnn;
-        PointerAction::Next
+        Ok(PointerAction::Next)
     }
 
     /// JP V0, addr
     ///  Jump to location nnn + V0.
-    fn op_bnnn(&mut self, nnn: usize) -> PointerAction {
-        PointerAction::Jump((nnn as u8 + self.v[0x00]) as usize)
+    ///  With quirks.jump_uses_vx (CHIP-48/SCHIP BXNN) the jump is nnn + Vx.
+    fn op_bnnn(&mut self, x: usize, nnn: usize) -> Result<PointerAction, CpuError> {
+        let offset = if self.quirks.jump_uses_vx { self.v[x] } else { self.v[0x00] };
+        Ok(PointerAction::Jump((nnn + offset as usize) & 0x0FFF))
     }
 
     /// RND Vx, byte
     /// Set Vx = random byte AND kk.
-    fn op_cxkk(&mut self, x: usize, kk: u8) -> PointerAction {
-        // TODO: Validate
-        let mut rng = rand::thread_rng();
-        let random_number = rng.gen_range(0, 255);
-        self.v[x] = random_number & kk;
-        PointerAction::Next
+    fn op_cxkk(&mut self, x: usize, kk: u8) -> Result<PointerAction, CpuError> {
+        self.v[x] = self.random.next_u8() & kk;
+        Ok(PointerAction::Next)
     }
 
     /// DRW Vx, Vy, nibble
     ///  Display n-byte sprite starting at memory location I at (Vx, Vy), set VF = collision.
-    fn op_dxyn(&mut self, x: usize, y: usize, n: usize) -> PointerAction {
+    ///  When n is 0 a 16x16 sprite of 32 bytes is drawn instead. (SUPER-CHIP)
+    ///  With both XO-CHIP planes selected the sprite data for the second plane
+    ///  follows the data for the first one.
+    ///  The starting position always wraps, pixels going over the edge wrap
+    ///  or get clipped depending on quirks.clip_sprites.
+    fn op_dxyn(&mut self, x: usize, y: usize, n: usize) -> Result<PointerAction, CpuError> {
+        let (sprite_width, sprite_height) = if n == 0 { (16, 16) } else { (8, n) };
+        let sprite_bytes = sprite_height * sprite_width / 8;
+        // One sprite per selected plane, one after another from I
+        self.check_range(self.i, sprite_bytes * self.plane.count_ones() as usize)?;
         self.v[0x0F] = 0;
+        let width = self.vram.width();
+        let height = self.vram.height();
+        let x0 = self.v[x] as usize % width;
+        let y0 = self.v[y] as usize % height;
+        let mut address = self.i;
 
-        for byte in 0..n {
-            let y = (self.v[y] as usize + byte) % 32;
-            for bit in 0..8 {
-                let x = (self.v[x] as usize + bit) % 64;
-                let pixel = (self.memory[self.i + byte] >> (7 - bit as u8)) & 1;
-                self.v[0x0f] |= pixel & self.vram[y][x];
-                self.vram[y][x] ^= pixel;
+        for plane in 0..PLANES {
+            if self.plane & (1 << plane) == 0 {
+                continue;
             }
+            for row in 0..sprite_height {
+                if self.quirks.clip_sprites && y0 + row >= height {
+                    break;
+                }
+                let y = (y0 + row) % height;
+                // Left align sprite row to 16 bits
+                let bits = if sprite_width == 16 {
+                    (self.read_memory(address + row * 2)? as u16) << 8 | self.read_memory(address + row * 2 + 1)? as u16
+                } else {
+                    (self.read_memory(address + row)? as u16) << 8
+                };
+                if self.vram.draw_sprite_row(plane, x0, y, bits, sprite_width, self.quirks.clip_sprites) {
+                    self.v[0x0f] = 1;
+                }
+            }
+            address += sprite_bytes;
         }
         self.vram_changed = true;
-        PointerAction::Next
+        self.vblank_wait = self.quirks.display_wait;
+        Ok(PointerAction::Next)
     }
 
     ///  Ex9E - SKP Vx
     ///  Skip next instruction if key with the value of Vx is pressed.
-    fn op_ex9e(&mut self, x: usize) -> PointerAction {
-        PointerAction::skip_or_next(self.keys[self.v[x] as usize])
+    fn op_ex9e(&mut self, x: usize) -> Result<PointerAction, CpuError> {
+        Ok(PointerAction::skip_or_next(self.key(self.v[x])?))
     }
 
     /// ExA1 - SKNP Vx
     /// Skip next instruction if key with the value of Vx is not pressed.
-    fn op_exa1(&mut self, x: usize) -> PointerAction {
-        PointerAction::skip_or_next(!self.keys[self.v[x] as usize])
+    fn op_exa1(&mut self, x: usize) -> Result<PointerAction, CpuError> {
+        Ok(PointerAction::skip_or_next(!self.key(self.v[x])?))
+    }
+
+    /// LD I, long addr
+    /// Set I = the 16 bit address stored in the next two bytes. (XO-CHIP)
+    /// The instruction is four bytes long.
+    fn op_f000(&mut self) -> Result<PointerAction, CpuError> {
+        let byte1 = (self.fetch(self.pc + 2)? as usize) << 8;
+        let byte2 = self.fetch(self.pc + 3)? as usize;
+        self.i = byte1 | byte2;
+        Ok(PointerAction::Jump(self.pc + 4))
+    }
+
+    /// PLANE n
+    /// Select the bitplanes n (0-3) used by drawing, clearing and scrolling. (XO-CHIP)
+    fn op_fn01(&mut self, n: usize) -> Result<PointerAction, CpuError> {
+        self.plane = n as u8 & 0x03;
+        Ok(PointerAction::Next)
+    }
+
+    /// AUDIO
+    /// Load the 16 byte audio pattern buffer from memory at I. (XO-CHIP)
+    fn op_f002(&mut self) -> Result<PointerAction, CpuError> {
+        let mut pattern = self.audio_pattern.unwrap_or_default();
+        for (offset, byte) in pattern.buffer.iter_mut().enumerate() {
+            *byte = self.read_memory(self.i + offset)?;
+        }
+        self.audio_pattern = Some(pattern);
+        Ok(PointerAction::Next)
     }
 
     /// LD Vx, DT
     /// Set Vx = delay timer value.
-    fn op_fx07(&mut self, x: usize) -> PointerAction {
+    fn op_fx07(&mut self, x: usize) -> Result<PointerAction, CpuError> {
         self.v[x] = self.delay_timer;
-        PointerAction::Next
+        Ok(PointerAction::Next)
     }
 
     /// LD Vx, K
     ///  Wait for a key press, store the value of the key in Vx.
-    fn op_fx0a(&mut self, x: usize) -> PointerAction {
+    fn op_fx0a(&mut self, x: usize) -> Result<PointerAction, CpuError> {
         self.wait_for_input = true;
         self.input_address = x;
-        PointerAction::Next
+        Ok(PointerAction::Next)
     }
 
     /// LD DT, Vx
     /// Set delay timer = Vx.
-    fn op_fx15(&mut self, x: usize) -> PointerAction {
+    fn op_fx15(&mut self, x: usize) -> Result<PointerAction, CpuError> {
         self.delay_timer = self.v[x];
-        PointerAction::Next
+        Ok(PointerAction::Next)
     }
 
     /// LD ST, Vx
     /// Set delay timer = Vx.
-    fn op_fx18(&mut self, x: usize) -> PointerAction {
+    fn op_fx18(&mut self, x: usize) -> Result<PointerAction, CpuError> {
         self.sound_timer = self.v[x];
-        PointerAction::Next
+        Ok(PointerAction::Next)
     }
 
     /// ADD I, Vx
     /// Set I = i + v[x]
-    fn op_fx1e(&mut self, x: usize) -> PointerAction{
+    fn op_fx1e(&mut self, x: usize) -> Result<PointerAction, CpuError> {
         // TODO: Validate
         self.i += self.v[x] as usize;
-        PointerAction::Next
+        Ok(PointerAction::Next)
     }
 
     /// LD F, Vx
     /// Set I = location of sprite for digit Vx.
-    fn op_fx29(&mut self, x: usize) -> PointerAction {
+    fn op_fx29(&mut self, x: usize) -> Result<PointerAction, CpuError> {
         self.i = (self.v[x] as usize) * 5;
-        PointerAction::Next
+        Ok(PointerAction::Next)
+    }
+
+    /// LD HF, Vx
+    /// Set I = location of the 10 byte high resolution sprite for digit Vx. (SUPER-CHIP,
+    /// digits A-F from XO-CHIP)
+    fn op_fx30(&mut self, x: usize) -> Result<PointerAction, CpuError> {
+        self.i = BIG_FONT_ADDRESS + (self.v[x] as usize & 0x0F) * 10;
+        Ok(PointerAction::Next)
     }
 
     /// LD B, Vx
     /// The interpreter takes the decimal value of Vx, and places
     /// the hundreds digit in memory at location in I, the tens digit
     /// at location I+1, and the ones digit at location I+2.
-    fn op_fx33(&mut self, x: usize) -> PointerAction {
-        self.memory[self.i] = self.v[x] / 100;
-        self.memory[self.i + 1] = (self.v[x] % 100) / 10;
-        self.memory[self.i + 2] = self.v[x] % 10;
-        PointerAction::Next
+    fn op_fx33(&mut self, x: usize) -> Result<PointerAction, CpuError> {
+        self.check_range(self.i, 3)?;
+        self.write_memory(self.i, self.v[x] / 100)?;
+        self.write_memory(self.i + 1, (self.v[x] % 100) / 10)?;
+        self.write_memory(self.i + 2, self.v[x] % 10)?;
+        Ok(PointerAction::Next)
+    }
+
+    /// PITCH Vx
+    /// Set the audio pattern playback rate to 4000*2^((Vx-64)/48) Hz. (XO-CHIP)
+    fn op_fx3a(&mut self, x: usize) -> Result<PointerAction, CpuError> {
+        let mut pattern = self.audio_pattern.unwrap_or_default();
+        pattern.pitch = self.v[x];
+        self.audio_pattern = Some(pattern);
+        Ok(PointerAction::Next)
     }
 
     /// LD [I], Vx
     /// The interpreter copies the values of registers V0 through Vx
     /// into memory, starting at the address in I.
-    fn op_fx55(&mut self, x: usize) -> PointerAction {
+    /// I moves on by quirks.load_store_increment.
+    fn op_fx55(&mut self, x: usize) -> Result<PointerAction, CpuError> {
+        self.check_range(self.i, x + 1)?;
         for i in 0..x + 1 {
-            self.memory[self.i + i] = self.v[i]
+            self.write_memory(self.i + i, self.v[i])?;
         }
-        PointerAction::Next
+        self.i += self.quirks.load_store_increment.amount(x);
+        Ok(PointerAction::Next)
     }
 
     /// LD Vx, [I]
     /// The interpreter reads values from memory starting at location
     /// I into registers V0 through Vx.
-    fn op_fx65(&mut self, x: usize) -> PointerAction {
+    /// I moves on by quirks.load_store_increment.
+    fn op_fx65(&mut self, x: usize) -> Result<PointerAction, CpuError> {
+        self.check_range(self.i, x + 1)?;
         for i in 0..x + 1 {
-            self.v[i] = self.memory[self.i + i]
+            self.v[i] = self.read_memory(self.i + i)?;
         }
-        PointerAction::Next
+        self.i += self.quirks.load_store_increment.amount(x);
+        Ok(PointerAction::Next)
+    }
 
+    /// LD R, Vx
+    /// Store V0 through Vx in the RPL user flags. (SUPER-CHIP)
+    fn op_fx75(&mut self, x: usize) -> Result<PointerAction, CpuError> {
+        self.rpl[..x + 1].copy_from_slice(&self.v[..x + 1]);
+        Ok(PointerAction::Next)
     }
 
+    /// LD Vx, R
+    /// Read V0 through Vx from the RPL user flags. (SUPER-CHIP)
+    fn op_fx85(&mut self, x: usize) -> Result<PointerAction, CpuError> {
+        self.v[..x + 1].copy_from_slice(&self.rpl[..x + 1]);
+        Ok(PointerAction::Next)
+    }
 }
 
 #[cfg(test)]
 #[path = "./cpu_tests.rs"]
+#[allow(clippy::bool_assert_comparison, clippy::mixed_case_hex_literals)]
 mod cpu_tests;
\ No newline at end of file
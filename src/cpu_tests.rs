@@ -1,25 +1,32 @@
 use super::*;
+use crate::quirks::IndexIncrement;
 
 const PC: usize = 0x200;
 
 fn new_cpu_with_inital_data() -> Cpu {
-    let mut cpu = Cpu::new();
+    let mut cpu = Cpu::new(Quirks::default());
     cpu.pc = PC;
     cpu.v = [0, 0, 1, 1, 3, 4, 5, 6, 7, 8, 4, 4, 2, 1, 3, 4];
     cpu
 }
 
 
+fn new_cpu_with_quirks(quirks: Quirks) -> Cpu {
+    let mut cpu = Cpu::new(quirks);
+    cpu.v = [0, 0, 1, 1, 3, 4, 5, 6, 7, 8, 4, 4, 2, 1, 3, 4];
+    cpu
+}
+
 fn set_register_values_and_run(mut cpu: Cpu, x: u8, y: u8, opcode: u16) -> Cpu {
     cpu.v[0] = x;
     cpu.v[1] = y;
-    cpu.run_opcode(opcode);
+    cpu.run_opcode(opcode).unwrap();
     cpu
 }
 
 #[test]
 fn test_cpu_intialization() {
-    let cpu = Cpu::new();
+    let cpu = Cpu::new(Quirks::default());
     assert_eq!(cpu.opcode, 0);
     assert_eq!(cpu.pc, 0x200); // Test pc location
     assert_eq!(cpu.memory[0x00], 0xF0); // Test font set
@@ -29,7 +36,7 @@ fn test_cpu_intialization() {
 
 #[test]
 fn test_load_data() {
-    let mut cpu = Cpu::new();
+    let mut cpu = Cpu::new(Quirks::default());
     let data = [0, 0, 33, 43];
     cpu.read_data_to_memory(&data);
 
@@ -42,26 +49,80 @@ fn test_load_data() {
 
 #[test]
 fn test_delay_and_sound_timer() {
-    let mut cpu = Cpu::new();
+    let mut cpu = Cpu::new(Quirks::default());
     cpu.sound_timer = 10;
     cpu.delay_timer = 10;
-    cpu.cycle();
+    cpu.tick_timers();
     assert_eq!(cpu.sound_timer, 9);
     assert_eq!(cpu.delay_timer, 9);
+
+    cpu.sound_timer = 1;
+    cpu.delay_timer = 0;
+    cpu.tick_timers();
+    assert_eq!(cpu.sound_timer, 0);
+    assert_eq!(cpu.delay_timer, 0);
+}
+
+#[test]
+fn test_cycle_does_not_tick_timers() {
+    let mut cpu = Cpu::new(Quirks::default());
+    cpu.sound_timer = 10;
+    cpu.delay_timer = 10;
+    for _ in 0..100 {
+        cpu.cycle([false; 16]).unwrap();
+    }
+    assert_eq!(cpu.sound_timer, 10);
+    assert_eq!(cpu.delay_timer, 10);
+}
+
+#[test]
+fn test_run_frame_ticks_timers_once() {
+    let mut cpu = Cpu::new(Quirks::default());
+    cpu.read_data_to_memory(&[0x12, 0x00]); // JP 0x200, loop forever
+    cpu.delay_timer = 60;
+    for instructions_per_frame in [1, 10, 1000].iter() {
+        cpu.run_frame([false; 16], *instructions_per_frame).unwrap();
+    }
+    assert_eq!(cpu.delay_timer, 57);
+
+    // One second of frames empties the timer regardless of cpu speed
+    for _ in 0..57 {
+        cpu.run_frame([false; 16], 30).unwrap();
+    }
+    assert_eq!(cpu.delay_timer, 0);
+}
+
+#[test]
+fn test_run_frame_keeps_vram_changed() {
+    let mut cpu = Cpu::new(Quirks::default());
+    cpu.read_data_to_memory(&[0x00, 0xe0, 0x12, 0x02]); // CLS, then loop
+    assert!(cpu.run_frame([false; 16], 10).unwrap().vram_changed);
+    assert!(!cpu.run_frame([false; 16], 10).unwrap().vram_changed);
+}
+
+#[test]
+fn test_timers_tick_while_waiting_for_key() {
+    let mut cpu = Cpu::new(Quirks::default());
+    cpu.read_data_to_memory(&[0xf0, 0x0a]);
+    cpu.delay_timer = 5;
+    cpu.run_frame([false; 16], 10).unwrap();
+    assert!(cpu.wait_for_input);
+    assert_eq!(cpu.delay_timer, 4);
 }
 
 
 // CLS = Clear the display
 #[test]
 fn test_op00e0() {
-    let mut cpu = Cpu::new();
-    cpu.vram = [[128; 64]; 32];
-    cpu.run_opcode(0x00e0);
+    let mut cpu = Cpu::new(Quirks::default());
+    cpu.vram.set_pixel(0, 0, 1);
+    cpu.vram.set_pixel(63, 31, 1);
+    cpu.run_opcode(0x00e0).unwrap();
     assert_eq!(cpu.vram_changed, true);
 
-    for (y, _row) in cpu.vram.iter().enumerate() {
-        for (x, _row) in cpu.vram[y].iter().enumerate() {
-            assert_eq!(cpu.vram[y][x], 0)
+    for row in cpu.vram.rows() {
+        for pixel in row {
+            assert_eq!(pixel, 0)
         }
     }
 }
@@ -69,10 +130,10 @@ fn test_op00e0() {
 // RET
 #[test]
 fn test_op00ee() {
-    let mut cpu = Cpu::new();
+    let mut cpu = Cpu::new(Quirks::default());
     cpu.sp = 4;
     cpu.stack[3] = 0x664;
-    cpu.run_opcode(0x00ee);
+    cpu.run_opcode(0x00ee).unwrap();
     assert_eq!(cpu.pc, 0x664);
     assert_eq!(cpu.sp, 3);
 }
@@ -80,18 +141,18 @@ fn test_op00ee() {
 // JP addr
 #[test]
 fn test_op1nnn() {
-    let mut cpu = Cpu::new();
-    cpu.run_opcode(0x1267);
+    let mut cpu = Cpu::new(Quirks::default());
+    cpu.run_opcode(0x1267).unwrap();
     assert_eq!(cpu.pc, 0x0267);
 }
 
 // CALL addr
 #[test]
 fn test_op2nnn() {
-    let mut cpu = Cpu::new();
+    let mut cpu = Cpu::new(Quirks::default());
     cpu.sp = 0;
     cpu.pc = 0x02666;
-    cpu.run_opcode(0x2267);
+    cpu.run_opcode(0x2267).unwrap();
     assert_eq!(cpu.sp, 1);
     assert_eq!(cpu.stack[0], 0x02668);
     assert_eq!(cpu.pc, 0x0267)
@@ -101,10 +162,10 @@ fn test_op2nnn() {
 #[test]
 fn test_op3xkk() {
     let mut cpu = new_cpu_with_inital_data();
-    cpu.run_opcode(0x3003);
+    cpu.run_opcode(0x3003).unwrap();
     assert_eq!(cpu.pc, PC + 2);
     let mut cpu = new_cpu_with_inital_data();
-    cpu.run_opcode(0x3000);
+    cpu.run_opcode(0x3000).unwrap();
     assert_eq!(cpu.pc, PC + 4);
 }
 
@@ -112,10 +173,10 @@ fn test_op3xkk() {
 #[test]
 fn test_op4xkk() {
     let mut cpu = new_cpu_with_inital_data();
-    cpu.run_opcode(0x4003);
+    cpu.run_opcode(0x4003).unwrap();
     assert_eq!(cpu.pc, PC + 4);
     let mut cpu = new_cpu_with_inital_data();
-    cpu.run_opcode(0x4000);
+    cpu.run_opcode(0x4000).unwrap();
     assert_eq!(cpu.pc, PC + 2);
 }
 
@@ -123,10 +184,10 @@ fn test_op4xkk() {
 #[test]
 fn test_op5xy0() {
     let mut cpu = new_cpu_with_inital_data();
-    cpu.run_opcode(0x5020);
+    cpu.run_opcode(0x5020).unwrap();
     assert_eq!(cpu.pc, PC + 2);
     let mut cpu = new_cpu_with_inital_data();
-    cpu.run_opcode(0x5010);
+    cpu.run_opcode(0x5010).unwrap();
     assert_eq!(cpu.pc, PC + 4);
 }
 
@@ -134,7 +195,7 @@ fn test_op5xy0() {
 #[test]
 fn test_op6xkk() {
     let mut cpu = new_cpu_with_inital_data();
-    cpu.run_opcode(0x6415);
+    cpu.run_opcode(0x6415).unwrap();
     assert_eq!(cpu.v[4], 0x0015)
 }
 
@@ -142,7 +203,7 @@ fn test_op6xkk() {
 #[test]
 fn test_op7xkk() {
     let mut cpu = new_cpu_with_inital_data();
-    cpu.run_opcode(0x7315); // x = 3, kk = 21
+    cpu.run_opcode(0x7315).unwrap(); // x = 3, kk = 21
     assert_eq!(cpu.v[3], 0x0016) // v[3] = 22
 }
 
@@ -150,7 +211,7 @@ fn test_op7xkk() {
 #[test]
 fn test_op8xy0() {
     let mut cpu = new_cpu_with_inital_data();
-    cpu.run_opcode(0x8350);
+    cpu.run_opcode(0x8350).unwrap();
     assert_eq!(cpu.v[3], 4)
 }
 
@@ -158,7 +219,7 @@ fn test_op8xy0() {
 #[test]
 fn test_op8xy1() {
     let mut cpu = new_cpu_with_inital_data();
-    cpu.run_opcode(0x8351);
+    cpu.run_opcode(0x8351).unwrap();
     assert_eq!(cpu.v[3], 0x05)
 }
 
@@ -166,7 +227,7 @@ fn test_op8xy1() {
 #[test]
 fn test_op8xy2() {
     let mut cpu = new_cpu_with_inital_data();
-    cpu.run_opcode(0x8562);
+    cpu.run_opcode(0x8562).unwrap();
     assert_eq!(cpu.v[5], 0x04)
 }
 
@@ -174,7 +235,7 @@ fn test_op8xy2() {
 #[test]
 fn test_op8xy3() {
     let mut cpu = new_cpu_with_inital_data();
-    cpu.run_opcode(0x8563);
+    cpu.run_opcode(0x8563).unwrap();
     assert_eq!(cpu.v[5], 0x01)
 }
 
@@ -200,8 +261,12 @@ fn test_op8xy5() {
     assert_eq!(cpu.v[0x00], 0x04);
     let mut cpu = new_cpu_with_inital_data();
     cpu = set_register_values_and_run(cpu, 3, 3, 0x8015);
-    assert_eq!(cpu.v[0x0F], 0);
+    assert_eq!(cpu.v[0x0F], 1);
     assert_eq!(cpu.v[0x00], 0x00);
+    let mut cpu = new_cpu_with_inital_data();
+    cpu = set_register_values_and_run(cpu, 2, 3, 0x8015);
+    assert_eq!(cpu.v[0x0F], 0);
+    assert_eq!(cpu.v[0x00], 0xFF);
 }
 
 // SHR Vx {, Vy}
@@ -228,6 +293,31 @@ fn test_op8xy7() {
     cpu = set_register_values_and_run(cpu, 255, 15, 0x8017);
     assert_eq!(cpu.v[0x0F], 0);
     assert_eq!(cpu.v[0x00], 0x10);
+    let mut cpu = new_cpu_with_inital_data();
+    cpu = set_register_values_and_run(cpu, 7, 7, 0x8017);
+    assert_eq!(cpu.v[0x0F], 1);
+    assert_eq!(cpu.v[0x00], 0x00);
+}
+
+// Arithmetic with VF as Vx keeps the flag, not the result
+#[test]
+fn test_8xyn_flag_written_last() {
+    let cases = [
+        (0x8f14, 200, 100, 1),
+        (0x8f15, 5, 3, 1),
+        (0x8f15, 3, 5, 0),
+        (0x8f16, 3, 0, 1),
+        (0x8f17, 3, 5, 1),
+        (0x8f17, 5, 3, 0),
+        (0x8f1e, 0x40, 0, 0),
+    ];
+    for &(opcode, vf, v1, flag) in cases.iter() {
+        let mut cpu = new_cpu_with_inital_data();
+        cpu.v[0x0f] = vf;
+        cpu.v[1] = v1;
+        cpu.run_opcode(opcode).unwrap();
+        assert_eq!(cpu.v[0x0f], flag, "opcode {:04X}", opcode);
+    }
 }
 
 // SHL Vx {, Vy}
@@ -271,7 +361,6 @@ fn test_bnnn() {
 }
 
 // RND Vx, byte
-// Not sure how to test this since it generates random numbers but will test that pc is corect
 #[test]
 fn test_cxkk() {
     let mut cpu = new_cpu_with_inital_data();
@@ -279,23 +368,44 @@ fn test_cxkk() {
     assert_eq!(cpu.pc, PC + 0x002);
 }
 
+#[test]
+fn test_cxkk_seeded() {
+    let run = |seed| {
+        let mut cpu = Cpu::new(Quirks::default());
+        cpu.set_seed(seed);
+        cpu.read_data_to_memory(&[0xC0, 0xFF, 0x12, 0x00]);
+        (0..64).map(|_| {
+            cpu.run_frame([false; 16], 2).unwrap();
+            cpu.v[0]
+        }).collect::<Vec<u8>>()
+    };
+    assert_eq!(run(7), run(7));
+    assert_ne!(run(7), run(8));
+
+    let mut random = Random::new(7);
+    let mut cpu = new_cpu_with_inital_data();
+    cpu.set_seed(7);
+    cpu = set_register_values_and_run(cpu, 4, 1, 0xC40F);
+    assert_eq!(cpu.v[4], random.next_u8() & 0x0F);
+}
+
 #[test]
 fn test_dxyn() {
     let mut cpu = new_cpu_with_inital_data();
     cpu.i = 0;
     cpu.memory[0] = 0b11111111;
     cpu.memory[1] = 0b00000000;
-    cpu.vram[0][0] = 1;
-    cpu.vram[0][1] = 0;
-    cpu.vram[1][0] = 1;
-    cpu.vram[1][1] = 0;
+    cpu.vram.set_pixel(0, 0, 1);
+    cpu.vram.set_pixel(1, 0, 0);
+    cpu.vram.set_pixel(0, 1, 1);
+    cpu.vram.set_pixel(1, 1, 0);
     cpu.v[0] = 0;
     cpu = set_register_values_and_run(cpu, 0, 1, 0xd002);
 
-    assert_eq!(cpu.vram[0][0], 0);
-    assert_eq!(cpu.vram[0][1], 1);
-    assert_eq!(cpu.vram[1][0], 1);
-    assert_eq!(cpu.vram[1][1], 0);
+    assert_eq!(cpu.vram.pixel(0, 0), 0);
+    assert_eq!(cpu.vram.pixel(1, 0), 1);
+    assert_eq!(cpu.vram.pixel(0, 1), 1);
+    assert_eq!(cpu.vram.pixel(1, 1), 0);
     assert_eq!(cpu.v[0x0f], 1);
     assert!(cpu.vram_changed);
     assert_eq!(cpu.pc, PC + 2);
@@ -349,13 +459,13 @@ fn test_fx0a() {
     assert_eq!(cpu.input_address, 1);
     assert_eq!(cpu.pc, PC + 2);
 
-    cpu.cycle();
+    cpu.cycle([false; 16]).unwrap();
     assert_eq!(cpu.wait_for_input, true);
     assert_eq!(cpu.input_address, 1);
 
     cpu.keys[0] = true;
 
-    cpu.cycle();
+    cpu.cycle(cpu.keys).unwrap();
     assert_eq!(cpu.wait_for_input, false);
     assert_eq!(cpu.v[0x01], 0);
 }
@@ -411,7 +521,7 @@ fn test_fx55() {
 
     let mut cpu = new_cpu_with_inital_data();
     cpu.i = 300;
-    cpu.run_opcode(0xf555);
+    cpu.run_opcode(0xf555).unwrap();
     for i in 0..5 {
         assert_eq!(cpu.memory[300+i], cpu.v[i])
     }
@@ -426,7 +536,7 @@ fn test_fx65() {
     for i in 0..7 {
         cpu.memory[cpu.i + i] = i as u8;
     }
-    cpu.run_opcode(0xf765);
+    cpu.run_opcode(0xf765).unwrap();
 
     for i in 0..7 {
         assert_eq!(cpu.v[i], cpu.memory[cpu.i + i])
@@ -436,4 +546,492 @@ fn test_fx65() {
 
 }
 
+// Quirks
+
+#[test]
+fn test_quirk_shift_uses_vy() {
+    let quirks = Quirks { shift_uses_vy: true, ..Quirks::default() };
+    let cpu = new_cpu_with_quirks(quirks);
+    let cpu = set_register_values_and_run(cpu, 1, 6, 0x8016);
+    assert_eq!(cpu.v[0x00], 3);
+    assert_eq!(cpu.v[0x0F], 0);
+
+    let cpu = new_cpu_with_quirks(quirks);
+    let cpu = set_register_values_and_run(cpu, 1, 0x81, 0x801E);
+    assert_eq!(cpu.v[0x00], 2);
+    assert_eq!(cpu.v[0x0F], 1);
+}
+
+#[test]
+fn test_quirk_load_store_increments_i() {
+    let quirks = Quirks { load_store_increment: IndexIncrement::ByXPlusOne, ..Quirks::default() };
+    let mut cpu = new_cpu_with_quirks(quirks);
+    cpu.i = 300;
+    cpu.run_opcode(0xf355).unwrap();
+    assert_eq!(cpu.i, 304);
+    cpu.run_opcode(0xf165).unwrap();
+    assert_eq!(cpu.i, 306);
+
+    let quirks = Quirks { load_store_increment: IndexIncrement::ByX, ..Quirks::default() };
+    let mut cpu = new_cpu_with_quirks(quirks);
+    cpu.i = 300;
+    cpu.run_opcode(0xf355).unwrap();
+    assert_eq!(cpu.i, 303);
+    cpu.run_opcode(0xf165).unwrap();
+    assert_eq!(cpu.i, 304);
+}
+
+#[test]
+fn test_quirk_jump_uses_vx() {
+    let quirks = Quirks { jump_uses_vx: true, ..Quirks::default() };
+    let mut cpu = new_cpu_with_quirks(quirks);
+    cpu.v[2] = 0x10;
+    cpu.run_opcode(0xB234).unwrap();
+    assert_eq!(cpu.pc, 0x244);
+}
+
+#[test]
+fn test_quirk_logic_resets_vf() {
+    let quirks = Quirks { logic_resets_vf: true, ..Quirks::default() };
+    for &opcode in [0x8351, 0x8352, 0x8353].iter() {
+        let mut cpu = new_cpu_with_quirks(quirks);
+        cpu.v[0x0F] = 1;
+        cpu.run_opcode(opcode).unwrap();
+        assert_eq!(cpu.v[0x0F], 0);
+    }
+}
+
+#[test]
+fn test_quirk_clip_sprites() {
+    let mut cpu = new_cpu_with_quirks(Quirks::default());
+    cpu.i = 0;
+    cpu.memory[0] = 0xFF;
+    cpu = set_register_values_and_run(cpu, 60, 31, 0xd011);
+    assert_eq!(cpu.vram.pixel(63, 31), 1);
+    assert_eq!(cpu.vram.pixel(0, 31), 1); // wrapped
+
+    let quirks = Quirks { clip_sprites: true, ..Quirks::default() };
+    let mut cpu = new_cpu_with_quirks(quirks);
+    cpu.i = 0;
+    cpu.memory[0] = 0xFF;
+    cpu.memory[1] = 0xFF;
+    cpu = set_register_values_and_run(cpu, 60, 31, 0xd012);
+    assert_eq!(cpu.vram.pixel(63, 31), 1);
+    assert_eq!(cpu.vram.pixel(0, 31), 0); // clipped
+    assert_eq!(cpu.vram.pixel(60, 0), 0); // clipped
+}
+
+#[test]
+fn test_quirk_display_wait() {
+    let quirks = Quirks { display_wait: true, ..Quirks::default() };
+    let mut cpu = new_cpu_with_quirks(quirks);
+    cpu.read_data_to_memory(&[0xd0, 0x01, 0x60, 0x05]);
+    cpu.cycle([false; 16]).unwrap();
+    assert_eq!(cpu.pc, PC + 2);
+    cpu.cycle([false; 16]).unwrap(); // waiting for vblank
+    cpu.cycle([false; 16]).unwrap();
+    assert_eq!(cpu.pc, PC + 2);
+    cpu.tick_timers();
+    cpu.cycle([false; 16]).unwrap();
+    assert_eq!(cpu.pc, PC + 4);
+    assert_eq!(cpu.v[0], 5);
+}
+
+#[test]
+fn test_quirk_presets() {
+    assert_eq!(Quirks::from_name("vip"), Some(Quirks::cosmac_vip()));
+    assert_eq!(Quirks::from_name("SCHIP"), Some(Quirks::schip()));
+    assert_eq!(Quirks::from_name("xo-chip"), Some(Quirks::xo_chip()));
+    assert_eq!(Quirks::from_name("unknown"), None);
+    assert!(Quirks::cosmac_vip().display_wait);
+    assert!(Quirks::chip48().jump_uses_vx);
+    assert_eq!(Quirks::from_name("chip48"), Some(Quirks::chip48()));
+    assert_ne!(Quirks::chip48(), Quirks::schip());
+    assert_eq!(Quirks::from_name("chip8"), Some(Quirks::default()));
+}
+
+// SUPER-CHIP
+
+// SCD nibble
+#[test]
+fn test_00cn() {
+    let mut cpu = new_cpu_with_inital_data();
+    cpu.vram.set_pixel(5, 0, 1);
+    cpu.run_opcode(0x00c3).unwrap();
+    assert_eq!(cpu.vram.pixel(5, 0), 0);
+    assert_eq!(cpu.vram.pixel(5, 3), 1);
+    assert!(cpu.vram_changed);
+    assert_eq!(cpu.pc, PC + 2);
+}
+
+// SCR and SCL
+#[test]
+fn test_00fb_00fc() {
+    let mut cpu = new_cpu_with_inital_data();
+    cpu.vram.set_pixel(5, 2, 1);
+    cpu.run_opcode(0x00fb).unwrap();
+    assert_eq!(cpu.vram.pixel(5, 2), 0);
+    assert_eq!(cpu.vram.pixel(9, 2), 1);
+    cpu.run_opcode(0x00fc).unwrap();
+    cpu.run_opcode(0x00fc).unwrap();
+    assert_eq!(cpu.vram.pixel(1, 2), 1);
+    assert_eq!(cpu.vram.pixel(9, 2), 0);
+    assert_eq!(cpu.pc, PC + 6);
+}
+
+// EXIT
+#[test]
+fn test_00fd() {
+    let mut cpu = new_cpu_with_inital_data();
+    cpu.read_data_to_memory(&[0x00, 0xfd, 0x60, 0x05]);
+    assert!(cpu.cycle([false; 16]).unwrap().halted);
+    assert!(cpu.cycle([false; 16]).unwrap().halted);
+    assert_eq!(cpu.pc, PC);
+    assert_eq!(cpu.v[0], 0);
+}
+
+// LOW and HIGH
+#[test]
+fn test_00fe_00ff() {
+    let mut cpu = new_cpu_with_inital_data();
+    cpu.vram.set_pixel(0, 0, 1);
+    cpu.run_opcode(0x00ff).unwrap();
+    assert!(cpu.vram.is_hires());
+    assert_eq!(cpu.vram.width(), 128);
+    assert_eq!(cpu.vram.height(), 64);
+    assert_eq!(cpu.vram.pixel(0, 0), 0);
+    cpu.run_opcode(0x00fe).unwrap();
+    assert!(!cpu.vram.is_hires());
+    assert_eq!(cpu.vram.width(), 64);
+    assert_eq!(cpu.pc, PC + 4);
+}
+
+// DRW Vx, Vy, 0
+#[test]
+fn test_dxy0() {
+    let mut cpu = new_cpu_with_inital_data();
+    cpu.run_opcode(0x00ff).unwrap();
+    cpu.i = 0x300;
+    for byte in 0..32 {
+        cpu.memory[0x300 + byte] = 0xFF;
+    }
+    cpu = set_register_values_and_run(cpu, 100, 50, 0xd010);
+    assert_eq!(cpu.vram.pixel(100, 50), 1);
+    assert_eq!(cpu.vram.pixel(115, 50), 1);
+    assert_eq!(cpu.vram.pixel(100, 63), 1);
+    assert_eq!(cpu.vram.pixel(116, 50), 0);
+    assert_eq!(cpu.v[0x0f], 0);
+    cpu.run_opcode(0xd010).unwrap();
+    assert_eq!(cpu.vram.pixel(100, 50), 0);
+    assert_eq!(cpu.v[0x0f], 1);
+}
+
+// LD HF, Vx
+#[test]
+fn test_fx30() {
+    let mut cpu = new_cpu_with_inital_data();
+    cpu = set_register_values_and_run(cpu, 3, 0, 0xf030);
+    assert_eq!(cpu.i, BIG_FONT_ADDRESS + 30);
+    assert_eq!(cpu.memory[cpu.i], BIG_FONT_SET[30]);
+    assert_eq!(cpu.pc, PC + 2);
+}
+
+#[test]
+fn test_fx30_hex_digit() {
+    let mut cpu = new_cpu_with_inital_data();
+    cpu = set_register_values_and_run(cpu, 0xa, 0, 0xf030);
+    assert_eq!(cpu.i, BIG_FONT_ADDRESS + 100);
+    assert_eq!(&cpu.memory[cpu.i..cpu.i + 10], &BIG_FONT_SET[100..110]);
+    assert_eq!(cpu.memory[cpu.i], 0x7E);
+}
+
+// LD R, Vx and LD Vx, R
+#[test]
+fn test_fx75_fx85() {
+    let mut cpu = new_cpu_with_inital_data();
+    cpu.run_opcode(0xf775).unwrap();
+    let saved = cpu.v;
+    cpu.v = [0; 16];
+    cpu.run_opcode(0xf785).unwrap();
+    assert_eq!(cpu.v[..8], saved[..8]);
+    assert_eq!(cpu.v[8], 0);
+    assert_eq!(cpu.pc, PC + 4);
+}
+
+// XO-CHIP
+
+#[test]
+fn test_load_data_past_4k() {
+    let mut cpu = Cpu::new(Quirks::default());
+    let data = vec![0xAB; 0x1000];
+    cpu.read_data_to_memory(&data);
+    assert_eq!(cpu.memory[0x11FF], 0xAB);
+    assert_eq!(cpu.memory[0x1200], 0);
+}
+
+// LD I, long addr
+#[test]
+fn test_f000() {
+    let mut cpu = new_cpu_with_inital_data();
+    cpu.read_data_to_memory(&[0xf0, 0x00, 0xAB, 0xCD]);
+    cpu.cycle([false; 16]).unwrap();
+    assert_eq!(cpu.i, 0xABCD);
+    assert_eq!(cpu.pc, PC + 4);
+}
+
+// Skip over a four byte instruction
+#[test]
+fn test_skip_f000() {
+    let mut cpu = new_cpu_with_inital_data();
+    cpu.read_data_to_memory(&[0x30, 0x00, 0xf0, 0x00, 0xAB, 0xCD]);
+    cpu.cycle([false; 16]).unwrap();
+    assert_eq!(cpu.pc, PC + 6);
+}
+
+// SAVE Vx - Vy and LOAD Vx - Vy
+#[test]
+fn test_5xy2_5xy3() {
+    let mut cpu = new_cpu_with_inital_data();
+    cpu.i = 0x300;
+    cpu.run_opcode(0x5362).unwrap();
+    assert_eq!(cpu.memory[0x300..0x304], [1, 3, 4, 5]);
+    assert_eq!(cpu.i, 0x300);
+
+    cpu.run_opcode(0x5a72).unwrap(); // Reverse order
+    assert_eq!(cpu.memory[0x300..0x304], [4, 8, 7, 6]);
+
+    cpu.run_opcode(0x50f3).unwrap();
+    assert_eq!(cpu.v[0..4], [4, 8, 7, 6]);
+    assert_eq!(cpu.pc, PC + 6);
+}
+
+// PLANE n
+#[test]
+fn test_fn01_planes() {
+    let mut cpu = new_cpu_with_inital_data();
+    cpu.i = 0x300;
+    cpu.memory[0x300] = 0x80; // First plane
+    cpu.memory[0x301] = 0xC0; // Second plane
+    cpu.run_opcode(0xf301).unwrap();
+    cpu = set_register_values_and_run(cpu, 0, 0, 0xd011);
+    assert_eq!(cpu.vram.pixel(0, 0), 3);
+    assert_eq!(cpu.vram.pixel(1, 0), 2);
+
+    // Clear only the second plane
+    cpu.run_opcode(0xf201).unwrap();
+    cpu.run_opcode(0x00e0).unwrap();
+    assert_eq!(cpu.vram.pixel(0, 0), 1);
+    assert_eq!(cpu.vram.pixel(1, 0), 0);
+
+    // Drawing on the second plane doesn't collide with the first
+    cpu.memory[0x300] = 0x80;
+    cpu.run_opcode(0xd011).unwrap();
+    assert_eq!(cpu.vram.pixel(0, 0), 3);
+    assert_eq!(cpu.v[0x0f], 0);
+}
+
+// AUDIO and PITCH Vx
+#[test]
+fn test_f002_fx3a() {
+    let mut cpu = new_cpu_with_inital_data();
+    assert_eq!(cpu.audio_pattern, None);
+    cpu.i = 0x300;
+    cpu.memory[0x300] = 0x80;
+    cpu.run_opcode(0xf002).unwrap();
+    cpu.v[3] = 112;
+    cpu.run_opcode(0xf33a).unwrap();
+
+    let pattern = cpu.audio_pattern.unwrap();
+    assert_eq!(pattern.bit(0), 1);
+    assert_eq!(pattern.bit(1), 0);
+    assert_eq!(pattern.pitch, 112);
+    assert_eq!(pattern.sample_rate(), 8000.0);
+    assert_eq!(cpu.pc, PC + 4);
+}
+
+// Errors
+
+#[test]
+fn test_stack_overflow() {
+    let mut cpu = new_cpu_with_inital_data();
+    cpu.read_data_to_memory(&[0x22, 0x00]); // CALL 0x200 forever
+    for _ in 0..16 {
+        cpu.cycle([false; 16]).unwrap();
+    }
+    assert_eq!(cpu.cycle([false; 16]).err(), Some(CpuError::StackOverflow { pc: PC }));
+    assert_eq!(cpu.sp, 16);
+    assert_eq!(cpu.pc, PC);
+}
+
+#[test]
+fn test_stack_underflow() {
+    let mut cpu = new_cpu_with_inital_data();
+    assert_eq!(cpu.run_opcode(0x00ee), Err(CpuError::StackUnderflow { pc: PC }));
+    assert_eq!(cpu.pc, PC);
+}
+
+#[test]
+fn test_memory_out_of_bounds() {
+    for &opcode in [0xf033, 0xf155, 0xf165, 0xd012, 0x5012, 0xf002].iter() {
+        let mut cpu = new_cpu_with_inital_data();
+        cpu.i = MEMORY_SIZE - 1;
+        let error = cpu.run_opcode(opcode).unwrap_err();
+        assert_eq!(error, CpuError::MemoryOutOfBounds { addr: MEMORY_SIZE, pc: PC });
+        assert_eq!(cpu.pc, PC);
+    }
+}
+
+#[test]
+fn test_memory_out_of_bounds_changes_nothing() {
+    // D013 draws three rows, two of them would fit
+    for &opcode in [0xf355, 0x5032, 0xf033, 0xf365, 0x5033, 0xd013].iter() {
+        let mut cpu = new_cpu_with_inital_data();
+        cpu.i = MEMORY_SIZE - 2;
+        cpu.memory[MEMORY_SIZE - 2] = 0xaa;
+        cpu.memory[MEMORY_SIZE - 1] = 0xbb;
+        cpu.vram.set_pixel(0, 0, 1);
+        let registers = cpu.v;
+        let vram = cpu.vram.clone();
+        let error = cpu.run_opcode(opcode).unwrap_err();
+        assert_eq!(error, CpuError::MemoryOutOfBounds { addr: MEMORY_SIZE, pc: PC });
+        assert_eq!(&cpu.memory[MEMORY_SIZE - 2..], &[0xaa, 0xbb]);
+        assert_eq!(cpu.v, registers);
+        assert_eq!(cpu.vram, vram);
+        assert_eq!(cpu.i, MEMORY_SIZE - 2);
+    }
+}
+
+#[test]
+fn test_fetch_out_of_bounds() {
+    let mut cpu = new_cpu_with_inital_data();
+    cpu.pc = MEMORY_SIZE - 1;
+    let error = cpu.cycle([false; 16]).err();
+    assert_eq!(error, Some(CpuError::MemoryOutOfBounds { addr: MEMORY_SIZE, pc: MEMORY_SIZE - 1 }));
+}
+
+#[test]
+fn test_invalid_key() {
+    let mut cpu = new_cpu_with_inital_data();
+    cpu.v[3] = 0x10;
+    assert_eq!(cpu.run_opcode(0xe39e), Err(CpuError::InvalidKey { key: 0x10, pc: PC }));
+    assert_eq!(cpu.run_opcode(0xe3a1), Err(CpuError::InvalidKey { key: 0x10, pc: PC }));
+}
+
+#[test]
+fn test_run_frame_stops_on_error() {
+    let mut cpu = new_cpu_with_inital_data();
+    cpu.read_data_to_memory(&[0x60, 0x01, 0x00, 0xee]);
+    cpu.delay_timer = 5;
+    assert!(cpu.run_frame([false; 16], 10).is_err());
+    assert_eq!(cpu.v[0], 1);
+    assert_eq!(cpu.pc, PC + 2);
+}
+
+#[test]
+fn test_unknown_opcode_policy() {
+    // 0NNN machine code call and an unused 8xy8
+    for &opcode in [0x0123, 0x8128].iter() {
+        let mut cpu = new_cpu_with_inital_data();
+        cpu.run_opcode(opcode).unwrap();
+        assert_eq!(cpu.pc, PC + 2);
+
+        let mut cpu = new_cpu_with_inital_data();
+        cpu.set_unknown_opcode_policy(UnknownOpcodePolicy::Log);
+        cpu.run_opcode(opcode).unwrap();
+        assert_eq!(cpu.pc, PC + 2);
+
+        let mut cpu = new_cpu_with_inital_data();
+        cpu.set_unknown_opcode_policy(UnknownOpcodePolicy::Halt);
+        assert_eq!(cpu.run_opcode(opcode), Err(CpuError::UnknownOpcode { opcode, pc: PC }));
+        assert_eq!(cpu.pc, PC);
+    }
+}
+
+#[test]
+fn test_run_assembled_program() {
+    let source = "
+        LD V0, 0
+        LD V1, 10
+        loop:
+            ADD V0, 3
+            ADD V1, -1
+            SE V1, 0
+            JP loop
+        EXIT
+    ";
+    let mut cpu = crate::test_utils::cpu_with_program(source);
+    let state = cpu.run_frame([false; 16], 100).unwrap();
+    assert!(state.halted);
+    assert_eq!(cpu.v[0], 30);
+}
+
+#[test]
+fn test_memory_accesses() {
+    let mut cpu = new_cpu_with_inital_data();
+    cpu.i = 0x300;
+    cpu.memory[0x200..0x208].copy_from_slice(&[0xf2, 0x55, 0xf4, 0x33, 0xd0, 0x13, 0xf1, 0x65]);
+
+    cpu.cycle([false; 16]).unwrap();
+    assert_eq!(cpu.memory_accesses(), &[MemoryAccess { address: 0x300, length: 3, kind: AccessKind::Write }]);
+    cpu.cycle([false; 16]).unwrap();
+    assert_eq!(cpu.memory_accesses(), &[MemoryAccess { address: 0x300, length: 3, kind: AccessKind::Write }]);
+    cpu.cycle([false; 16]).unwrap();
+    assert_eq!(cpu.memory_accesses(), &[MemoryAccess { address: 0x300, length: 3, kind: AccessKind::Read }]);
+    cpu.cycle([false; 16]).unwrap();
+    assert_eq!(cpu.memory_accesses(), &[MemoryAccess { address: 0x300, length: 2, kind: AccessKind::Read }]);
+    assert!(cpu.memory_accesses()[0].contains(0x301));
+    assert!(!cpu.memory_accesses()[0].contains(0x302));
+
+    // Instructions that don't touch memory leave nothing behind
+    cpu.memory[0x208..0x20a].copy_from_slice(&[0x60, 0x01]);
+    cpu.cycle([false; 16]).unwrap();
+    assert!(cpu.memory_accesses().is_empty());
+}
+
+#[test]
+fn test_save_and_load_state() {
+    let source = "
+        HIGH
+        LD V0, 5
+        LD F, V0
+        DRW V0, V0, 5
+        CALL sub
+        EXIT
+        sub:
+            LD DT, V0
+            ADD V0, 1
+            RET
+    ";
+    let rom = crate::assembler::assemble(source).unwrap().image;
+    let hash = save_state::rom_hash(&rom);
+    let mut cpu = Cpu::new(Quirks::default());
+    cpu.read_data_to_memory(&rom);
+    for _ in 0..6 {
+        cpu.cycle([false; 16]).unwrap();
+    }
+    let state = cpu.save_state(hash);
+
+    cpu.run_frame([true; 16], 10).unwrap();
+    assert!(cpu.halted);
+    cpu.load_state(&state, hash).unwrap();
+    assert_eq!(cpu.save_state(hash), state);
+    assert_eq!((cpu.pc, cpu.sp, cpu.v[0], cpu.delay_timer), (0x20e, 1, 5, 5));
+    assert!(cpu.vram.is_hires());
+    assert!(!cpu.halted);
+    assert!(cpu.vram_changed);
+
+    // Bad states leave the machine alone
+    assert_eq!(cpu.load_state(&state, hash + 1), Err(StateError::WrongRom { expected: hash + 1, found: hash }));
+    assert_eq!(cpu.load_state(&state[..state.len() - 1], hash), Err(StateError::Corrupt));
+    let mut extra = state.clone();
+    extra.push(0);
+    assert_eq!(cpu.load_state(&extra, hash), Err(StateError::Corrupt));
+    assert_eq!(cpu.save_state(hash), state);
+
+    // Runs on exactly like the machine that was saved
+    cpu.run_frame([false; 16], 10).unwrap();
+    assert!(cpu.halted);
+    assert_eq!(cpu.v[0], 6);
+}
+
 // TODO: Write tests for opcodes and write opcode
@@ -0,0 +1,547 @@
+use crate::cpu::{AccessKind, Cpu};
+use crate::disassembler::{self, Syntax};
+use crate::error::CpuError;
+use crate::instruction::Instruction;
+use std::collections::BTreeSet;
+use std::fmt;
+use std::fmt::Write;
+
+pub const HELP: &str = "Commands:
+    s, step [n]             Execute n instructions (default 1)
+    c, continue             Run until a breakpoint
+    b, break [addr]         Break at address, without one list breakpoints
+    bo, break-opcode <pat>  Break on opcodes matching pat, ? matches any nibble, e.g. D??F
+    w, watch <addr> [len]   Break after an instruction writes memory at addr
+    rwatch <addr> [len]     Break after an instruction reads memory at addr
+    awatch <addr> [len]     Break after an instruction reads or writes addr
+    rb <reg> [value]        Break when V0-VF or I changes, or changes to value
+    d, delete [addr|pat|reg]
+                            Delete a breakpoint, without one delete all
+    r, regs                 Print registers and timers
+    stack                   Print the call stack
+    x, mem <addr> [len]     Hex dump memory (default 64 bytes)
+    l, dis [addr] [n]       Disassemble n instructions around addr (default PC)
+    h, help                 Print this help
+    q, quit                 Quit";
+
+/// Opcode with wildcard nibbles, e.g. `D??F` matches every 15 row sprite
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct OpcodePattern {
+    value: u16,
+    mask: u16,
+}
+
+impl OpcodePattern {
+    /// Four hex digits or `?`
+    pub fn parse(text: &str) -> Option<Self> {
+        if text.chars().count() != 4 {
+            return None;
+        }
+        let mut pattern = OpcodePattern { value: 0, mask: 0 };
+        for c in text.chars() {
+            pattern.value <<= 4;
+            pattern.mask <<= 4;
+            if c != '?' {
+                pattern.value |= c.to_digit(16)? as u16;
+                pattern.mask |= 0xF;
+            }
+        }
+        Some(pattern)
+    }
+
+    pub fn matches(&self, opcode: u16) -> bool {
+        opcode & self.mask == self.value
+    }
+}
+
+impl fmt::Display for OpcodePattern {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for shift in [12, 8, 4, 0].iter() {
+            if (self.mask >> shift) & 0xF == 0 {
+                write!(f, "?")?;
+            } else {
+                write!(f, "{:X}", (self.value >> shift) & 0xF)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Which memory accesses trigger a watchpoint
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WatchKind {
+    Read,
+    Write,
+    Access,
+}
+
+impl WatchKind {
+    fn matches(&self, kind: AccessKind) -> bool {
+        match *self {
+            WatchKind::Read => kind == AccessKind::Read,
+            WatchKind::Write => kind == AccessKind::Write,
+            WatchKind::Access => true,
+        }
+    }
+}
+
+/// Memory range watched for reads, writes or both
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Watchpoint {
+    pub address: usize,
+    pub length: usize,
+    pub kind: WatchKind,
+}
+
+impl fmt::Display for Watchpoint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let kind = match self.kind {
+            WatchKind::Read => "read",
+            WatchKind::Write => "write",
+            WatchKind::Access => "access",
+        };
+        if self.length > 1 {
+            write!(f, "{} watchpoint {:#05X}-{:#05X}", kind, self.address, self.address + self.length - 1)
+        } else {
+            write!(f, "{} watchpoint {:#05X}", kind, self.address)
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Register {
+    V(u8),
+    I,
+}
+
+impl Register {
+    pub fn parse(text: &str) -> Option<Self> {
+        let upper = text.to_uppercase();
+        match upper.strip_prefix('V') {
+            Some(digit) if digit.len() == 1 => u8::from_str_radix(digit, 16).ok().map(Register::V),
+            _ if upper == "I" => Some(Register::I),
+            _ => None,
+        }
+    }
+
+    fn value(&self, cpu: &Cpu) -> usize {
+        match *self {
+            Register::V(x) => cpu.registers()[x as usize] as usize,
+            Register::I => cpu.i(),
+        }
+    }
+}
+
+impl fmt::Display for Register {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Register::V(x) => write!(f, "V{:X}", x),
+            Register::I => write!(f, "I"),
+        }
+    }
+}
+
+/// Break when a register changes, or only when it changes to `value`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RegisterBreakpoint {
+    pub register: Register,
+    pub value: Option<usize>,
+}
+
+impl fmt::Display for RegisterBreakpoint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.value {
+            Some(value) => write!(f, "{} == {:#X}", self.register, value),
+            None => write!(f, "{} changes", self.register),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Command {
+    Step(usize),
+    Continue,
+    Break(usize),
+    BreakOpcode(OpcodePattern),
+    ListBreakpoints,
+    Delete(usize),
+    DeleteOpcode(OpcodePattern),
+    Watch(Watchpoint),
+    BreakRegister(RegisterBreakpoint),
+    DeleteRegister(Register),
+    DeleteAll,
+    Registers,
+    Stack,
+    Memory { address: usize, length: usize },
+    Disassemble { address: Option<usize>, count: usize },
+    Help,
+    Quit,
+}
+
+impl Command {
+    pub fn parse(line: &str) -> Result<Self, String> {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let command = match words.as_slice() {
+            ["s"] | ["step"] => Command::Step(1),
+            ["s", n] | ["step", n] => Command::Step(parse_number(n)?),
+            ["c"] | ["continue"] => Command::Continue,
+            ["b"] | ["break"] => Command::ListBreakpoints,
+            ["b", address] | ["break", address] => Command::Break(parse_number(address)?),
+            ["bo", pattern] | ["break-opcode", pattern] => Command::BreakOpcode(parse_pattern(pattern)?),
+            ["d"] | ["delete"] => Command::DeleteAll,
+            ["d", target] | ["delete", target] => match (Register::parse(target), parse_number(target)) {
+                (Some(register), _) => Command::DeleteRegister(register),
+                (None, Ok(address)) => Command::Delete(address),
+                (None, Err(_)) => Command::DeleteOpcode(parse_pattern(target)?),
+            },
+            [watch, address, rest @ ..] if rest.len() <= 1 && ["w", "watch", "rwatch", "awatch"].contains(watch) => {
+                let kind = match *watch {
+                    "rwatch" => WatchKind::Read,
+                    "awatch" => WatchKind::Access,
+                    _ => WatchKind::Write,
+                };
+                let length = match rest.first() {
+                    Some(length) => parse_number(length)?.max(1),
+                    None => 1,
+                };
+                Command::Watch(Watchpoint { address: parse_number(address)?, length, kind })
+            }
+            ["rb", register, rest @ ..] if rest.len() <= 1 => {
+                let register = Register::parse(register).ok_or_else(|| format!("Invalid register: {}", register))?;
+                let value = match rest.first() {
+                    Some(value) => Some(parse_number(value)?),
+                    None => None,
+                };
+                Command::BreakRegister(RegisterBreakpoint { register, value })
+            }
+            ["r"] | ["regs"] => Command::Registers,
+            ["stack"] => Command::Stack,
+            ["x", address] | ["mem", address] => Command::Memory { address: parse_number(address)?, length: 64 },
+            ["x", address, length] | ["mem", address, length] => {
+                Command::Memory { address: parse_number(address)?, length: parse_number(length)? }
+            }
+            ["l"] | ["dis"] => Command::Disassemble { address: None, count: 9 },
+            ["l", address] | ["dis", address] => Command::Disassemble { address: Some(parse_number(address)?), count: 9 },
+            ["l", address, count] | ["dis", address, count] => {
+                Command::Disassemble { address: Some(parse_number(address)?), count: parse_number(count)? }
+            }
+            ["h"] | ["help"] => Command::Help,
+            ["q"] | ["quit"] => Command::Quit,
+            [] => return Err("Empty command, try help".to_string()),
+            _ => return Err(format!("Unknown command: {}, try help", line.trim())),
+        };
+        Ok(command)
+    }
+}
+
+/// Hex with or without 0x, decimal with a leading #
+fn parse_number(text: &str) -> Result<usize, String> {
+    let parsed = match text.strip_prefix('#') {
+        Some(decimal) => decimal.parse().ok(),
+        None => usize::from_str_radix(text.trim_start_matches("0x"), 16).ok(),
+    };
+    parsed.ok_or_else(|| format!("Invalid number: {}", text))
+}
+
+fn parse_pattern(text: &str) -> Result<OpcodePattern, String> {
+    OpcodePattern::parse(text).ok_or_else(|| format!("Invalid opcode pattern: {}", text))
+}
+
+/// Runs the Cpu one instruction at a time so it can stop on breakpoints.
+/// Timers tick every `instructions_per_frame` instructions, stepping keeps
+/// the same timing as running.
+pub struct Debugger {
+    breakpoints: BTreeSet<usize>,
+    opcode_breakpoints: Vec<OpcodePattern>,
+    watchpoints: Vec<Watchpoint>,
+    register_breakpoints: Vec<RegisterBreakpoint>,
+    paused: bool,
+    /// Set when resuming so the breakpoint we're stopped at doesn't fire again
+    resumed: bool,
+    instructions_per_frame: usize,
+    cycles: usize,
+}
+
+impl Debugger {
+    /// The debugger starts paused, before the first instruction
+    pub fn new(instructions_per_frame: usize) -> Self {
+        Debugger {
+            breakpoints: BTreeSet::new(),
+            opcode_breakpoints: Vec::new(),
+            watchpoints: Vec::new(),
+            register_breakpoints: Vec::new(),
+            paused: true,
+            resumed: false,
+            instructions_per_frame,
+            cycles: 0,
+        }
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Stop running, e.g. on an interrupt from the user
+    pub fn pause(&mut self) {
+        self.paused = true;
+    }
+
+    pub fn set_instructions_per_frame(&mut self, instructions_per_frame: usize) {
+        self.instructions_per_frame = instructions_per_frame;
+    }
+
+    /// Run a command and return what to print. Quit is left to the caller.
+    pub fn execute(&mut self, command: Command, cpu: &mut Cpu, keys: [bool; 16]) -> String {
+        match command {
+            Command::Step(count) => {
+                for _ in 0..count {
+                    if let Err(error) = self.step(cpu, keys) {
+                        return format!("CPU error: {}\n{}", error, current_line(cpu));
+                    }
+                    if cpu.state().halted {
+                        return format!("Program exited\n{}", current_line(cpu));
+                    }
+                }
+                current_line(cpu)
+            }
+            Command::Continue => {
+                self.paused = false;
+                self.resumed = true;
+                "Continuing".to_string()
+            }
+            Command::Break(address) => {
+                self.breakpoints.insert(address);
+                format!("Breakpoint at {:#05X}", address)
+            }
+            Command::BreakOpcode(pattern) => {
+                if !self.opcode_breakpoints.contains(&pattern) {
+                    self.opcode_breakpoints.push(pattern);
+                }
+                format!("Breakpoint on opcode {}", pattern)
+            }
+            Command::ListBreakpoints => {
+                let mut output = String::new();
+                for address in self.breakpoints.iter() {
+                    writeln!(output, "{:#05X}", address).unwrap();
+                }
+                for pattern in self.opcode_breakpoints.iter() {
+                    writeln!(output, "opcode {}", pattern).unwrap();
+                }
+                for watchpoint in self.watchpoints.iter() {
+                    writeln!(output, "{}", watchpoint).unwrap();
+                }
+                for breakpoint in self.register_breakpoints.iter() {
+                    writeln!(output, "{}", breakpoint).unwrap();
+                }
+                if output.is_empty() { "No breakpoints".to_string() } else { output.trim_end().to_string() }
+            }
+            Command::Delete(address) => {
+                let watchpoints = self.watchpoints.len();
+                self.watchpoints.retain(|watchpoint| watchpoint.address != address);
+                if self.breakpoints.remove(&address) || self.watchpoints.len() < watchpoints {
+                    format!("Deleted breakpoints at {:#05X}", address)
+                } else {
+                    format!("No breakpoint at {:#05X}", address)
+                }
+            }
+            Command::Watch(watchpoint) => {
+                self.watchpoints.push(watchpoint);
+                format!("Added {}", watchpoint)
+            }
+            Command::BreakRegister(breakpoint) => {
+                self.register_breakpoints.retain(|other| other.register != breakpoint.register);
+                self.register_breakpoints.push(breakpoint);
+                format!("Breakpoint when {}", breakpoint)
+            }
+            Command::DeleteRegister(register) => {
+                let count = self.register_breakpoints.len();
+                self.register_breakpoints.retain(|breakpoint| breakpoint.register != register);
+                if self.register_breakpoints.len() < count {
+                    format!("Deleted breakpoint on {}", register)
+                } else {
+                    format!("No breakpoint on {}", register)
+                }
+            }
+            Command::DeleteOpcode(pattern) => {
+                let count = self.opcode_breakpoints.len();
+                self.opcode_breakpoints.retain(|&other| other != pattern);
+                if self.opcode_breakpoints.len() < count {
+                    format!("Deleted breakpoint on opcode {}", pattern)
+                } else {
+                    format!("No breakpoint on opcode {}", pattern)
+                }
+            }
+            Command::DeleteAll => {
+                self.breakpoints.clear();
+                self.opcode_breakpoints.clear();
+                self.watchpoints.clear();
+                self.register_breakpoints.clear();
+                "Deleted all breakpoints".to_string()
+            }
+            Command::Registers => registers(cpu),
+            Command::Stack => stack(cpu),
+            Command::Memory { address, length } => hex_dump(cpu.memory(), address, length),
+            Command::Disassemble { address, count } => disassemble(cpu, address.unwrap_or_else(|| cpu.pc()), count),
+            Command::Help => HELP.to_string(),
+            Command::Quit => String::new(),
+        }
+    }
+
+    /// Execute one instruction, ignoring breakpoints
+    pub fn step(&mut self, cpu: &mut Cpu, keys: [bool; 16]) -> Result<(), CpuError> {
+        self.resumed = true;
+        self.cycle(cpu, keys)
+    }
+
+    /// Run the rest of a frame unless paused. Returns why the debugger
+    /// stopped if it hit a breakpoint or the program exited.
+    pub fn run_frame(&mut self, cpu: &mut Cpu, keys: [bool; 16]) -> Result<Option<String>, CpuError> {
+        if self.paused {
+            return Ok(None);
+        }
+        loop {
+            if !self.resumed {
+                if let Some(reason) = self.breakpoint_hit(cpu) {
+                    self.paused = true;
+                    return Ok(Some(format!("{}\n{}", reason, current_line(cpu))));
+                }
+            }
+            self.resumed = false;
+            let pc = cpu.pc();
+            let registers: Vec<usize> = self.register_breakpoints.iter().map(|breakpoint| breakpoint.register.value(cpu)).collect();
+            if let Err(error) = self.cycle(cpu, keys) {
+                self.paused = true;
+                return Err(error);
+            }
+            // Watchpoints and register breakpoints stop after the instruction
+            if let Some(reason) = self.watch_hit(cpu, &registers) {
+                self.paused = true;
+                return Ok(Some(format!("{} by\n{}\n{}", reason, disassemble(cpu, pc, 1).replacen('>', " ", 1), current_line(cpu))));
+            }
+            if cpu.state().halted {
+                self.paused = true;
+                return Ok(Some(format!("Program exited\n{}", current_line(cpu))));
+            }
+            if self.cycles == 0 {
+                return Ok(None);
+            }
+        }
+    }
+
+    fn cycle(&mut self, cpu: &mut Cpu, keys: [bool; 16]) -> Result<(), CpuError> {
+        cpu.cycle(keys)?;
+        self.cycles += 1;
+        if self.cycles >= self.instructions_per_frame {
+            cpu.tick_timers();
+            self.cycles = 0;
+        }
+        Ok(())
+    }
+
+    /// `registers` holds the values of the register breakpoints before the instruction ran
+    fn watch_hit(&self, cpu: &Cpu, registers: &[usize]) -> Option<String> {
+        for watchpoint in self.watchpoints.iter() {
+            let hit = cpu.memory_accesses().iter().find(|access| {
+                watchpoint.kind.matches(access.kind)
+                    && access.address < watchpoint.address + watchpoint.length
+                    && watchpoint.address < access.address + access.length
+            });
+            if let Some(access) = hit {
+                let verb = if access.kind == AccessKind::Read { "read" } else { "written" };
+                return Some(format!("Hit {}, {:#05X}-{:#05X} {}", watchpoint, access.address, access.address + access.length - 1, verb));
+            }
+        }
+        for (breakpoint, &before) in self.register_breakpoints.iter().zip(registers.iter()) {
+            let after = breakpoint.register.value(cpu);
+            if after != before && breakpoint.value.is_none_or(|value| value == after) {
+                return Some(format!("{} changed from {:#X} to {:#X}", breakpoint.register, before, after));
+            }
+        }
+        None
+    }
+
+    fn breakpoint_hit(&self, cpu: &Cpu) -> Option<String> {
+        let pc = cpu.pc();
+        if self.breakpoints.contains(&pc) {
+            return Some(format!("Breakpoint at {:#05X}", pc));
+        }
+        let opcode = cpu.opcode_at(pc)?;
+        self.opcode_breakpoints
+            .iter()
+            .find(|pattern| pattern.matches(opcode))
+            .map(|pattern| format!("Opcode {:04X} matches {}", opcode, pattern))
+    }
+}
+
+pub fn registers(cpu: &Cpu) -> String {
+    let mut output = String::new();
+    for (x, value) in cpu.registers().iter().enumerate() {
+        let separator = if x % 8 == 7 { "\n" } else { " " };
+        write!(output, "V{:X}={:02X}{}", x, value, separator).unwrap();
+    }
+    let state = cpu.state();
+    write!(
+        output,
+        "I={:04X} PC={:04X} SP={:X} DT={:02X} ST={:02X}{}",
+        cpu.i(),
+        cpu.pc(),
+        cpu.sp(),
+        cpu.delay_timer,
+        cpu.sound_timer,
+        if state.halted { " halted" } else if cpu.is_waiting_for_key() { " waiting for key" } else { "" },
+    )
+    .unwrap();
+    output
+}
+
+pub fn stack(cpu: &Cpu) -> String {
+    if cpu.stack().is_empty() {
+        return "Stack is empty".to_string();
+    }
+    let lines: Vec<String> = cpu.stack().iter().enumerate().rev()
+        .map(|(level, address)| format!("{:X}: {:#05X}", level, address))
+        .collect();
+    lines.join("\n")
+}
+
+/// 16 bytes per line with an ASCII column
+pub fn hex_dump(memory: &[u8], address: usize, length: usize) -> String {
+    let end = address.saturating_add(length).min(memory.len());
+    let mut lines = Vec::new();
+    for start in (address.min(end)..end).step_by(16) {
+        let bytes = &memory[start..(start + 16).min(end)];
+        let hex: Vec<String> = bytes.iter().map(|byte| format!("{:02X}", byte)).collect();
+        let ascii: String = bytes.iter()
+            .map(|&byte| if byte.is_ascii_graphic() || byte == b' ' { byte as char } else { '.' })
+            .collect();
+        lines.push(format!("{:04X}  {:<47}  {}", start, hex.join(" "), ascii));
+    }
+    if lines.is_empty() { format!("{:#06X} is out of memory", address) } else { lines.join("\n") }
+}
+
+/// `count` instructions with `address` in the middle, the PC marked with `>`
+pub fn disassemble(cpu: &Cpu, address: usize, count: usize) -> String {
+    let mut lines = Vec::new();
+    let mut address = address.saturating_sub(count / 2 * 2);
+    for _ in 0..count {
+        let opcode = match cpu.opcode_at(address) {
+            Some(opcode) => opcode,
+            None => break,
+        };
+        let instruction = Instruction::decode(opcode);
+        let long_address = cpu.opcode_at(address + 2);
+        let text = disassembler::mnemonic(instruction, long_address, Syntax::Cowgod);
+        let marker = if address == cpu.pc() { ">" } else { " " };
+        lines.push(format!("{} {:04X}  {:04X}  {}", marker, address, opcode, text));
+        address += instruction.size();
+    }
+    lines.join("\n")
+}
+
+fn current_line(cpu: &Cpu) -> String {
+    disassemble(cpu, cpu.pc(), 1)
+}
+
+#[cfg(test)]
+#[path = "./debugger_tests.rs"]
+mod debugger_tests;
@@ -0,0 +1,181 @@
+use super::*;
+use crate::test_utils::cpu_with_program;
+
+const KEYS: [bool; 16] = [false; 16];
+
+const PROGRAM: &str = "
+    LD V0, 1
+    loop:
+        ADD V0, 1
+        CALL sub
+        JP loop
+    sub:
+        LD I, 0x300
+        DRW V0, V0, 5
+        RET
+";
+
+#[test]
+fn test_parse_commands() {
+    assert_eq!(Command::parse("s"), Ok(Command::Step(1)));
+    assert_eq!(Command::parse("step #12"), Ok(Command::Step(12)));
+    assert_eq!(Command::parse("b 0x20a"), Ok(Command::Break(0x20a)));
+    assert_eq!(Command::parse("bo D??5"), Ok(Command::BreakOpcode(OpcodePattern::parse("D??5").unwrap())));
+    assert_eq!(Command::parse("d D??5"), Ok(Command::DeleteOpcode(OpcodePattern::parse("D??5").unwrap())));
+    assert_eq!(Command::parse("x 200 10"), Ok(Command::Memory { address: 0x200, length: 0x10 }));
+    assert_eq!(Command::parse("l"), Ok(Command::Disassemble { address: None, count: 9 }));
+    assert!(Command::parse("step lots").is_err());
+    assert!(Command::parse("bo D?5").is_err());
+    assert!(Command::parse("jump").is_err());
+}
+
+#[test]
+fn test_opcode_pattern() {
+    let pattern = OpcodePattern::parse("d??5").unwrap();
+    assert!(pattern.matches(0xd125));
+    assert!(!pattern.matches(0xd12f));
+    assert_eq!(pattern.to_string(), "D??5");
+}
+
+#[test]
+fn test_step() {
+    let mut cpu = cpu_with_program(PROGRAM);
+    let mut debugger = Debugger::new(10);
+    assert_eq!(debugger.execute(Command::Step(3), &mut cpu, KEYS), "> 0208  A300  LD I, 0x300");
+    assert_eq!(cpu.registers()[0], 2);
+    assert_eq!(cpu.stack(), &[0x206]);
+    assert!(debugger.is_paused());
+}
+
+#[test]
+fn test_break_at_address() {
+    let mut cpu = cpu_with_program(PROGRAM);
+    let mut debugger = Debugger::new(10);
+    debugger.execute(Command::Break(0x204), &mut cpu, KEYS);
+    debugger.execute(Command::Continue, &mut cpu, KEYS);
+    assert!(!debugger.is_paused());
+
+    let reason = debugger.run_frame(&mut cpu, KEYS).unwrap().unwrap();
+    assert!(reason.starts_with("Breakpoint at 0x204"));
+    assert_eq!(cpu.pc(), 0x204);
+    assert!(debugger.is_paused());
+    assert_eq!(debugger.run_frame(&mut cpu, KEYS), Ok(None));
+
+    // Continuing runs the instruction at the breakpoint before stopping again
+    debugger.execute(Command::Continue, &mut cpu, KEYS);
+    debugger.run_frame(&mut cpu, KEYS).unwrap().unwrap();
+    assert_eq!(cpu.pc(), 0x204);
+    assert_eq!(cpu.registers()[0], 3);
+}
+
+#[test]
+fn test_break_on_opcode() {
+    let mut cpu = cpu_with_program(PROGRAM);
+    let mut debugger = Debugger::new(100);
+    debugger.execute(Command::BreakOpcode(OpcodePattern::parse("D???").unwrap()), &mut cpu, KEYS);
+    debugger.execute(Command::Continue, &mut cpu, KEYS);
+    let reason = debugger.run_frame(&mut cpu, KEYS).unwrap().unwrap();
+    assert!(reason.starts_with("Opcode D005 matches D???"));
+    assert_eq!(cpu.pc(), 0x20a);
+}
+
+#[test]
+fn test_timers_tick_per_frame() {
+    let mut cpu = cpu_with_program("LD V0, 5\nLD DT, V0\nloop: JP loop");
+    let mut debugger = Debugger::new(4);
+    debugger.execute(Command::Step(3), &mut cpu, KEYS);
+    assert_eq!(cpu.delay_timer, 5);
+    debugger.execute(Command::Step(1), &mut cpu, KEYS);
+    assert_eq!(cpu.delay_timer, 4);
+}
+
+#[test]
+fn test_inspection_output() {
+    let mut cpu = cpu_with_program(PROGRAM);
+    let mut debugger = Debugger::new(10);
+    debugger.execute(Command::Step(3), &mut cpu, KEYS);
+
+    let registers = debugger.execute(Command::Registers, &mut cpu, KEYS);
+    assert!(registers.starts_with("V0=02 V1=00"));
+    assert!(registers.ends_with("I=0000 PC=0208 SP=1 DT=00 ST=00"));
+    assert_eq!(debugger.execute(Command::Stack, &mut cpu, KEYS), "0: 0x206");
+
+    let dump = debugger.execute(Command::Memory { address: 0x200, length: 4 }, &mut cpu, KEYS);
+    assert_eq!(dump, format!("0200  {:<47}  `.p.", "60 01 70 01"));
+
+    let listing = debugger.execute(Command::Disassemble { address: None, count: 3 }, &mut cpu, KEYS);
+    assert_eq!(listing, "  0206  1202  JP 0x202\n> 0208  A300  LD I, 0x300\n  020A  D005  DRW V0, V0, 5");
+}
+
+const BCD_PROGRAM: &str = "
+    LD I, 0x300
+    LD V0, 123
+    loop:
+        ADD V1, 1
+        LD B, V0
+        LD V2, [I]
+        JP loop
+";
+
+#[test]
+fn test_parse_watch_commands() {
+    assert_eq!(
+        Command::parse("w 300"),
+        Ok(Command::Watch(Watchpoint { address: 0x300, length: 1, kind: WatchKind::Write }))
+    );
+    assert_eq!(
+        Command::parse("awatch 300 10"),
+        Ok(Command::Watch(Watchpoint { address: 0x300, length: 0x10, kind: WatchKind::Access }))
+    );
+    assert_eq!(
+        Command::parse("rb va 2"),
+        Ok(Command::BreakRegister(RegisterBreakpoint { register: Register::V(0xa), value: Some(2) }))
+    );
+    assert_eq!(Command::parse("d i"), Ok(Command::DeleteRegister(Register::I)));
+    assert!(Command::parse("rb vg").is_err());
+}
+
+#[test]
+fn test_write_watchpoint() {
+    let mut cpu = cpu_with_program(BCD_PROGRAM);
+    let mut debugger = Debugger::new(100);
+    debugger.execute(Command::parse("w 302").unwrap(), &mut cpu, KEYS);
+    debugger.execute(Command::Continue, &mut cpu, KEYS);
+
+    let reason = debugger.run_frame(&mut cpu, KEYS).unwrap().unwrap();
+    assert!(reason.starts_with("Hit write watchpoint 0x302, 0x300-0x302 written by\n  0206  F033  LD B, V0"));
+    assert_eq!(cpu.pc(), 0x208);
+    assert_eq!(cpu.memory()[0x302], 3);
+}
+
+#[test]
+fn test_read_watchpoint() {
+    let mut cpu = cpu_with_program(BCD_PROGRAM);
+    let mut debugger = Debugger::new(100);
+    debugger.execute(Command::parse("rwatch 300").unwrap(), &mut cpu, KEYS);
+    debugger.execute(Command::Continue, &mut cpu, KEYS);
+
+    debugger.run_frame(&mut cpu, KEYS).unwrap().unwrap();
+    assert_eq!(cpu.pc(), 0x20a);
+    assert_eq!(&cpu.registers()[..3], &[1, 2, 3]);
+}
+
+#[test]
+fn test_register_breakpoint() {
+    let mut cpu = cpu_with_program(BCD_PROGRAM);
+    let mut debugger = Debugger::new(100);
+    debugger.execute(Command::parse("rb v1 3").unwrap(), &mut cpu, KEYS);
+    debugger.execute(Command::Continue, &mut cpu, KEYS);
+
+    let reason = debugger.run_frame(&mut cpu, KEYS).unwrap().unwrap();
+    assert!(reason.starts_with("V1 changed from 0x2 to 0x3"));
+    assert_eq!(cpu.registers()[1], 3);
+
+    // Without a value any change stops
+    debugger.execute(Command::parse("rb i").unwrap(), &mut cpu, KEYS);
+    debugger.execute(Command::parse("d v1").unwrap(), &mut cpu, KEYS);
+    let mut cpu = cpu_with_program(BCD_PROGRAM);
+    debugger.execute(Command::Continue, &mut cpu, KEYS);
+    let reason = debugger.run_frame(&mut cpu, KEYS).unwrap().unwrap();
+    assert!(reason.starts_with("I changed from 0x0 to 0x300"));
+}
@@ -0,0 +1,248 @@
+use crate::analysis::{self, Analysis, EdgeKind};
+use crate::cpu::PROGRAM_START;
+use crate::instruction::Instruction;
+use std::fmt::Write;
+
+/// Assembly syntax used for mnemonics
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Syntax {
+    /// Cowgod's Chip-8 Technical Reference, e.g. `LD V1, 0x05`
+    Cowgod,
+    /// Octo assembly language, e.g. `v1 := 0x05`
+    Octo,
+}
+
+/// One disassembled instruction or data word
+pub struct Line {
+    pub address: usize,
+    pub bytes: Vec<u8>,
+    pub text: String,
+    /// Reachable from PROGRAM_START by following the control flow
+    pub reachable: bool,
+    /// Bnnn computed jump, the analysis can't follow it
+    pub unresolved: bool,
+}
+
+/// Linear sweep over the whole rom from PROGRAM_START. Every word is decoded,
+/// `reachable` marks which ones the control flow analysis found to be code.
+/// The sweep gets back in step at every reachable instruction: bytes that
+/// would overlap one are shown as data, and a reachable instruction that
+/// overlaps another is listed with its full bytes before the one inside it.
+pub fn disassemble(rom: &[u8], syntax: Syntax) -> Vec<Line> {
+    let analysis = analysis::analyze(rom);
+    let mut lines = Vec::new();
+    let mut offset = 0;
+
+    while offset < rom.len() {
+        let address = PROGRAM_START + offset;
+        let (mut size, mut text) = match analysis::instruction_at(rom, address) {
+            Some(Instruction::LdILong) if offset + 4 <= rom.len() => {
+                let operand = (rom[offset + 2] as u16) << 8 | rom[offset + 3] as u16;
+                (4, mnemonic(Instruction::LdILong, Some(operand), syntax))
+            }
+            Some(instruction) => (2, mnemonic(instruction, None, syntax)),
+            // Odd byte at the end of the rom
+            None => (1, data_bytes(&rom[offset..offset + 1], syntax)),
+        };
+        let reachable = analysis.code.contains(&address);
+        let mut step = size;
+        if let Some(&next_code) = analysis.code.range(address + 1..address + size).next() {
+            step = next_code - address;
+            if !reachable {
+                size = step;
+                text = data_bytes(&rom[offset..offset + size], syntax);
+            }
+        }
+
+        lines.push(Line {
+            address,
+            bytes: rom[offset..offset + size].to_vec(),
+            text,
+            reachable,
+            unresolved: analysis.unresolved.contains(&address),
+        });
+        offset += step;
+    }
+    lines
+}
+
+/// Control flow graph in Graphviz DOT format
+pub fn to_dot(analysis: &Analysis, syntax: Syntax) -> String {
+    let mut dot = String::new();
+    writeln!(dot, "digraph rom {{").unwrap();
+    writeln!(dot, "    node [shape=box fontname=\"monospace\"];").unwrap();
+
+    for block in analysis.blocks.values() {
+        let mut label = String::new();
+        if analysis.subroutines.contains(&block.start) {
+            write!(label, "sub_{:03X}:\\l", block.start).unwrap();
+        }
+        for &(address, instruction) in block.instructions.iter() {
+            let long_address = analysis.long_address(address, instruction);
+            let text = mnemonic(instruction, long_address, syntax);
+            write!(label, "{:03X}  {}\\l", address, text.replace('"', "\\\"")).unwrap();
+        }
+        writeln!(dot, "    b{:03X} [label=\"{}\"];", block.start, label).unwrap();
+
+        for edge in block.edges.iter() {
+            let style = match edge.kind {
+                EdgeKind::Fallthrough => "",
+                EdgeKind::Jump => " [label=\"jump\"]",
+                EdgeKind::Call => " [label=\"call\" style=dashed]",
+                EdgeKind::Skip => " [label=\"skip\"]",
+            };
+            writeln!(dot, "    b{:03X} -> b{:03X}{};", block.start, edge.target, style).unwrap();
+        }
+        let (last, _) = *block.instructions.last().unwrap();
+        if analysis.unresolved.contains(&last) {
+            writeln!(dot, "    u{:03X} [label=\"?\" shape=circle];", last).unwrap();
+            writeln!(dot, "    b{:03X} -> u{:03X} [label=\"computed\" style=dotted];", block.start, last).unwrap();
+        }
+    }
+    writeln!(dot, "}}").unwrap();
+    dot
+}
+
+/// Raw bytes as a data directive
+pub fn data_bytes(bytes: &[u8], syntax: Syntax) -> String {
+    let values: Vec<String> = bytes.iter().map(|byte| format!("0x{:02X}", byte)).collect();
+    match syntax {
+        Syntax::Cowgod => format!("DB {}", values.join(", ")),
+        Syntax::Octo => values.join(" "),
+    }
+}
+
+/// Mnemonic for an instruction. `long_address` is the word following
+/// F000 (LD I, long addr), it is ignored for other instructions.
+pub fn mnemonic(instruction: Instruction, long_address: Option<u16>, syntax: Syntax) -> String {
+    match syntax {
+        Syntax::Cowgod => cowgod(instruction, long_address),
+        Syntax::Octo => octo(instruction, long_address),
+    }
+}
+
+fn cowgod(instruction: Instruction, long_address: Option<u16>) -> String {
+    match instruction {
+        Instruction::Sys(nnn) => format!("SYS 0x{:03X}", nnn),
+        Instruction::Scd(n) => format!("SCD {}", n),
+        Instruction::Cls => "CLS".to_string(),
+        Instruction::Ret => "RET".to_string(),
+        Instruction::Scr => "SCR".to_string(),
+        Instruction::Scl => "SCL".to_string(),
+        Instruction::Exit => "EXIT".to_string(),
+        Instruction::Low => "LOW".to_string(),
+        Instruction::High => "HIGH".to_string(),
+        Instruction::Jp(nnn) => format!("JP 0x{:03X}", nnn),
+        Instruction::Call(nnn) => format!("CALL 0x{:03X}", nnn),
+        Instruction::SeVxByte { x, kk } => format!("SE V{:X}, 0x{:02X}", x, kk),
+        Instruction::SneVxByte { x, kk } => format!("SNE V{:X}, 0x{:02X}", x, kk),
+        Instruction::SeVxVy { x, y } => format!("SE V{:X}, V{:X}", x, y),
+        Instruction::Save { x, y } => format!("SAVE V{:X} - V{:X}", x, y),
+        Instruction::Load { x, y } => format!("LOAD V{:X} - V{:X}", x, y),
+        Instruction::LdVxByte { x, kk } => format!("LD V{:X}, 0x{:02X}", x, kk),
+        Instruction::AddVxByte { x, kk } => format!("ADD V{:X}, 0x{:02X}", x, kk),
+        Instruction::LdVxVy { x, y } => format!("LD V{:X}, V{:X}", x, y),
+        Instruction::Or { x, y } => format!("OR V{:X}, V{:X}", x, y),
+        Instruction::And { x, y } => format!("AND V{:X}, V{:X}", x, y),
+        Instruction::Xor { x, y } => format!("XOR V{:X}, V{:X}", x, y),
+        Instruction::AddVxVy { x, y } => format!("ADD V{:X}, V{:X}", x, y),
+        Instruction::Sub { x, y } => format!("SUB V{:X}, V{:X}", x, y),
+        Instruction::Shr { x, y } => format!("SHR V{:X}, V{:X}", x, y),
+        Instruction::Subn { x, y } => format!("SUBN V{:X}, V{:X}", x, y),
+        Instruction::Shl { x, y } => format!("SHL V{:X}, V{:X}", x, y),
+        Instruction::SneVxVy { x, y } => format!("SNE V{:X}, V{:X}", x, y),
+        Instruction::LdI(nnn) => format!("LD I, 0x{:03X}", nnn),
+        Instruction::JpV0(nnn) => format!("JP V0, 0x{:03X}", nnn),
+        Instruction::Rnd { x, kk } => format!("RND V{:X}, 0x{:02X}", x, kk),
+        Instruction::Drw { x, y, n } => format!("DRW V{:X}, V{:X}, {}", x, y, n),
+        Instruction::Skp(x) => format!("SKP V{:X}", x),
+        Instruction::Sknp(x) => format!("SKNP V{:X}", x),
+        Instruction::LdILong => match long_address {
+            Some(address) => format!("LD I, LONG 0x{:04X}", address),
+            None => "DW 0xF000".to_string(),
+        },
+        Instruction::Plane(n) => format!("PLANE {}", n),
+        Instruction::Audio => "AUDIO".to_string(),
+        Instruction::LdVxDt(x) => format!("LD V{:X}, DT", x),
+        Instruction::LdVxK(x) => format!("LD V{:X}, K", x),
+        Instruction::LdDtVx(x) => format!("LD DT, V{:X}", x),
+        Instruction::LdStVx(x) => format!("LD ST, V{:X}", x),
+        Instruction::AddIVx(x) => format!("ADD I, V{:X}", x),
+        Instruction::LdFVx(x) => format!("LD F, V{:X}", x),
+        Instruction::LdHfVx(x) => format!("LD HF, V{:X}", x),
+        Instruction::LdBVx(x) => format!("LD B, V{:X}", x),
+        Instruction::Pitch(x) => format!("PITCH V{:X}", x),
+        Instruction::LdMemVx(x) => format!("LD [I], V{:X}", x),
+        Instruction::LdVxMem(x) => format!("LD V{:X}, [I]", x),
+        Instruction::LdRVx(x) => format!("LD R, V{:X}", x),
+        Instruction::LdVxR(x) => format!("LD V{:X}, R", x),
+        Instruction::Unknown(opcode) => format!("DW 0x{:04X}", opcode),
+    }
+}
+
+fn octo(instruction: Instruction, long_address: Option<u16>) -> String {
+    match instruction {
+        Instruction::Scd(n) => format!("scroll-down {}", n),
+        Instruction::Cls => "clear".to_string(),
+        Instruction::Ret => "return".to_string(),
+        Instruction::Scr => "scroll-right".to_string(),
+        Instruction::Scl => "scroll-left".to_string(),
+        Instruction::Exit => "exit".to_string(),
+        Instruction::Low => "lores".to_string(),
+        Instruction::High => "hires".to_string(),
+        Instruction::Jp(nnn) => format!("jump 0x{:03X}", nnn),
+        Instruction::Call(nnn) => format!(":call 0x{:03X}", nnn),
+        // Octo conditions say when the next instruction runs, skips say when it doesn't
+        Instruction::SeVxByte { x, kk } => format!("if v{:x} != 0x{:02X} then", x, kk),
+        Instruction::SneVxByte { x, kk } => format!("if v{:x} == 0x{:02X} then", x, kk),
+        Instruction::SeVxVy { x, y } => format!("if v{:x} != v{:x} then", x, y),
+        Instruction::Save { x, y } => format!("save v{:x} - v{:x}", x, y),
+        Instruction::Load { x, y } => format!("load v{:x} - v{:x}", x, y),
+        Instruction::LdVxByte { x, kk } => format!("v{:x} := 0x{:02X}", x, kk),
+        Instruction::AddVxByte { x, kk } => format!("v{:x} += 0x{:02X}", x, kk),
+        Instruction::LdVxVy { x, y } => format!("v{:x} := v{:x}", x, y),
+        Instruction::Or { x, y } => format!("v{:x} |= v{:x}", x, y),
+        Instruction::And { x, y } => format!("v{:x} &= v{:x}", x, y),
+        Instruction::Xor { x, y } => format!("v{:x} ^= v{:x}", x, y),
+        Instruction::AddVxVy { x, y } => format!("v{:x} += v{:x}", x, y),
+        Instruction::Sub { x, y } => format!("v{:x} -= v{:x}", x, y),
+        Instruction::Shr { x, y } => format!("v{:x} >>= v{:x}", x, y),
+        Instruction::Subn { x, y } => format!("v{:x} =- v{:x}", x, y),
+        Instruction::Shl { x, y } => format!("v{:x} <<= v{:x}", x, y),
+        Instruction::SneVxVy { x, y } => format!("if v{:x} == v{:x} then", x, y),
+        Instruction::LdI(nnn) => format!("i := 0x{:03X}", nnn),
+        Instruction::JpV0(nnn) => format!("jump0 0x{:03X}", nnn),
+        Instruction::Rnd { x, kk } => format!("v{:x} := random 0x{:02X}", x, kk),
+        Instruction::Drw { x, y, n } => format!("sprite v{:x} v{:x} {}", x, y, n),
+        Instruction::Skp(x) => format!("if v{:x} -key then", x),
+        Instruction::Sknp(x) => format!("if v{:x} key then", x),
+        Instruction::LdILong => match long_address {
+            Some(address) => format!("i := long 0x{:04X}", address),
+            None => "0xF0 0x00".to_string(),
+        },
+        Instruction::Plane(n) => format!("plane {}", n),
+        Instruction::Audio => "audio".to_string(),
+        Instruction::LdVxDt(x) => format!("v{:x} := delay", x),
+        Instruction::LdVxK(x) => format!("v{:x} := key", x),
+        Instruction::LdDtVx(x) => format!("delay := v{:x}", x),
+        Instruction::LdStVx(x) => format!("buzzer := v{:x}", x),
+        Instruction::AddIVx(x) => format!("i += v{:x}", x),
+        Instruction::LdFVx(x) => format!("i := hex v{:x}", x),
+        Instruction::LdHfVx(x) => format!("i := bighex v{:x}", x),
+        Instruction::LdBVx(x) => format!("bcd v{:x}", x),
+        Instruction::Pitch(x) => format!("pitch := v{:x}", x),
+        Instruction::LdMemVx(x) => format!("save v{:x}", x),
+        Instruction::LdVxMem(x) => format!("load v{:x}", x),
+        Instruction::LdRVx(x) => format!("saveflags v{:x}", x),
+        Instruction::LdVxR(x) => format!("loadflags v{:x}", x),
+        // Octo has no machine code calls, write them out as data
+        Instruction::Sys(_) | Instruction::Unknown(_) => {
+            let opcode = instruction.encode();
+            data_bytes(&[(opcode >> 8) as u8, opcode as u8], Syntax::Octo)
+        }
+    }
+}
+
+#[cfg(test)]
+#[path = "./disassembler_tests.rs"]
+mod disassembler_tests;
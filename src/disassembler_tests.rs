@@ -0,0 +1,123 @@
+use super::*;
+
+// 200: LD V0, 0x01
+// 202: SE V0, 0x01
+// 204: JP 0x20A
+// 206: CALL 0x20E
+// 208: JP 0x206
+// 20A: JP V0, 0x300
+// 20C: data
+// 20E: RET
+const ANALYSIS_ROM: [u8; 16] = [
+    0x60, 0x01, 0x30, 0x01, 0x12, 0x0a, 0x22, 0x0e,
+    0x12, 0x06, 0xb3, 0x00, 0xff, 0xff, 0x00, 0xee,
+];
+
+#[test]
+fn test_cowgod_mnemonics() {
+    let cases = [
+        (0x00e0, "CLS"),
+        (0x1234, "JP 0x234"),
+        (0x3a05, "SE VA, 0x05"),
+        (0x8126, "SHR V1, V2"),
+        (0xa2f0, "LD I, 0x2F0"),
+        (0xd125, "DRW V1, V2, 5"),
+        (0xf355, "LD [I], V3"),
+        (0x5121, "DW 0x5121"),
+    ];
+    for &(opcode, text) in cases.iter() {
+        assert_eq!(mnemonic(Instruction::decode(opcode), None, Syntax::Cowgod), text);
+    }
+}
+
+#[test]
+fn test_octo_mnemonics() {
+    let cases = [
+        (0x00e0, "clear"),
+        (0x2234, ":call 0x234"),
+        (0x3a05, "if va != 0x05 then"),
+        (0x8127, "v1 =- v2"),
+        (0xc10f, "v1 := random 0x0F"),
+        (0xe1a1, "if v1 key then"),
+        (0xf129, "i := hex v1"),
+        (0x0123, "0x01 0x23"),
+    ];
+    for &(opcode, text) in cases.iter() {
+        assert_eq!(mnemonic(Instruction::decode(opcode), None, Syntax::Octo), text);
+    }
+}
+
+#[test]
+fn test_disassemble_marks_data() {
+    // CALL 0x206, loop forever, sprite data, RET
+    let rom = [0x22, 0x06, 0x12, 0x02, 0xff, 0x81, 0x00, 0xee, 0x12];
+    let lines = disassemble(&rom, Syntax::Cowgod);
+    let listing: Vec<(usize, &str, bool)> = lines
+        .iter()
+        .map(|line| (line.address, line.text.as_str(), line.reachable))
+        .collect();
+    assert_eq!(listing, vec![
+        (0x200, "CALL 0x206", true),
+        (0x202, "JP 0x202", true),
+        (0x204, "DW 0xFF81", false),
+        (0x206, "RET", true),
+        (0x208, "DB 0x12", false),
+    ]);
+}
+
+#[test]
+fn test_disassemble_long_load() {
+    let rom = [0xf0, 0x00, 0x12, 0x34, 0x00, 0xfd];
+    let lines = disassemble(&rom, Syntax::Octo);
+    assert_eq!(lines[0].text, "i := long 0x1234");
+    assert_eq!(lines[0].bytes, vec![0xf0, 0x00, 0x12, 0x34]);
+    assert_eq!(lines[1].address, 0x204);
+    assert!(lines[1].reachable);
+}
+
+#[test]
+fn test_disassemble_odd_address_code() {
+    // JP 0x205, three bytes of data, LD V0, 0x01 at an odd address, loop
+    let rom = [0x12, 0x05, 0xff, 0x81, 0xcc, 0x60, 0x01, 0x12, 0x07];
+    let lines = disassemble(&rom, Syntax::Cowgod);
+    let listing: Vec<(usize, &str, bool)> = lines
+        .iter()
+        .map(|line| (line.address, line.text.as_str(), line.reachable))
+        .collect();
+    assert_eq!(listing, vec![
+        (0x200, "JP 0x205", true),
+        (0x202, "DW 0xFF81", false),
+        (0x204, "DB 0xCC", false),
+        (0x205, "LD V0, 0x01", true),
+        (0x207, "JP 0x207", true),
+    ]);
+}
+
+#[test]
+fn test_disassemble_overlapping_code() {
+    // LD V0, 0x12, CLS, JP 0x201 into the operand byte of the first
+    // instruction, which decodes as JP 0x200
+    let rom = [0x60, 0x12, 0x00, 0xe0, 0x12, 0x01];
+    let lines = disassemble(&rom, Syntax::Cowgod);
+    let listing: Vec<(usize, &[u8], &str, bool)> = lines
+        .iter()
+        .map(|line| (line.address, line.bytes.as_slice(), line.text.as_str(), line.reachable))
+        .collect();
+    assert_eq!(listing, vec![
+        (0x200, &[0x60, 0x12][..], "LD V0, 0x12", true),
+        (0x201, &[0x12, 0x00][..], "JP 0x200", true),
+        (0x202, &[0x00, 0xe0][..], "CLS", true),
+        (0x204, &[0x12, 0x01][..], "JP 0x201", true),
+    ]);
+}
+
+#[test]
+fn test_dot_export() {
+    let dot = to_dot(&analysis::analyze(&ANALYSIS_ROM), Syntax::Cowgod);
+    assert!(dot.starts_with("digraph rom {"));
+    assert!(dot.contains("b200 -> b206 [label=\"skip\"];"));
+    assert!(dot.contains("b206 -> b20E [label=\"call\" style=dashed];"));
+    assert!(dot.contains("b20A -> u20A [label=\"computed\" style=dotted];"));
+    assert!(dot.contains("sub_20E:\\l20E  RET\\l"));
+    assert!(dot.trim_end().ends_with('}'));
+}
@@ -0,0 +1,49 @@
+use std::error::Error;
+use std::fmt;
+
+/// Faults raised while executing an instruction.
+/// The program counter is left pointing at the faulting instruction.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CpuError {
+    /// CALL with all 16 stack levels in use
+    StackOverflow { pc: usize },
+    /// RET with an empty stack
+    StackUnderflow { pc: usize },
+    /// Instruction fetch or memory access past the end of memory
+    MemoryOutOfBounds { addr: usize, pc: usize },
+    /// Ex9E/ExA1 with a register value that isn't a key 0-F
+    InvalidKey { key: u8, pc: usize },
+    /// Opcode that the interpreter doesn't implement
+    UnknownOpcode { opcode: u16, pc: usize },
+}
+
+impl CpuError {
+    /// Address of the faulting instruction
+    pub fn pc(&self) -> usize {
+        match *self {
+            CpuError::StackOverflow { pc }
+            | CpuError::StackUnderflow { pc }
+            | CpuError::MemoryOutOfBounds { pc, .. }
+            | CpuError::InvalidKey { pc, .. }
+            | CpuError::UnknownOpcode { pc, .. } => pc,
+        }
+    }
+}
+
+impl fmt::Display for CpuError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            CpuError::StackOverflow { pc } => write!(f, "stack overflow at {:#05X}", pc),
+            CpuError::StackUnderflow { pc } => write!(f, "stack underflow at {:#05X}", pc),
+            CpuError::MemoryOutOfBounds { addr, pc } => {
+                write!(f, "memory access out of bounds at {:#05X} (address {:#06X})", pc, addr)
+            }
+            CpuError::InvalidKey { key, pc } => write!(f, "invalid key {:#04X} at {:#05X}", key, pc),
+            CpuError::UnknownOpcode { opcode, pc } => {
+                write!(f, "unknown opcode {:04X} at {:#05X}", opcode, pc)
+            }
+        }
+    }
+}
+
+impl Error for CpuError {}
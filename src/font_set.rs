@@ -79,4 +79,171 @@ pub const FONT_SET: [u8; 80] = [
     0xF0,
     0x80,
     0x80,
-];
\ No newline at end of file
+];
+
+// SUPER-CHIP 8x10 digits 0-9 and the XO-CHIP hex digits A-F, stored in
+// memory right after FONT_SET
+pub const BIG_FONT_ADDRESS: usize = 0x50;
+
+pub const BIG_FONT_SET: [u8; 160] = [
+    0x3C,
+    0x7E,
+    0xE7,
+    0xC3,
+    0xC3,
+    0xC3,
+    0xC3,
+    0xE7,
+    0x7E,
+    0x3C,
+    0x18,
+    0x38,
+    0x58,
+    0x18,
+    0x18,
+    0x18,
+    0x18,
+    0x18,
+    0x18,
+    0x3C,
+    0x3E,
+    0x7F,
+    0xC3,
+    0x06,
+    0x0C,
+    0x18,
+    0x30,
+    0x60,
+    0xFF,
+    0xFF,
+    0x3C,
+    0x7E,
+    0xC3,
+    0x03,
+    0x0E,
+    0x0E,
+    0x03,
+    0xC3,
+    0x7E,
+    0x3C,
+    0x06,
+    0x0E,
+    0x1E,
+    0x36,
+    0x66,
+    0xC6,
+    0xFF,
+    0xFF,
+    0x06,
+    0x06,
+    0xFF,
+    0xFF,
+    0xC0,
+    0xC0,
+    0xFC,
+    0xFE,
+    0x03,
+    0xC3,
+    0x7E,
+    0x3C,
+    0x3E,
+    0x7C,
+    0xE0,
+    0xC0,
+    0xFC,
+    0xFE,
+    0xC3,
+    0xC3,
+    0x7E,
+    0x3C,
+    0xFF,
+    0xFF,
+    0x03,
+    0x06,
+    0x0C,
+    0x18,
+    0x30,
+    0x60,
+    0x60,
+    0x60,
+    0x3C,
+    0x7E,
+    0xC3,
+    0xC3,
+    0x7E,
+    0x7E,
+    0xC3,
+    0xC3,
+    0x7E,
+    0x3C,
+    0x3C,
+    0x7E,
+    0xC3,
+    0xC3,
+    0x7F,
+    0x3F,
+    0x03,
+    0x03,
+    0x3E,
+    0x7C,
+    0x7E,
+    0xFF,
+    0xC3,
+    0xC3,
+    0xC3,
+    0xFF,
+    0xFF,
+    0xC3,
+    0xC3,
+    0xC3,
+    0xFC,
+    0xFC,
+    0xC3,
+    0xC3,
+    0xFC,
+    0xFC,
+    0xC3,
+    0xC3,
+    0xFC,
+    0xFC,
+    0x3C,
+    0xFF,
+    0xC3,
+    0xC0,
+    0xC0,
+    0xC0,
+    0xC0,
+    0xC3,
+    0xFF,
+    0x3C,
+    0xFC,
+    0xFE,
+    0xC3,
+    0xC3,
+    0xC3,
+    0xC3,
+    0xC3,
+    0xC3,
+    0xFE,
+    0xFC,
+    0xFF,
+    0xFF,
+    0xC0,
+    0xC0,
+    0xFF,
+    0xFF,
+    0xC0,
+    0xC0,
+    0xFF,
+    0xFF,
+    0xFF,
+    0xFF,
+    0xC0,
+    0xC0,
+    0xFF,
+    0xFF,
+    0xC0,
+    0xC0,
+    0xC0,
+    0xC0,
+];
@@ -0,0 +1,35 @@
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Frame timer
+/// Paces the main loop at a fixed frame rate. Deadlines are advanced by
+/// exactly one frame from the previous deadline instead of from the time
+/// the frame finished, so time spent emulating and drawing doesn't make
+/// the loop drift slower over time.
+pub struct FrameTimer {
+    frame_duration: Duration,
+    next_frame: Instant,
+}
+
+impl FrameTimer {
+    pub fn new(frames_per_second: u32) -> Self {
+        FrameTimer {
+            frame_duration: Duration::from_secs(1) / frames_per_second,
+            next_frame: Instant::now(),
+        }
+    }
+
+    /// Sleep until the next frame is due.
+    /// If the loop has fallen more than a frame behind (e.g. the window was
+    /// being dragged) the schedule is reset instead of running a burst of
+    /// frames to catch up.
+    pub fn wait(&mut self) {
+        self.next_frame += self.frame_duration;
+        let now = Instant::now();
+        if self.next_frame > now {
+            thread::sleep(self.next_frame - now);
+        } else if now - self.next_frame > self.frame_duration {
+            self.next_frame = now;
+        }
+    }
+}
@@ -0,0 +1,180 @@
+pub const LORES_WIDTH: usize = 64;
+pub const LORES_HEIGHT: usize = 32;
+pub const HIRES_WIDTH: usize = 128;
+pub const HIRES_HEIGHT: usize = 64;
+/// XO-CHIP has two bitplanes, plain CHIP-8 only uses the first
+pub const PLANES: usize = 2;
+
+/// Colors for each plane combination: none, first plane, second plane, both.
+/// Plain CHIP-8 and SUPER-CHIP roms only use the first two.
+pub const PALETTE: [(u8, u8, u8); 4] = [
+    (0, 0, 0),
+    (255, 255, 255),
+    (255, 102, 0),
+    (102, 34, 0),
+];
+
+/// Framebuffer
+/// Storage is always sized for the SUPER-CHIP 128x64 high resolution mode,
+/// in low resolution mode only the top left 64x32 pixels are used.
+///
+/// Each plane keeps a row in one u128 with x = 0 in the highest bit, so
+/// sprites are drawn a whole row at a time with shifts and XOR. Pixels read
+/// through `pixel` or `rows` are a bitmask of the planes that are lit, bit 0
+/// is the first plane and bit 1 the second.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Framebuffer {
+    planes: [[u128; HIRES_HEIGHT]; PLANES],
+    hires: bool,
+}
+
+impl Default for Framebuffer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Framebuffer {
+    pub fn new() -> Self {
+        Framebuffer {
+            planes: [[0; HIRES_HEIGHT]; PLANES],
+            hires: false,
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        if self.hires { HIRES_WIDTH } else { LORES_WIDTH }
+    }
+
+    pub fn height(&self) -> usize {
+        if self.hires { HIRES_HEIGHT } else { LORES_HEIGHT }
+    }
+
+    pub fn is_hires(&self) -> bool {
+        self.hires
+    }
+
+    /// Switch between 64x32 and 128x64 modes. The screen is cleared.
+    pub fn set_hires(&mut self, hires: bool) {
+        self.hires = hires;
+        self.clear();
+    }
+
+    pub fn clear(&mut self) {
+        self.planes = [[0; HIRES_HEIGHT]; PLANES];
+    }
+
+    /// Clear only the planes in mask
+    pub fn clear_planes(&mut self, mask: u8) {
+        for plane in planes_in(mask) {
+            self.planes[plane] = [0; HIRES_HEIGHT];
+        }
+    }
+
+    /// Bitmask of the planes lit at (x, y)
+    pub fn pixel(&self, x: usize, y: usize) -> u8 {
+        let bit = 1u128 << (HIRES_WIDTH - 1 - x);
+        (0..PLANES).fold(0, |pixel, plane| pixel | ((self.planes[plane][y] & bit != 0) as u8) << plane)
+    }
+
+    /// Light the planes in `value` at (x, y) and turn off the others
+    pub fn set_pixel(&mut self, x: usize, y: usize, value: u8) {
+        let bit = 1u128 << (HIRES_WIDTH - 1 - x);
+        for (plane, rows) in self.planes.iter_mut().enumerate() {
+            if value & (1 << plane) != 0 {
+                rows[y] |= bit;
+            } else {
+                rows[y] &= !bit;
+            }
+        }
+    }
+
+    /// Row y of one plane, x = 0 in the highest bit. Bits past the
+    /// current width are always 0.
+    pub fn plane_row(&self, plane: usize, y: usize) -> u128 {
+        self.planes[plane][y]
+    }
+
+    /// Replace row y of one plane, bits past the current width are dropped
+    pub fn set_plane_row(&mut self, plane: usize, y: usize, bits: u128) {
+        self.planes[plane][y] = bits & (!0u128 << (HIRES_WIDTH - self.width()));
+    }
+
+    /// Rows of the visible area as plane bitmasks, each trimmed to the
+    /// current width. Converts from the packed storage, meant for
+    /// frontends drawing once a frame.
+    pub fn rows(&self) -> impl Iterator<Item = Vec<u8>> + '_ {
+        (0..self.height()).map(move |y| (0..self.width()).map(|x| self.pixel(x, y)).collect())
+    }
+
+    /// XOR one row of a sprite onto a plane. `bits` is the sprite row left
+    /// aligned in 16 bits and `width` how many of them are used. Pixels past
+    /// the right edge wrap around, or are dropped when `clip` is set.
+    /// Returns true if a lit pixel was turned off.
+    pub fn draw_sprite_row(&mut self, plane: usize, x: usize, y: usize, bits: u16, width: usize, clip: bool) -> bool {
+        let screen_width = self.width();
+        let visible = !0u128 << (HIRES_WIDTH - screen_width);
+        let sprite = ((bits as u128) << (HIRES_WIDTH - 16)) & (!0u128 << (HIRES_WIDTH - width));
+        let mut row = (sprite >> x) & visible;
+        if !clip && x > 0 {
+            // Shifting left by the distance to the edge brings the pixels
+            // that fell off it back to the start of the row
+            row |= (sprite << (screen_width - x)) & visible;
+        }
+        let target = &mut self.planes[plane][y];
+        let collision = *target & row != 0;
+        *target ^= row;
+        collision
+    }
+
+    /// Scroll planes in mask down by n pixels, new rows are blank
+    pub fn scroll_down(&mut self, n: usize, mask: u8) {
+        let height = self.height();
+        for plane in planes_in(mask) {
+            let rows = &mut self.planes[plane];
+            for y in (0..height).rev() {
+                rows[y] = if y >= n { rows[y - n] } else { 0 };
+            }
+        }
+    }
+
+    /// Scroll planes in mask right by n pixels, new columns are blank
+    pub fn scroll_right(&mut self, n: usize, mask: u8) {
+        let visible = !0u128 << (HIRES_WIDTH - self.width());
+        for plane in planes_in(mask) {
+            for row in self.planes[plane].iter_mut() {
+                *row = (*row >> n) & visible;
+            }
+        }
+    }
+
+    /// Scroll planes in mask left by n pixels, new columns are blank
+    pub fn scroll_left(&mut self, n: usize, mask: u8) {
+        for plane in planes_in(mask) {
+            for row in self.planes[plane].iter_mut() {
+                *row <<= n;
+            }
+        }
+    }
+
+    /// Visible area as packed RGB bytes in PALETTE colors, row by row
+    pub fn rgb24(&self) -> Vec<u8> {
+        let mut pixels = Vec::with_capacity(self.width() * self.height() * 3);
+        for row in self.rows() {
+            for pixel in row {
+                let (r, g, b) = PALETTE[pixel as usize & 3];
+                pixels.extend_from_slice(&[r, g, b]);
+            }
+        }
+        pixels
+    }
+}
+
+/// Plane numbers selected by a plane bitmask
+fn planes_in(mask: u8) -> impl Iterator<Item = usize> {
+    (0..PLANES).filter(move |plane| mask & (1 << plane) != 0)
+}
+
+#[cfg(test)]
+#[path = "./framebuffer_tests.rs"]
+mod framebuffer_tests;
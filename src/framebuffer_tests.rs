@@ -0,0 +1,92 @@
+use super::*;
+
+#[test]
+fn test_set_pixel_and_rows() {
+    let mut vram = Framebuffer::new();
+    vram.set_pixel(0, 0, 1);
+    vram.set_pixel(63, 31, 3);
+    vram.set_pixel(5, 2, 2);
+    assert_eq!(vram.pixel(0, 0), 1);
+    assert_eq!(vram.pixel(63, 31), 3);
+    assert_eq!(vram.pixel(5, 2), 2);
+    assert_eq!(vram.plane_row(0, 0), 1 << 127);
+    assert_eq!(vram.plane_row(1, 2), 1 << (127 - 5));
+
+    let rows: Vec<Vec<u8>> = vram.rows().collect();
+    assert_eq!(rows.len(), LORES_HEIGHT);
+    assert!(rows.iter().all(|row| row.len() == LORES_WIDTH));
+    assert_eq!(rows[31][63], 3);
+
+    vram.set_pixel(63, 31, 0);
+    assert_eq!(vram.pixel(63, 31), 0);
+}
+
+#[test]
+fn test_draw_sprite_row_collision() {
+    let mut vram = Framebuffer::new();
+    assert!(!vram.draw_sprite_row(0, 10, 4, 0xf000, 8, false));
+    assert_eq!((10..14).map(|x| vram.pixel(x, 4)).collect::<Vec<_>>(), vec![1; 4]);
+    // Overlapping only on the unused low byte of `bits` is no collision
+    assert!(!vram.draw_sprite_row(0, 14, 4, 0x00ff, 8, false));
+    assert!(vram.draw_sprite_row(0, 12, 4, 0x8000, 8, false));
+    assert_eq!(vram.pixel(12, 4), 0);
+    // Planes are independent
+    assert!(!vram.draw_sprite_row(1, 10, 4, 0xf000, 8, false));
+    assert_eq!(vram.pixel(10, 4), 3);
+}
+
+#[test]
+fn test_draw_sprite_row_wraps_and_clips() {
+    for &hires in &[false, true] {
+        let mut vram = Framebuffer::new();
+        vram.set_hires(hires);
+        let width = vram.width();
+
+        vram.draw_sprite_row(0, width - 4, 0, 0xff00, 8, false);
+        assert_eq!(vram.pixel(width - 1, 0), 1);
+        assert_eq!(vram.pixel(0, 0), 1);
+        assert_eq!(vram.pixel(3, 0), 1);
+        assert_eq!(vram.pixel(4, 0), 0);
+
+        vram.draw_sprite_row(0, width - 4, 1, 0xff00, 8, true);
+        assert_eq!(vram.pixel(width - 1, 1), 1);
+        assert_eq!(vram.pixel(0, 1), 0);
+
+        vram.draw_sprite_row(0, width - 8, 2, 0xffff, 16, false);
+        assert_eq!((0..8).map(|x| vram.pixel(x, 2)).collect::<Vec<_>>(), vec![1; 8]);
+
+        // Nothing is ever stored past the visible width
+        for y in 0..3 {
+            assert_eq!(vram.plane_row(0, y) & !(!0u128 << (HIRES_WIDTH - width)), 0);
+        }
+    }
+}
+
+#[test]
+fn test_scroll() {
+    let mut vram = Framebuffer::new();
+    vram.set_pixel(0, 0, 3);
+    vram.set_pixel(63, 5, 1);
+
+    vram.scroll_right(4, 1);
+    assert_eq!(vram.pixel(4, 0), 1);
+    assert_eq!(vram.pixel(0, 0), 2);
+    // Pushed past the right edge and gone
+    vram.scroll_left(4, 1);
+    assert_eq!(vram.pixel(0, 0), 3);
+    assert_eq!(vram.pixel(59, 5), 0);
+    assert_eq!(vram.pixel(63, 5), 0);
+
+    vram.scroll_down(2, 3);
+    assert_eq!(vram.pixel(0, 0), 0);
+    assert_eq!(vram.pixel(0, 2), 3);
+}
+
+#[test]
+fn test_rgb24() {
+    let mut vram = Framebuffer::new();
+    vram.set_pixel(1, 0, 2);
+    let pixels = vram.rgb24();
+    assert_eq!(pixels.len(), LORES_WIDTH * LORES_HEIGHT * 3);
+    assert_eq!(&pixels[..6], &[0, 0, 0, 255, 102, 0]);
+}
@@ -0,0 +1,183 @@
+use std::collections::VecDeque;
+
+use crate::cpu::{AudioPattern, CycleState};
+use crate::framebuffer::Framebuffer;
+
+/// Emulator controls that aren't part of the CHIP-8 keypad
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Hotkey {
+    Faster,
+    Slower,
+    SaveState,
+    LoadState,
+    PreviousSlot,
+    NextSlot,
+}
+
+/// Where frames are shown: a window, a terminal, nowhere at all
+pub trait VideoSink {
+    fn draw(&mut self, vram: &Framebuffer);
+
+    /// Status line, e.g. the window title. Ignored by default.
+    fn set_title(&mut self, _title: &str) {}
+}
+
+/// Where the buzzer plays
+pub trait AudioSink {
+    fn start_audio(&mut self);
+
+    fn stop_audio(&mut self);
+
+    /// Play the XO-CHIP audio pattern instead of the square wave, None
+    /// switches back to the square wave.
+    fn set_pattern(&mut self, pattern: Option<AudioPattern>);
+}
+
+/// Where the keypad state comes from
+pub trait InputSource {
+    /// Keys held for the next frame, None once the user wants to quit
+    fn poll(&mut self) -> Option<[bool; 16]>;
+
+    /// Hotkeys pressed since the last call
+    fn take_hotkeys(&mut self) -> Vec<Hotkey> {
+        Vec::new()
+    }
+
+    /// Rewind key is being held
+    fn is_rewinding(&self) -> bool {
+        false
+    }
+}
+
+/// Show the result of a frame: draw if the screen changed (or `redraw` is
+/// set) and play the buzzer while the sound timer runs, unless `muted`.
+pub fn present<V: VideoSink, A: AudioSink>(video: &mut V, audio: &mut A, state: &CycleState, redraw: bool, muted: bool) {
+    if state.vram_changed || redraw {
+        video.draw(state.vram);
+    }
+    audio.set_pattern(state.audio_pattern);
+    if state.sound && !muted {
+        audio.start_audio()
+    } else {
+        audio.stop_audio()
+    }
+}
+
+/// Discards every frame
+#[derive(Clone, Copy, Debug, Default)]
+pub struct NullVideo;
+
+impl VideoSink for NullVideo {
+    fn draw(&mut self, _vram: &Framebuffer) {}
+}
+
+/// Never makes a sound
+#[derive(Clone, Copy, Debug, Default)]
+pub struct NullAudio;
+
+impl AudioSink for NullAudio {
+    fn start_audio(&mut self) {}
+
+    fn stop_audio(&mut self) {}
+
+    fn set_pattern(&mut self, _pattern: Option<AudioPattern>) {}
+}
+
+/// No keys held, never quits
+#[derive(Clone, Copy, Debug, Default)]
+pub struct NullInput;
+
+impl InputSource for NullInput {
+    fn poll(&mut self) -> Option<[bool; 16]> {
+        Some([false; 16])
+    }
+}
+
+/// Keeps the last frame drawn, for tests
+#[derive(Clone, Default)]
+pub struct MemoryVideo {
+    pub screen: Framebuffer,
+    pub frames_drawn: usize,
+    pub title: String,
+}
+
+impl VideoSink for MemoryVideo {
+    fn draw(&mut self, vram: &Framebuffer) {
+        self.screen = vram.clone();
+        self.frames_drawn += 1;
+    }
+
+    fn set_title(&mut self, title: &str) {
+        self.title = title.to_string();
+    }
+}
+
+/// Remembers what the buzzer was told to do, for tests
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct MemoryAudio {
+    pub playing: bool,
+    pub pattern: Option<AudioPattern>,
+    /// Frames the buzzer was on for
+    pub frames_played: usize,
+}
+
+impl AudioSink for MemoryAudio {
+    fn start_audio(&mut self) {
+        self.playing = true;
+        self.frames_played += 1;
+    }
+
+    fn stop_audio(&mut self) {
+        self.playing = false;
+    }
+
+    fn set_pattern(&mut self, pattern: Option<AudioPattern>) {
+        self.pattern = pattern;
+    }
+}
+
+/// Plays back a list of keypad states, one per frame, then quits
+#[derive(Clone, Debug, Default)]
+pub struct MemoryInput {
+    frames: VecDeque<[bool; 16]>,
+    hotkeys: VecDeque<Vec<Hotkey>>,
+    hotkeys_due: Vec<Hotkey>,
+    pub rewinding: bool,
+}
+
+impl MemoryInput {
+    pub fn new(frames: Vec<[bool; 16]>) -> Self {
+        MemoryInput {
+            frames: frames.into(),
+            ..Default::default()
+        }
+    }
+
+    /// Press `hotkey` together with the keys of frame `frame`
+    pub fn press_hotkey(&mut self, frame: usize, hotkey: Hotkey) {
+        if self.hotkeys.len() <= frame {
+            self.hotkeys.resize(frame + 1, Vec::new());
+        }
+        self.hotkeys[frame].push(hotkey);
+    }
+}
+
+impl InputSource for MemoryInput {
+    fn poll(&mut self) -> Option<[bool; 16]> {
+        let keys = self.frames.pop_front()?;
+        self.hotkeys_due.extend(self.hotkeys.pop_front().unwrap_or_default());
+        Some(keys)
+    }
+
+    fn take_hotkeys(&mut self) -> Vec<Hotkey> {
+        std::mem::take(&mut self.hotkeys_due)
+    }
+
+    fn is_rewinding(&self) -> bool {
+        self.rewinding
+    }
+}
+
+#[cfg(test)]
+#[path = "./frontend_tests.rs"]
+mod frontend_tests;
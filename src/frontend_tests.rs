@@ -0,0 +1,47 @@
+use super::*;
+use crate::cpu::Cpu;
+use crate::quirks::Quirks;
+
+#[test]
+fn test_present() {
+    let mut cpu = Cpu::new(Quirks::default());
+    // CLS, LD V0 4, LD ST V0, DRW V0 V0 1
+    cpu.read_data_to_memory(&[0x00, 0xE0, 0x60, 0x04, 0xF0, 0x18, 0xD0, 0x01]);
+    let mut video = MemoryVideo::default();
+    let mut audio = MemoryAudio::default();
+
+    let state = cpu.run_frame([false; 16], 3).unwrap();
+    present(&mut video, &mut audio, &state, false, false);
+    assert_eq!(video.frames_drawn, 1);
+    assert!(audio.playing);
+
+    // Nothing new to draw, muted while e.g. paused in the debugger
+    let state = cpu.run_frame([false; 16], 0).unwrap();
+    present(&mut video, &mut audio, &state, false, true);
+    assert_eq!(video.frames_drawn, 1);
+    assert!(!audio.playing);
+
+    let state = cpu.run_frame([false; 16], 1).unwrap();
+    present(&mut video, &mut audio, &state, false, false);
+    assert_eq!(video.frames_drawn, 2);
+    assert_eq!(video.screen.pixel(4, 4), 1);
+    present(&mut video, &mut audio, &cpu.state(), true, false);
+    assert_eq!(video.frames_drawn, 3);
+}
+
+#[test]
+fn test_memory_input() {
+    let mut held = [false; 16];
+    held[3] = true;
+    let mut input = MemoryInput::new(vec![[false; 16], held, [false; 16]]);
+    input.press_hotkey(1, Hotkey::SaveState);
+    input.press_hotkey(1, Hotkey::NextSlot);
+
+    assert_eq!(input.poll(), Some([false; 16]));
+    assert!(input.take_hotkeys().is_empty());
+    assert_eq!(input.poll(), Some(held));
+    assert_eq!(input.take_hotkeys(), vec![Hotkey::SaveState, Hotkey::NextSlot]);
+    assert!(input.take_hotkeys().is_empty());
+    assert_eq!(input.poll(), Some([false; 16]));
+    assert_eq!(input.poll(), None);
+}
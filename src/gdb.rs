@@ -0,0 +1,473 @@
+use crate::cpu::Cpu;
+use crate::debugger::{Command, Debugger, WatchKind, Watchpoint};
+use crate::error::CpuError;
+use std::io::{self, ErrorKind, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+
+/// Register numbers in `g`/`p` packets. V0-VF are 0-15, the rest follow.
+/// Values are sent big endian, the CHIP-8 byte order.
+pub const REGISTER_I: usize = 16;
+pub const REGISTER_PC: usize = 17;
+pub const REGISTER_SP: usize = 18;
+pub const REGISTER_DT: usize = 19;
+pub const REGISTER_ST: usize = 20;
+pub const REGISTER_COUNT: usize = 21;
+
+/// Target description so front ends know the register names and sizes
+const TARGET_XML: &str = r#"<?xml version="1.0"?>
+<!DOCTYPE target SYSTEM "gdb-target.dtd">
+<target version="1.0">
+  <feature name="org.rchip8.core">
+    <reg name="v0" bitsize="8" regnum="0"/>
+    <reg name="v1" bitsize="8"/>
+    <reg name="v2" bitsize="8"/>
+    <reg name="v3" bitsize="8"/>
+    <reg name="v4" bitsize="8"/>
+    <reg name="v5" bitsize="8"/>
+    <reg name="v6" bitsize="8"/>
+    <reg name="v7" bitsize="8"/>
+    <reg name="v8" bitsize="8"/>
+    <reg name="v9" bitsize="8"/>
+    <reg name="va" bitsize="8"/>
+    <reg name="vb" bitsize="8"/>
+    <reg name="vc" bitsize="8"/>
+    <reg name="vd" bitsize="8"/>
+    <reg name="ve" bitsize="8"/>
+    <reg name="vf" bitsize="8"/>
+    <reg name="i" bitsize="16" type="data_ptr"/>
+    <reg name="pc" bitsize="16" type="code_ptr"/>
+    <reg name="sp" bitsize="8"/>
+    <reg name="dt" bitsize="8"/>
+    <reg name="st" bitsize="8"/>
+  </feature>
+</target>
+"#;
+
+/// Size in bytes of a register in packets
+fn register_size(register: usize) -> usize {
+    if register == REGISTER_I || register == REGISTER_PC { 2 } else { 1 }
+}
+
+/// GDB remote serial protocol server. Runs alongside the frontend main loop:
+/// `run_frame` handles whatever the client sent and runs the cpu for a frame
+/// unless the client has it stopped. The cpu is stopped until a client
+/// attaches and continues.
+pub struct GdbStub {
+    listener: TcpListener,
+    client: Option<TcpStream>,
+    session: Session,
+    input: Vec<u8>,
+}
+
+impl GdbStub {
+    /// Listen on localhost only, port 0 picks a free port
+    pub fn bind(port: u16, instructions_per_frame: usize) -> io::Result<Self> {
+        let listener = TcpListener::bind(("127.0.0.1", port))?;
+        listener.set_nonblocking(true)?;
+        Ok(GdbStub {
+            listener,
+            client: None,
+            session: Session::new(instructions_per_frame),
+            input: Vec::new(),
+        })
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.client.is_some()
+    }
+
+    /// The cpu is stopped waiting for the client
+    pub fn is_stopped(&self) -> bool {
+        self.session.debugger.is_paused()
+    }
+
+    pub fn set_instructions_per_frame(&mut self, instructions_per_frame: usize) {
+        self.session.instructions_per_frame = instructions_per_frame;
+        self.session.debugger.set_instructions_per_frame(instructions_per_frame);
+    }
+
+    pub fn run_frame(&mut self, cpu: &mut Cpu, keys: [bool; 16]) -> io::Result<()> {
+        if self.client.is_none() {
+            match self.listener.accept() {
+                Ok((stream, _)) => {
+                    stream.set_nonblocking(true)?;
+                    stream.set_nodelay(true)?;
+                    self.client = Some(stream);
+                    self.session = Session::new(self.session.instructions_per_frame);
+                    self.input.clear();
+                }
+                Err(error) if error.kind() == ErrorKind::WouldBlock => {
+                    // Before the first client the rom waits at its first
+                    // instruction, after one detaches it runs on its own
+                    if !self.is_stopped() {
+                        self.session.run_frame(cpu, keys);
+                    }
+                    return Ok(());
+                }
+                Err(error) => return Err(error),
+            }
+        }
+
+        if !self.receive()? {
+            // Client went away, let the program run on its own
+            self.client = None;
+            self.session.debugger.execute(Command::DeleteAll, cpu, keys);
+            self.session.debugger.execute(Command::Continue, cpu, keys);
+        }
+        let mut replies = Vec::new();
+        for packet in self.session.take_packets(&mut self.input) {
+            match packet {
+                Some(packet) => replies.extend(self.session.handle(&packet, cpu, keys)),
+                None => replies.push(Reply::Nack),
+            }
+        }
+        if let Some(reply) = self.session.run_frame(cpu, keys) {
+            replies.push(reply);
+        }
+        for reply in replies {
+            self.send(&reply)?;
+        }
+        if self.session.detached {
+            self.client = None;
+        }
+        Ok(())
+    }
+
+    /// Read what the client sent, false if it disconnected
+    fn receive(&mut self) -> io::Result<bool> {
+        let client = match self.client.as_mut() {
+            Some(client) => client,
+            None => return Ok(true),
+        };
+        let mut buffer = [0; 4096];
+        loop {
+            match client.read(&mut buffer) {
+                Ok(0) => return Ok(false),
+                Ok(count) => self.input.extend_from_slice(&buffer[..count]),
+                Err(error) if error.kind() == ErrorKind::WouldBlock => return Ok(true),
+                Err(error) if error.kind() == ErrorKind::ConnectionReset => return Ok(false),
+                Err(error) => return Err(error),
+            }
+        }
+    }
+
+    fn send(&mut self, reply: &Reply) -> io::Result<()> {
+        if let Some(client) = self.client.as_mut() {
+            let bytes = match reply {
+                Reply::Ack => b"+".to_vec(),
+                Reply::Nack => b"-".to_vec(),
+                Reply::Packet(data) => frame(data).into_bytes(),
+            };
+            // Replies are small, wait for the socket instead of buffering
+            client.set_nonblocking(false)?;
+            let result = client.write_all(&bytes);
+            client.set_nonblocking(true)?;
+            result?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Reply {
+    Ack,
+    Nack,
+    Packet(String),
+}
+
+/// `$data#checksum`
+fn frame(data: &str) -> String {
+    let checksum = data.bytes().fold(0u8, |sum, byte| sum.wrapping_add(byte));
+    format!("${}#{:02x}", data, checksum)
+}
+
+/// Protocol state of one client connection, separate from the socket
+struct Session {
+    debugger: Debugger,
+    instructions_per_frame: usize,
+    no_ack: bool,
+    /// A continue or step waiting for the cpu to stop
+    running: bool,
+    detached: bool,
+}
+
+impl Session {
+    fn new(instructions_per_frame: usize) -> Self {
+        Session {
+            debugger: Debugger::new(instructions_per_frame),
+            instructions_per_frame,
+            no_ack: false,
+            running: false,
+            detached: false,
+        }
+    }
+
+    /// Complete packets in input, removed from it. Ctrl-C arrives as a
+    /// 0x03 byte outside of a packet and is returned as `\x03`. Packets with
+    /// a bad checksum come back as None and are nacked.
+    fn take_packets(&mut self, input: &mut Vec<u8>) -> Vec<Option<String>> {
+        let mut packets = Vec::new();
+        let mut consumed = 0;
+        while consumed < input.len() {
+            match input[consumed] {
+                b'$' => {
+                    let rest = &input[consumed..];
+                    let end = match rest.iter().position(|&byte| byte == b'#') {
+                        Some(end) if end + 3 <= rest.len() => end,
+                        _ => break,
+                    };
+                    let data = &rest[1..end];
+                    let checksum = std::str::from_utf8(&rest[end + 1..end + 3])
+                        .ok()
+                        .and_then(|text| u8::from_str_radix(text, 16).ok());
+                    let valid = checksum == Some(data.iter().fold(0u8, |sum, &byte| sum.wrapping_add(byte)));
+                    packets.push(if valid || self.no_ack { Some(String::from_utf8_lossy(data).into_owned()) } else { None });
+                    consumed += end + 3;
+                }
+                0x03 => {
+                    packets.push(Some("\x03".to_string()));
+                    consumed += 1;
+                }
+                // Acks from the client and line noise
+                _ => consumed += 1,
+            }
+        }
+        input.drain(..consumed);
+        packets
+    }
+
+    /// Replies for one packet, None means the checksum was wrong
+    fn handle(&mut self, packet: &str, cpu: &mut Cpu, keys: [bool; 16]) -> Vec<Reply> {
+        let mut replies = Vec::new();
+        if packet == "\x03" {
+            if self.running {
+                self.debugger.pause();
+                self.running = false;
+                replies.push(Reply::Packet("S02".to_string()));
+            }
+            return replies;
+        }
+        if !self.no_ack {
+            replies.push(Reply::Ack);
+        }
+        if let Some(reply) = self.command(packet, cpu, keys) {
+            replies.push(Reply::Packet(reply));
+        }
+        replies
+    }
+
+    /// Reply to a command, None while the cpu runs after a continue
+    fn command(&mut self, packet: &str, cpu: &mut Cpu, keys: [bool; 16]) -> Option<String> {
+        let (command, arguments) = packet.split_at(packet.chars().next().map_or(0, |c| c.len_utf8()));
+        let reply = match command {
+            "?" => "S05".to_string(),
+            "g" => (0..REGISTER_COUNT).map(|register| read_register(cpu, register)).collect(),
+            "G" => {
+                let mut offset = 0;
+                for register in 0..REGISTER_COUNT {
+                    let size = register_size(register) * 2;
+                    match arguments.get(offset..offset + size).and_then(parse_hex) {
+                        Some(value) => write_register(cpu, register, value),
+                        None => return Some("E01".to_string()),
+                    }
+                    offset += size;
+                }
+                "OK".to_string()
+            }
+            "p" => match parse_hex(arguments) {
+                Some(register) if (register as usize) < REGISTER_COUNT => read_register(cpu, register as usize),
+                _ => "E01".to_string(),
+            },
+            "P" => match arguments.split_once('=').map(|(register, value)| (parse_hex(register), parse_hex(value))) {
+                Some((Some(register), Some(value))) if (register as usize) < REGISTER_COUNT => {
+                    write_register(cpu, register as usize, value);
+                    "OK".to_string()
+                }
+                _ => "E01".to_string(),
+            },
+            "m" => match address_length(arguments) {
+                Some((address, length)) => {
+                    let memory = cpu.memory();
+                    let end = address.saturating_add(length).min(memory.len());
+                    if address >= end && length > 0 {
+                        "E01".to_string()
+                    } else {
+                        memory[address.min(end)..end].iter().map(|byte| format!("{:02x}", byte)).collect()
+                    }
+                }
+                None => "E01".to_string(),
+            },
+            "M" => {
+                let written = arguments.split_once(':').and_then(|(range, data)| {
+                    let (address, length) = address_length(range)?;
+                    let bytes = parse_bytes(data)?;
+                    if bytes.len() != length {
+                        return None;
+                    }
+                    Some(bytes.iter().enumerate().all(|(offset, &byte)| cpu.poke(address + offset, byte)))
+                });
+                if written == Some(true) { "OK".to_string() } else { "E01".to_string() }
+            }
+            "s" => {
+                if let Some(address) = parse_hex(arguments) {
+                    cpu.set_pc(address as usize);
+                }
+                let result = self.debugger.step(cpu, keys);
+                stop_reply(cpu, result)
+            }
+            "c" => {
+                if let Some(address) = parse_hex(arguments) {
+                    cpu.set_pc(address as usize);
+                }
+                self.debugger.execute(Command::Continue, cpu, keys);
+                self.running = true;
+                return None;
+            }
+            "Z" | "z" => return Some(self.breakpoint(command == "Z", arguments, cpu, keys)),
+            "q" | "Q" => self.query(packet),
+            "H" => "OK".to_string(),
+            "T" => "OK".to_string(),
+            // Detach and kill both leave the program running without breakpoints
+            "D" | "k" => {
+                self.detached = true;
+                self.debugger.execute(Command::DeleteAll, cpu, keys);
+                self.debugger.execute(Command::Continue, cpu, keys);
+                if command == "k" {
+                    return None;
+                }
+                "OK".to_string()
+            }
+            // Unsupported packets get an empty reply
+            _ => String::new(),
+        };
+        Some(reply)
+    }
+
+    /// Z0/Z1 breakpoints, Z2/Z3/Z4 write, read and access watchpoints
+    fn breakpoint(&mut self, insert: bool, arguments: &str, cpu: &mut Cpu, keys: [bool; 16]) -> String {
+        let fields: Vec<&str> = arguments.split(',').collect();
+        let (kind, address, length) = match fields.as_slice() {
+            [kind, address, length, ..] => match (parse_hex(address), parse_hex(length)) {
+                (Some(address), Some(length)) => (*kind, address as usize, length as usize),
+                _ => return "E01".to_string(),
+            },
+            _ => return "E01".to_string(),
+        };
+        let watch = |kind| Command::Watch(Watchpoint { address, length: length.max(1), kind });
+        let command = match (kind, insert) {
+            ("0", true) | ("1", true) => Command::Break(address),
+            ("2", true) => watch(WatchKind::Write),
+            ("3", true) => watch(WatchKind::Read),
+            ("4", true) => watch(WatchKind::Access),
+            ("0" | "1" | "2" | "3" | "4", false) => Command::Delete(address),
+            _ => return String::new(),
+        };
+        self.debugger.execute(command, cpu, keys);
+        "OK".to_string()
+    }
+
+    fn query(&mut self, packet: &str) -> String {
+        if packet.starts_with("qSupported") {
+            return "PacketSize=4000;qXfer:features:read+;QStartNoAckMode+".to_string();
+        }
+        if let Some(range) = packet.strip_prefix("qXfer:features:read:target.xml:") {
+            let (offset, length) = match address_length(range) {
+                Some(range) => range,
+                None => return "E01".to_string(),
+            };
+            let start = offset.min(TARGET_XML.len());
+            let end = offset.saturating_add(length).min(TARGET_XML.len());
+            let marker = if end == TARGET_XML.len() { 'l' } else { 'm' };
+            return format!("{}{}", marker, &TARGET_XML[start..end]);
+        }
+        match packet {
+            "QStartNoAckMode" => {
+                self.no_ack = true;
+                "OK".to_string()
+            }
+            "qAttached" => "1".to_string(),
+            "qC" => "QC1".to_string(),
+            "qfThreadInfo" => "m1".to_string(),
+            "qsThreadInfo" => "l".to_string(),
+            _ => String::new(),
+        }
+    }
+
+    /// Run a frame while continuing, the stop reply once the cpu stops
+    fn run_frame(&mut self, cpu: &mut Cpu, keys: [bool; 16]) -> Option<Reply> {
+        if !self.running {
+            // Without a client the debugger may still be running
+            if !self.debugger.is_paused() {
+                self.debugger.run_frame(cpu, keys).ok();
+            }
+            return None;
+        }
+        let result = self.debugger.run_frame(cpu, keys);
+        if !self.debugger.is_paused() {
+            return None;
+        }
+        self.running = false;
+        Some(Reply::Packet(stop_reply(cpu, result.map(|_| ()))))
+    }
+}
+
+/// S05 (SIGTRAP) after a step or breakpoint, W00 once 00FD exited,
+/// S04 (SIGILL) for unknown opcodes and S0B (SIGSEGV) for other faults
+fn stop_reply(cpu: &Cpu, result: Result<(), CpuError>) -> String {
+    match result {
+        Err(CpuError::UnknownOpcode { .. }) => "S04".to_string(),
+        Err(_) => "S0B".to_string(),
+        Ok(()) if cpu.state().halted => "W00".to_string(),
+        Ok(()) => "S05".to_string(),
+    }
+}
+
+fn read_register(cpu: &Cpu, register: usize) -> String {
+    match register {
+        0..=15 => format!("{:02x}", cpu.registers()[register]),
+        REGISTER_I => format!("{:04x}", cpu.i() as u16),
+        REGISTER_PC => format!("{:04x}", cpu.pc() as u16),
+        REGISTER_SP => format!("{:02x}", cpu.sp()),
+        REGISTER_DT => format!("{:02x}", cpu.delay_timer),
+        _ => format!("{:02x}", cpu.sound_timer),
+    }
+}
+
+fn write_register(cpu: &mut Cpu, register: usize, value: u64) {
+    match register {
+        0..=15 => cpu.set_register(register, value as u8),
+        REGISTER_I => cpu.set_i(value as u16 as usize),
+        REGISTER_PC => cpu.set_pc(value as u16 as usize),
+        REGISTER_SP => cpu.set_sp(value as usize),
+        REGISTER_DT => cpu.delay_timer = value as u8,
+        _ => cpu.sound_timer = value as u8,
+    }
+}
+
+fn parse_hex(text: &str) -> Option<u64> {
+    if text.is_empty() {
+        return None;
+    }
+    u64::from_str_radix(text, 16).ok()
+}
+
+fn parse_bytes(text: &str) -> Option<Vec<u8>> {
+    if !text.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..text.len()).step_by(2).map(|offset| u8::from_str_radix(text.get(offset..offset + 2)?, 16).ok()).collect()
+}
+
+/// `addr,length` of m, M and qXfer packets
+fn address_length(text: &str) -> Option<(usize, usize)> {
+    let (address, length) = text.split_once(',')?;
+    Some((parse_hex(address)? as usize, parse_hex(length)? as usize))
+}
+
+#[cfg(test)]
+#[path = "./gdb_tests.rs"]
+mod gdb_tests;
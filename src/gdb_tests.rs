@@ -0,0 +1,171 @@
+use super::*;
+use crate::test_utils::cpu_with_program;
+use std::time::Duration;
+
+const KEYS: [bool; 16] = [false; 16];
+
+const PROGRAM: &str = "
+    LD V0, 1
+    loop:
+        ADD V0, 1
+        LD I, 0x300
+        LD [I], V0
+        JP loop
+";
+
+/// Reply packet bodies for a command, acks left out
+fn command(session: &mut Session, packet: &str, cpu: &mut Cpu) -> Option<String> {
+    session.handle(packet, cpu, KEYS).into_iter().find_map(|reply| match reply {
+        Reply::Packet(data) => Some(data),
+        _ => None,
+    })
+}
+
+#[test]
+fn test_frame() {
+    assert_eq!(frame("OK"), "$OK#9a");
+    assert_eq!(frame(""), "$#00");
+}
+
+#[test]
+fn test_take_packets() {
+    let mut session = Session::new(10);
+    let mut input = b"+$g#67$m200,2#xx\x03$c#6".to_vec();
+    let packets = session.take_packets(&mut input);
+    assert_eq!(packets, vec![Some("g".to_string()), None, Some("\x03".to_string())]);
+    // The incomplete packet stays for the next read
+    assert_eq!(input, b"$c#6".to_vec());
+}
+
+#[test]
+fn test_registers() {
+    let mut cpu = cpu_with_program(PROGRAM);
+    let mut session = Session::new(10);
+    assert_eq!(session.handle("g", &mut cpu, KEYS)[0], Reply::Ack);
+
+    let registers = command(&mut session, "g", &mut cpu).unwrap();
+    assert_eq!(registers, format!("{}00000200000000", "00".repeat(16)));
+
+    assert_eq!(command(&mut session, "P3=2a", &mut cpu).unwrap(), "OK");
+    assert_eq!(command(&mut session, "P11=0204", &mut cpu).unwrap(), "OK");
+    assert_eq!(cpu.registers()[3], 0x2a);
+    assert_eq!(cpu.pc(), 0x204);
+    assert_eq!(command(&mut session, "p3", &mut cpu).unwrap(), "2a");
+    assert_eq!(command(&mut session, "p10", &mut cpu).unwrap(), "0000");
+    assert_eq!(command(&mut session, "p20", &mut cpu).unwrap(), "E01");
+
+    let mut all = registers.clone();
+    all.replace_range(32..36, "0123");
+    assert_eq!(command(&mut session, &format!("G{}", all), &mut cpu).unwrap(), "OK");
+    assert_eq!(cpu.i(), 0x123);
+}
+
+#[test]
+fn test_memory() {
+    let mut cpu = cpu_with_program(PROGRAM);
+    let mut session = Session::new(10);
+    assert_eq!(command(&mut session, "m200,4", &mut cpu).unwrap(), "60017001");
+    assert_eq!(command(&mut session, "M300,2:beef", &mut cpu).unwrap(), "OK");
+    assert_eq!(&cpu.memory()[0x300..0x302], &[0xbe, 0xef]);
+    assert_eq!(command(&mut session, "M300,2:be", &mut cpu).unwrap(), "E01");
+    assert_eq!(command(&mut session, "m10000,2", &mut cpu).unwrap(), "E01");
+}
+
+#[test]
+fn test_step_and_breakpoint() {
+    let mut cpu = cpu_with_program(PROGRAM);
+    let mut session = Session::new(10);
+    assert_eq!(command(&mut session, "s", &mut cpu).unwrap(), "S05");
+    assert_eq!(cpu.pc(), 0x202);
+
+    assert_eq!(command(&mut session, "Z0,206,2", &mut cpu).unwrap(), "OK");
+    assert_eq!(command(&mut session, "c", &mut cpu), None);
+    assert_eq!(session.run_frame(&mut cpu, KEYS), Some(Reply::Packet("S05".to_string())));
+    assert_eq!(cpu.pc(), 0x206);
+
+    // Continuing from the breakpoint runs a whole loop before stopping again
+    command(&mut session, "c", &mut cpu);
+    assert_eq!(session.run_frame(&mut cpu, KEYS), Some(Reply::Packet("S05".to_string())));
+    assert_eq!(cpu.pc(), 0x206);
+    assert_eq!(cpu.registers()[0], 3);
+
+    assert_eq!(command(&mut session, "z0,206,2", &mut cpu).unwrap(), "OK");
+    command(&mut session, "c", &mut cpu);
+    assert_eq!(session.run_frame(&mut cpu, KEYS), None);
+    assert_eq!(session.handle("\x03", &mut cpu, KEYS), vec![Reply::Packet("S02".to_string())]);
+}
+
+#[test]
+fn test_watchpoint_and_exit() {
+    let mut cpu = cpu_with_program(PROGRAM);
+    let mut session = Session::new(10);
+    assert_eq!(command(&mut session, "Z2,300,1", &mut cpu).unwrap(), "OK");
+    command(&mut session, "c", &mut cpu);
+    assert_eq!(session.run_frame(&mut cpu, KEYS), Some(Reply::Packet("S05".to_string())));
+    assert_eq!(cpu.memory()[0x300], 2);
+
+    let mut cpu = cpu_with_program("EXIT");
+    let mut session = Session::new(10);
+    assert_eq!(command(&mut session, "s", &mut cpu).unwrap(), "W00");
+}
+
+#[test]
+fn test_queries() {
+    let mut cpu = cpu_with_program(PROGRAM);
+    let mut session = Session::new(10);
+    assert!(command(&mut session, "qSupported:multiprocess+", &mut cpu).unwrap().contains("qXfer:features:read+"));
+    let xml = command(&mut session, "qXfer:features:read:target.xml:0,20", &mut cpu).unwrap();
+    assert_eq!(xml, format!("m{}", &TARGET_XML[..0x20]));
+    assert_eq!(command(&mut session, "vMustReplyEmpty", &mut cpu).unwrap(), "");
+
+    assert_eq!(command(&mut session, "QStartNoAckMode", &mut cpu).unwrap(), "OK");
+    assert_eq!(session.handle("?", &mut cpu, KEYS), vec![Reply::Packet("S05".to_string())]);
+}
+
+#[test]
+fn test_tcp_session() {
+    let mut cpu = cpu_with_program(PROGRAM);
+    let mut stub = GdbStub::bind(0, 10).unwrap();
+    let mut client = TcpStream::connect(stub.local_addr().unwrap()).unwrap();
+    client.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+
+    client.write_all(frame("m200,2").as_bytes()).unwrap();
+    let mut reply = Vec::new();
+    let expected = format!("+{}", frame("6001"));
+    while reply.len() < expected.len() {
+        stub.run_frame(&mut cpu, KEYS).unwrap();
+        std::thread::sleep(Duration::from_millis(1));
+        let mut buffer = [0; 64];
+        client.set_nonblocking(true).unwrap();
+        if let Ok(count) = client.read(&mut buffer) {
+            reply.extend_from_slice(&buffer[..count]);
+        }
+    }
+    assert!(stub.is_connected());
+    assert!(stub.is_stopped());
+    assert_eq!(String::from_utf8(reply).unwrap(), expected);
+}
+
+#[test]
+fn test_runs_on_after_detach() {
+    let mut cpu = cpu_with_program(PROGRAM);
+    let mut stub = GdbStub::bind(0, 10).unwrap();
+    stub.run_frame(&mut cpu, KEYS).unwrap();
+    assert_eq!(cpu.pc(), 0x200);
+
+    let mut client = TcpStream::connect(stub.local_addr().unwrap()).unwrap();
+    client.write_all(frame("D").as_bytes()).unwrap();
+    for _ in 0..1000 {
+        stub.run_frame(&mut cpu, KEYS).unwrap();
+        if stub.session.detached {
+            break;
+        }
+        std::thread::sleep(Duration::from_millis(1));
+    }
+    assert!(!stub.is_connected());
+    assert!(!stub.is_stopped());
+
+    let count = cpu.memory()[0x300];
+    stub.run_frame(&mut cpu, KEYS).unwrap();
+    assert_ne!(cpu.memory()[0x300], count);
+}
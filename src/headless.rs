@@ -0,0 +1,199 @@
+use std::error::Error;
+use std::fmt;
+
+use crate::cpu::Cpu;
+use crate::error::CpuError;
+use crate::framebuffer::Framebuffer;
+use crate::frontend::{self, AudioSink, InputSource, VideoSink};
+
+/// When a headless run stops before its frame limit
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StopCondition {
+    /// The rom exited with 00FD
+    Halt,
+    /// The rom is stuck on a jump to itself, the usual way to end a test rom
+    Loop,
+}
+
+impl StopCondition {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "halt" => Some(StopCondition::Halt),
+            "loop" => Some(StopCondition::Loop),
+            _ => None,
+        }
+    }
+
+    fn is_met(self, cpu: &Cpu) -> bool {
+        match self {
+            StopCondition::Halt => cpu.state().halted,
+            StopCondition::Loop => cpu.opcode_at(cpu.pc()) == Some(0x1000 | cpu.pc() as u16),
+        }
+    }
+}
+
+/// Why a headless run ended
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StopReason {
+    FrameLimit,
+    Condition(StopCondition),
+    /// The input source quit
+    Quit,
+}
+
+impl fmt::Display for StopReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StopReason::FrameLimit => write!(f, "frame limit reached"),
+            StopReason::Condition(StopCondition::Halt) => write!(f, "rom halted"),
+            StopReason::Condition(StopCondition::Loop) => write!(f, "rom is looping on a jump to itself"),
+            StopReason::Quit => write!(f, "input ended"),
+        }
+    }
+}
+
+/// Input script line that can't be parsed
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ScriptError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ScriptError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl Error for ScriptError {}
+
+/// Input script
+/// Keys held during a headless run. Each line is `<frame> <keys>`, the keys
+/// are hex digits of the keypad keys held from that frame on, `-` releases
+/// all of them. Lines starting with `#` are comments.
+/// ```text
+/// 0 -
+/// 60 5     # hold 5 for half a second
+/// 90 -
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct InputScript {
+    changes: Vec<(usize, [bool; 16])>,
+}
+
+impl InputScript {
+    pub fn parse(source: &str) -> Result<Self, ScriptError> {
+        let mut changes: Vec<(usize, [bool; 16])> = Vec::new();
+        for (index, line) in source.lines().enumerate() {
+            let error = |message: String| ScriptError { line: index + 1, message };
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let mut fields = line.split_whitespace();
+            let frame = fields.next().unwrap_or_default();
+            let frame: usize = frame.parse().map_err(|_| error(format!("invalid frame number `{}`", frame)))?;
+            let held = fields.next().ok_or_else(|| error("missing keys, use `-` for none".to_string()))?;
+            if let Some(extra) = fields.next() {
+                return Err(error(format!("unexpected `{}`", extra)));
+            }
+            if changes.last().is_some_and(|&(previous, _)| previous >= frame) {
+                return Err(error(format!("frame {} is not after the previous line", frame)));
+            }
+            let mut keys = [false; 16];
+            if held != "-" {
+                for digit in held.chars() {
+                    let key = digit.to_digit(16).ok_or_else(|| error(format!("invalid key `{}`", digit)))?;
+                    keys[key as usize] = true;
+                }
+            }
+            changes.push((frame, keys));
+        }
+        Ok(InputScript { changes })
+    }
+
+    /// Keys held in `frame`
+    pub fn keys(&self, frame: usize) -> [bool; 16] {
+        self.changes.iter()
+            .take_while(|&&(start, _)| start <= frame)
+            .last()
+            .map_or([false; 16], |&(_, keys)| keys)
+    }
+}
+
+/// Feeds an input script to the emulator frame by frame
+#[derive(Clone, Debug, Default)]
+pub struct ScriptedInput {
+    script: InputScript,
+    frame: usize,
+}
+
+impl ScriptedInput {
+    pub fn new(script: InputScript) -> Self {
+        ScriptedInput { script, frame: 0 }
+    }
+}
+
+impl InputSource for ScriptedInput {
+    fn poll(&mut self) -> Option<[bool; 16]> {
+        self.frame += 1;
+        Some(self.script.keys(self.frame - 1))
+    }
+}
+
+/// Headless
+/// Runs a rom as fast as it goes, for tests and batch jobs. Pass
+/// `NullVideo` and `NullAudio` to run without a window or audio device.
+/// Stops after `max_frames` frames, or earlier once one of the stop
+/// conditions is met.
+pub struct Headless {
+    pub max_frames: usize,
+    pub instructions_per_frame: usize,
+    pub stop_conditions: Vec<StopCondition>,
+}
+
+impl Headless {
+    /// Run until the frame limit, a stop condition or the input quits.
+    /// Returns how many frames ran and why it stopped.
+    pub fn run<V: VideoSink, A: AudioSink, I: InputSource>(
+        &self,
+        cpu: &mut Cpu,
+        video: &mut V,
+        audio: &mut A,
+        input: &mut I,
+    ) -> Result<(usize, StopReason), CpuError> {
+        for frame in 0..self.max_frames {
+            let Some(keys) = input.poll() else {
+                return Ok((frame, StopReason::Quit));
+            };
+            let state = cpu.run_frame(keys, self.instructions_per_frame)?;
+            frontend::present(video, audio, &state, false, false);
+            if let Some(&condition) = self.stop_conditions.iter().find(|condition| condition.is_met(cpu)) {
+                return Ok((frame + 1, StopReason::Condition(condition)));
+            }
+        }
+        Ok((self.max_frames, StopReason::FrameLimit))
+    }
+}
+
+/// Screen as text, `#` for lit pixels and `.` for dark ones. XO-CHIP pixels
+/// only lit on the second plane or on both are `+` and `@`.
+pub fn framebuffer_text(vram: &Framebuffer) -> String {
+    let mut text = String::new();
+    for row in vram.rows() {
+        text.extend(row.iter().map(|&pixel| ['.', '#', '+', '@'][pixel as usize & 3]));
+        text.push('\n');
+    }
+    text
+}
+
+/// Screen as a binary PPM image, one image pixel per screen pixel
+pub fn framebuffer_ppm(vram: &Framebuffer) -> Vec<u8> {
+    let mut image = format!("P6\n{} {}\n255\n", vram.width(), vram.height()).into_bytes();
+    image.extend_from_slice(&vram.rgb24());
+    image
+}
+
+#[cfg(test)]
+#[path = "./headless_tests.rs"]
+mod headless_tests;
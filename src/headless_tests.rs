@@ -0,0 +1,84 @@
+use super::*;
+use crate::frontend::{MemoryAudio, MemoryInput, MemoryVideo, NullAudio, NullVideo};
+use crate::test_utils::cpu_with_program;
+
+fn headless(max_frames: usize, stop_conditions: Vec<StopCondition>) -> Headless {
+    Headless { max_frames, instructions_per_frame: 10, stop_conditions }
+}
+
+fn run(runner: &Headless, cpu: &mut Cpu) -> Result<(usize, StopReason), CpuError> {
+    runner.run(cpu, &mut NullVideo, &mut NullAudio, &mut ScriptedInput::default())
+}
+
+#[test]
+fn test_input_script() {
+    let script = InputScript::parse("# start\n0 -\n\n10 5A  # both\n12 -\n").unwrap();
+    assert_eq!(script.keys(0), [false; 16]);
+    let mut held = [false; 16];
+    held[5] = true;
+    held[0xA] = true;
+    assert_eq!(script.keys(10), held);
+    assert_eq!(script.keys(11), held);
+    assert_eq!(script.keys(12), [false; 16]);
+    assert_eq!(InputScript::default().keys(100), [false; 16]);
+}
+
+#[test]
+fn test_input_script_errors() {
+    assert_eq!(InputScript::parse("x 1").unwrap_err(), ScriptError { line: 1, message: "invalid frame number `x`".to_string() });
+    assert_eq!(InputScript::parse("0 -\n1").unwrap_err().line, 2);
+    assert_eq!(InputScript::parse("0 G").unwrap_err().message, "invalid key `G`");
+    assert_eq!(InputScript::parse("5 1\n5 2").unwrap_err().message, "frame 5 is not after the previous line");
+    assert_eq!(InputScript::parse("0 1 2").unwrap_err().message, "unexpected `2`");
+}
+
+#[test]
+fn test_stop_conditions() {
+    let mut cpu = cpu_with_program("LD V0, 1\nEXIT");
+    assert_eq!(run(&headless(100, vec![StopCondition::Halt]), &mut cpu), Ok((1, StopReason::Condition(StopCondition::Halt))));
+
+    let mut cpu = cpu_with_program("LD V0, 1\nend: JP end");
+    assert_eq!(run(&headless(100, vec![StopCondition::Halt]), &mut cpu), Ok((100, StopReason::FrameLimit)));
+    let mut cpu = cpu_with_program("LD V0, 1\nend: JP end");
+    assert_eq!(run(&headless(100, vec![StopCondition::Loop]), &mut cpu), Ok((1, StopReason::Condition(StopCondition::Loop))));
+}
+
+#[test]
+fn test_scripted_input() {
+    // Waits for key 7, then draws its digit
+    let mut cpu = cpu_with_program("LD V0, K\nLD F, V0\nDRW V1, V1, 5\nend: JP end");
+    let runner = headless(100, vec![StopCondition::Loop]);
+    let mut input = ScriptedInput::new(InputScript::parse("20 7\n21 -").unwrap());
+    let mut video = MemoryVideo::default();
+    let result = runner.run(&mut cpu, &mut video, &mut NullAudio, &mut input);
+    assert_eq!(result, Ok((21, StopReason::Condition(StopCondition::Loop))));
+    assert_eq!(video.frames_drawn, 1);
+    let text = framebuffer_text(cpu.state().vram);
+    let lines: Vec<&str> = text.lines().collect();
+    assert_eq!(lines.len(), 32);
+    assert_eq!(&lines[0][..8], "####....");
+    assert_eq!(&lines[1][..8], "...#....");
+}
+
+#[test]
+fn test_input_ends() {
+    // Buzzes for 3 frames
+    let mut cpu = cpu_with_program("LD V0, 3\nLD ST, V0\nend: JP end");
+    let mut audio = MemoryAudio::default();
+    let mut input = MemoryInput::new(vec![[false; 16]; 5]);
+    let result = headless(100, Vec::new()).run(&mut cpu, &mut NullVideo, &mut audio, &mut input);
+    assert_eq!(result, Ok((5, StopReason::Quit)));
+    assert_eq!(audio.frames_played, 2);
+    assert!(!audio.playing);
+}
+
+#[test]
+fn test_framebuffer_ppm() {
+    let mut vram = Framebuffer::new();
+    vram.set_pixel(1, 0, 1);
+    let image = framebuffer_ppm(&vram);
+    let header = b"P6\n64 32\n255\n";
+    assert_eq!(&image[..header.len()], header);
+    assert_eq!(image.len(), header.len() + 64 * 32 * 3);
+    assert_eq!(&image[header.len()..header.len() + 6], &[0, 0, 0, 255, 255, 255]);
+}
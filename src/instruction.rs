@@ -0,0 +1,275 @@
+/// Instruction
+/// Decoded form of a CHIP-8, SUPER-CHIP or XO-CHIP opcode. Names follow the
+/// Cowgod mnemonics used in the `op_*` doc comments of `Cpu`.
+/// `x` and `y` are register indexes, `kk` a byte, `n` a nibble and the
+/// tuple variants with u16 carry a 12 bit address.
+///
+/// Every opcode decodes to exactly one instruction and `encode` gives the
+/// opcode back, opcodes that don't mean anything become `Unknown`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Instruction {
+    /// 0nnn - SYS addr, machine code call on the COSMAC VIP
+    Sys(u16),
+    /// 00Cn - SCD nibble (SUPER-CHIP)
+    Scd(u8),
+    /// 00E0 - CLS
+    Cls,
+    /// 00EE - RET
+    Ret,
+    /// 00FB - SCR (SUPER-CHIP)
+    Scr,
+    /// 00FC - SCL (SUPER-CHIP)
+    Scl,
+    /// 00FD - EXIT (SUPER-CHIP)
+    Exit,
+    /// 00FE - LOW (SUPER-CHIP)
+    Low,
+    /// 00FF - HIGH (SUPER-CHIP)
+    High,
+    /// 1nnn - JP addr
+    Jp(u16),
+    /// 2nnn - CALL addr
+    Call(u16),
+    /// 3xkk - SE Vx, byte
+    SeVxByte { x: u8, kk: u8 },
+    /// 4xkk - SNE Vx, byte
+    SneVxByte { x: u8, kk: u8 },
+    /// 5xy0 - SE Vx, Vy
+    SeVxVy { x: u8, y: u8 },
+    /// 5xy2 - SAVE Vx - Vy (XO-CHIP)
+    Save { x: u8, y: u8 },
+    /// 5xy3 - LOAD Vx - Vy (XO-CHIP)
+    Load { x: u8, y: u8 },
+    /// 6xkk - LD Vx, byte
+    LdVxByte { x: u8, kk: u8 },
+    /// 7xkk - ADD Vx, byte
+    AddVxByte { x: u8, kk: u8 },
+    /// 8xy0 - LD Vx, Vy
+    LdVxVy { x: u8, y: u8 },
+    /// 8xy1 - OR Vx, Vy
+    Or { x: u8, y: u8 },
+    /// 8xy2 - AND Vx, Vy
+    And { x: u8, y: u8 },
+    /// 8xy3 - XOR Vx, Vy
+    Xor { x: u8, y: u8 },
+    /// 8xy4 - ADD Vx, Vy
+    AddVxVy { x: u8, y: u8 },
+    /// 8xy5 - SUB Vx, Vy
+    Sub { x: u8, y: u8 },
+    /// 8xy6 - SHR Vx {, Vy}
+    Shr { x: u8, y: u8 },
+    /// 8xy7 - SUBN Vx, Vy
+    Subn { x: u8, y: u8 },
+    /// 8xyE - SHL Vx {, Vy}
+    Shl { x: u8, y: u8 },
+    /// 9xy0 - SNE Vx, Vy
+    SneVxVy { x: u8, y: u8 },
+    /// Annn - LD I, addr
+    LdI(u16),
+    /// Bnnn - JP V0, addr
+    JpV0(u16),
+    /// Cxkk - RND Vx, byte
+    Rnd { x: u8, kk: u8 },
+    /// Dxyn - DRW Vx, Vy, nibble
+    Drw { x: u8, y: u8, n: u8 },
+    /// Ex9E - SKP Vx
+    Skp(u8),
+    /// ExA1 - SKNP Vx
+    Sknp(u8),
+    /// F000 nnnn - LD I, long addr (XO-CHIP), the address is in the next word
+    LdILong,
+    /// Fn01 - PLANE n (XO-CHIP)
+    Plane(u8),
+    /// F002 - AUDIO (XO-CHIP)
+    Audio,
+    /// Fx07 - LD Vx, DT
+    LdVxDt(u8),
+    /// Fx0A - LD Vx, K
+    LdVxK(u8),
+    /// Fx15 - LD DT, Vx
+    LdDtVx(u8),
+    /// Fx18 - LD ST, Vx
+    LdStVx(u8),
+    /// Fx1E - ADD I, Vx
+    AddIVx(u8),
+    /// Fx29 - LD F, Vx
+    LdFVx(u8),
+    /// Fx30 - LD HF, Vx (SUPER-CHIP)
+    LdHfVx(u8),
+    /// Fx33 - LD B, Vx
+    LdBVx(u8),
+    /// Fx3A - PITCH Vx (XO-CHIP)
+    Pitch(u8),
+    /// Fx55 - LD [I], Vx
+    LdMemVx(u8),
+    /// Fx65 - LD Vx, [I]
+    LdVxMem(u8),
+    /// Fx75 - LD R, Vx (SUPER-CHIP)
+    LdRVx(u8),
+    /// Fx85 - LD Vx, R (SUPER-CHIP)
+    LdVxR(u8),
+    /// Anything else
+    Unknown(u16),
+}
+
+impl Instruction {
+    pub fn decode(opcode: u16) -> Instruction {
+        let nibbles = (
+            (opcode & 0xF000) >> 12,
+            (opcode & 0x0F00) >> 8,
+            (opcode & 0x00F0) >> 4,
+            (opcode & 0x000F)
+        );
+
+        let nnn = opcode & 0x0FFF;
+        let kk = (opcode & 0x00FF) as u8;
+        let x = nibbles.1 as u8;
+        let y = nibbles.2 as u8;
+        let n = nibbles.3 as u8;
+
+        match nibbles {
+            (0x00, 0x00, 0x0c, _) => Instruction::Scd(n),
+            (0x00, 0x00, 0x0e, 0x00) => Instruction::Cls,
+            (0x00, 0x00, 0x0e, 0x0e) => Instruction::Ret,
+            (0x00, 0x00, 0x0f, 0x0b) => Instruction::Scr,
+            (0x00, 0x00, 0x0f, 0x0c) => Instruction::Scl,
+            (0x00, 0x00, 0x0f, 0x0d) => Instruction::Exit,
+            (0x00, 0x00, 0x0f, 0x0e) => Instruction::Low,
+            (0x00, 0x00, 0x0f, 0x0f) => Instruction::High,
+            (0x00, _, _, _) => Instruction::Sys(nnn),
+            (0x01, _, _, _) => Instruction::Jp(nnn),
+            (0x02, _, _, _) => Instruction::Call(nnn),
+            (0x03, _, _, _) => Instruction::SeVxByte { x, kk },
+            (0x04, _, _, _) => Instruction::SneVxByte { x, kk },
+            (0x05, _, _, 0x00) => Instruction::SeVxVy { x, y },
+            (0x05, _, _, 0x02) => Instruction::Save { x, y },
+            (0x05, _, _, 0x03) => Instruction::Load { x, y },
+            (0x06, _, _, _) => Instruction::LdVxByte { x, kk },
+            (0x07, _, _, _) => Instruction::AddVxByte { x, kk },
+            (0x08, _, _, 0x00) => Instruction::LdVxVy { x, y },
+            (0x08, _, _, 0x01) => Instruction::Or { x, y },
+            (0x08, _, _, 0x02) => Instruction::And { x, y },
+            (0x08, _, _, 0x03) => Instruction::Xor { x, y },
+            (0x08, _, _, 0x04) => Instruction::AddVxVy { x, y },
+            (0x08, _, _, 0x05) => Instruction::Sub { x, y },
+            (0x08, _, _, 0x06) => Instruction::Shr { x, y },
+            (0x08, _, _, 0x07) => Instruction::Subn { x, y },
+            (0x08, _, _, 0x0E) => Instruction::Shl { x, y },
+            (0x09, _, _, 0x00) => Instruction::SneVxVy { x, y },
+            (0x0A, _, _, _) => Instruction::LdI(nnn),
+            (0x0B, _, _, _) => Instruction::JpV0(nnn),
+            (0x0C, _, _, _) => Instruction::Rnd { x, kk },
+            (0x0D, _, _, _) => Instruction::Drw { x, y, n },
+            (0x0E, _, 0x09, 0x0E) => Instruction::Skp(x),
+            (0x0E, _, 0x0A, 0x01) => Instruction::Sknp(x),
+            (0x0f, 0x00, 0x00, 0x00) => Instruction::LdILong,
+            (0x0f, _, 0x00, 0x01) => Instruction::Plane(x),
+            (0x0f, 0x00, 0x00, 0x02) => Instruction::Audio,
+            (0x0f, _, 0x00, 0x07) => Instruction::LdVxDt(x),
+            (0x0f, _, 0x00, 0x0A) => Instruction::LdVxK(x),
+            (0x0f, _, 0x01, 0x05) => Instruction::LdDtVx(x),
+            (0x0f, _, 0x01, 0x08) => Instruction::LdStVx(x),
+            (0x0f, _, 0x01, 0x0E) => Instruction::AddIVx(x),
+            (0x0f, _, 0x02, 0x09) => Instruction::LdFVx(x),
+            (0x0f, _, 0x03, 0x00) => Instruction::LdHfVx(x),
+            (0x0f, _, 0x03, 0x03) => Instruction::LdBVx(x),
+            (0x0f, _, 0x03, 0x0A) => Instruction::Pitch(x),
+            (0x0f, _, 0x05, 0x05) => Instruction::LdMemVx(x),
+            (0x0f, _, 0x06, 0x05) => Instruction::LdVxMem(x),
+            (0x0f, _, 0x07, 0x05) => Instruction::LdRVx(x),
+            (0x0f, _, 0x08, 0x05) => Instruction::LdVxR(x),
+            _ => Instruction::Unknown(opcode),
+        }
+    }
+
+    pub fn encode(&self) -> u16 {
+        fn xkk(high: u16, x: u8, kk: u8) -> u16 {
+            high << 12 | (x as u16 & 0x0F) << 8 | kk as u16
+        }
+        fn xyn(high: u16, x: u8, y: u8, n: u8) -> u16 {
+            high << 12 | (x as u16 & 0x0F) << 8 | (y as u16 & 0x0F) << 4 | (n as u16 & 0x0F)
+        }
+        fn fx(x: u8, low: u16) -> u16 {
+            0xF000 | (x as u16 & 0x0F) << 8 | low
+        }
+
+        match *self {
+            Instruction::Sys(nnn) => nnn & 0x0FFF,
+            Instruction::Scd(n) => 0x00C0 | (n as u16 & 0x0F),
+            Instruction::Cls => 0x00E0,
+            Instruction::Ret => 0x00EE,
+            Instruction::Scr => 0x00FB,
+            Instruction::Scl => 0x00FC,
+            Instruction::Exit => 0x00FD,
+            Instruction::Low => 0x00FE,
+            Instruction::High => 0x00FF,
+            Instruction::Jp(nnn) => 0x1000 | (nnn & 0x0FFF),
+            Instruction::Call(nnn) => 0x2000 | (nnn & 0x0FFF),
+            Instruction::SeVxByte { x, kk } => xkk(0x3, x, kk),
+            Instruction::SneVxByte { x, kk } => xkk(0x4, x, kk),
+            Instruction::SeVxVy { x, y } => xyn(0x5, x, y, 0x0),
+            Instruction::Save { x, y } => xyn(0x5, x, y, 0x2),
+            Instruction::Load { x, y } => xyn(0x5, x, y, 0x3),
+            Instruction::LdVxByte { x, kk } => xkk(0x6, x, kk),
+            Instruction::AddVxByte { x, kk } => xkk(0x7, x, kk),
+            Instruction::LdVxVy { x, y } => xyn(0x8, x, y, 0x0),
+            Instruction::Or { x, y } => xyn(0x8, x, y, 0x1),
+            Instruction::And { x, y } => xyn(0x8, x, y, 0x2),
+            Instruction::Xor { x, y } => xyn(0x8, x, y, 0x3),
+            Instruction::AddVxVy { x, y } => xyn(0x8, x, y, 0x4),
+            Instruction::Sub { x, y } => xyn(0x8, x, y, 0x5),
+            Instruction::Shr { x, y } => xyn(0x8, x, y, 0x6),
+            Instruction::Subn { x, y } => xyn(0x8, x, y, 0x7),
+            Instruction::Shl { x, y } => xyn(0x8, x, y, 0xE),
+            Instruction::SneVxVy { x, y } => xyn(0x9, x, y, 0x0),
+            Instruction::LdI(nnn) => 0xA000 | (nnn & 0x0FFF),
+            Instruction::JpV0(nnn) => 0xB000 | (nnn & 0x0FFF),
+            Instruction::Rnd { x, kk } => xkk(0xC, x, kk),
+            Instruction::Drw { x, y, n } => xyn(0xD, x, y, n),
+            Instruction::Skp(x) => xkk(0xE, x, 0x9E),
+            Instruction::Sknp(x) => xkk(0xE, x, 0xA1),
+            Instruction::LdILong => 0xF000,
+            Instruction::Plane(n) => fx(n, 0x01),
+            Instruction::Audio => 0xF002,
+            Instruction::LdVxDt(x) => fx(x, 0x07),
+            Instruction::LdVxK(x) => fx(x, 0x0A),
+            Instruction::LdDtVx(x) => fx(x, 0x15),
+            Instruction::LdStVx(x) => fx(x, 0x18),
+            Instruction::AddIVx(x) => fx(x, 0x1E),
+            Instruction::LdFVx(x) => fx(x, 0x29),
+            Instruction::LdHfVx(x) => fx(x, 0x30),
+            Instruction::LdBVx(x) => fx(x, 0x33),
+            Instruction::Pitch(x) => fx(x, 0x3A),
+            Instruction::LdMemVx(x) => fx(x, 0x55),
+            Instruction::LdVxMem(x) => fx(x, 0x65),
+            Instruction::LdRVx(x) => fx(x, 0x75),
+            Instruction::LdVxR(x) => fx(x, 0x85),
+            Instruction::Unknown(opcode) => opcode,
+        }
+    }
+
+    /// Conditional skip of the next instruction
+    pub fn is_skip(&self) -> bool {
+        matches!(
+            *self,
+            Instruction::SeVxByte { .. }
+                | Instruction::SneVxByte { .. }
+                | Instruction::SeVxVy { .. }
+                | Instruction::SneVxVy { .. }
+                | Instruction::Skp(_)
+                | Instruction::Sknp(_)
+        )
+    }
+
+    /// Instruction length in bytes, F000 nnnn is the only four byte one
+    pub fn size(&self) -> usize {
+        match *self {
+            Instruction::LdILong => 4,
+            _ => 2,
+        }
+    }
+}
+
+#[cfg(test)]
+#[path = "./instruction_tests.rs"]
+mod instruction_tests;
@@ -0,0 +1,52 @@
+use super::*;
+
+#[test]
+fn test_round_trip_all_opcodes() {
+    for opcode in 0..=0xFFFFu16 {
+        assert_eq!(Instruction::decode(opcode).encode(), opcode, "opcode {:04X}", opcode);
+    }
+}
+
+#[test]
+fn test_round_trip_instructions() {
+    let instructions = [
+        Instruction::Cls,
+        Instruction::Jp(0x234),
+        Instruction::Drw { x: 1, y: 2, n: 0 },
+        Instruction::Save { x: 0xF, y: 0 },
+        Instruction::LdVxMem(7),
+        Instruction::LdILong,
+    ];
+    for &instruction in instructions.iter() {
+        assert_eq!(Instruction::decode(instruction.encode()), instruction);
+    }
+}
+
+#[test]
+fn test_decode() {
+    assert_eq!(Instruction::decode(0x00e0), Instruction::Cls);
+    assert_eq!(Instruction::decode(0x00ee), Instruction::Ret);
+    assert_eq!(Instruction::decode(0x00c4), Instruction::Scd(4));
+    assert_eq!(Instruction::decode(0x0123), Instruction::Sys(0x123));
+    assert_eq!(Instruction::decode(0x1abc), Instruction::Jp(0xabc));
+    assert_eq!(Instruction::decode(0x3a12), Instruction::SeVxByte { x: 0xa, kk: 0x12 });
+    assert_eq!(Instruction::decode(0x8ab6), Instruction::Shr { x: 0xa, y: 0xb });
+    assert_eq!(Instruction::decode(0xd125), Instruction::Drw { x: 1, y: 2, n: 5 });
+    assert_eq!(Instruction::decode(0xe29e), Instruction::Skp(2));
+    assert_eq!(Instruction::decode(0xf000), Instruction::LdILong);
+    assert_eq!(Instruction::decode(0xf201), Instruction::Plane(2));
+    assert_eq!(Instruction::decode(0xf565), Instruction::LdVxMem(5));
+}
+
+#[test]
+fn test_decode_unknown() {
+    for &opcode in [0x5121, 0x8008, 0x9001, 0xe000, 0xf100, 0xf102, 0xffff].iter() {
+        assert_eq!(Instruction::decode(opcode), Instruction::Unknown(opcode));
+    }
+}
+
+#[test]
+fn test_size() {
+    assert_eq!(Instruction::LdILong.size(), 4);
+    assert_eq!(Instruction::Cls.size(), 2);
+}
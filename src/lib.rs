@@ -0,0 +1,42 @@
+//! rChip8 emulator core
+//!
+//! Contains the CHIP-8 interpreter and ROM loading. Nothing in here depends on
+//! SDL2, the window frontend lives in the `r_chip8` binary behind the `sdl`
+//! feature.
+//! The `disasm` and `asm` binaries are built on the same instruction set.
+pub mod analysis;
+pub mod assembler;
+pub mod cpu;
+pub mod debugger;
+pub mod disassembler;
+pub mod error;
+pub mod font_set;
+pub mod frame_timer;
+pub mod framebuffer;
+pub mod frontend;
+pub mod gdb;
+pub mod headless;
+pub mod instruction;
+pub mod movie;
+pub mod octo;
+pub mod quirks;
+pub mod random;
+pub mod rewind;
+pub mod rom_reader;
+pub mod save_state;
+pub mod tty;
+
+#[cfg(test)]
+mod test_utils;
+
+pub use cpu::{AccessKind, AudioPattern, Cpu, CycleState, MemoryAccess, UnknownOpcodePolicy};
+pub use error::CpuError;
+pub use frame_timer::FrameTimer;
+pub use framebuffer::Framebuffer;
+pub use instruction::Instruction;
+pub use movie::Movie;
+pub use quirks::{IndexIncrement, Quirks};
+pub use random::Random;
+pub use rewind::Rewind;
+pub use rom_reader::RomReader;
+pub use save_state::StateError;
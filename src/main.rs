@@ -1,28 +1,96 @@
-mod cpu;
+mod options;
+#[cfg(feature = "sdl")]
 mod utils;
-mod font_set;
 
-use cpu::Cpu;
-use utils::Display;
-use utils::RomReader;
-use utils::Audio;
-use utils::EventHandler;
+use options::{Options, USAGE, MAX_INSTRUCTIONS_PER_FRAME};
+use r_chip8::Cpu;
+use r_chip8::CpuError;
+use r_chip8::FrameTimer;
+use r_chip8::Movie;
+use r_chip8::Rewind;
+use r_chip8::RomReader;
+use r_chip8::octo;
+use r_chip8::save_state;
+use r_chip8::debugger::{Command, Debugger};
+use r_chip8::gdb::GdbStub;
+use r_chip8::debugger;
+use r_chip8::frontend::{self, AudioSink, Hotkey, InputSource, NullAudio, NullVideo, VideoSink};
+use r_chip8::tty::{RawMode, TtyInput, TtyMode, TtyVideo};
+use r_chip8::headless::{self, Headless, InputScript, ScriptedInput, StopReason};
+#[cfg(feature = "sdl")]
+use utils::{Audio, Display, EventHandler};
 
 use std::process::exit;
 use std::env;
+use std::path::Path;
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::sync::mpsc::{self, Receiver};
 use std::thread;
-use std::time;
+use std::time::Duration;
+
+const FRAMES_PER_SECOND: u32 = 60;
+const SAVE_STATE_SLOTS: usize = 10;
 
 fn main() {
-    // Get rom file name from args
+    // Get rom file name and options from args
     let args: Vec<String> = env::args().collect();
-    let rom_filename = &args[1];
+    let options = Options::parse(&args).unwrap_or_else(|e| {
+        eprintln!("{}\n\n{}", e, USAGE);
+        exit(1)
+    });
+
+    // Load game, Octo sources are compiled first
+    let program = load_program(&options.rom_filename);
+
+    // Save states and movies are only used with the rom they were made with
+    let rom_hash = save_state::rom_hash(&program);
+
+    // Movies replay recorded input, so both need the random seed of the run
+    let playback = options.play_filename.as_ref().map(|filename| load_movie(filename, rom_hash));
+    let seed = match (&playback, options.seed) {
+        (Some(movie), _) => Some(movie.seed),
+        (None, Some(seed)) => Some(seed),
+        (None, None) if options.record_filename.is_some() => Some(rand::random()),
+        (None, None) => None,
+    };
+    let recording = match (&options.record_filename, seed) {
+        (Some(_), Some(seed)) => Some(Movie::new(rom_hash, seed)),
+        _ => None,
+    };
+
+    // Initialize machine
+    let mut processor = Cpu::new(options.quirks);
+    processor.set_unknown_opcode_policy(options.unknown_opcode_policy);
+    if let Some(seed) = seed {
+        processor.set_seed(seed);
+    }
+
+    // Load game to machine memory
+    processor.read_data_to_memory(&program);
 
+    // Headless runs never touch SDL, so they work without a display or sound card
+    if options.headless {
+        exit(run_headless(&mut processor, &options))
+    }
+
+    if let Some(mode) = options.tty {
+        exit(run_tty(mode, &mut processor, &options, rom_hash, (playback, recording)))
+    }
+
+    exit(run_window(&mut processor, &options, rom_hash, (playback, recording)))
+}
+
+/// Run in an SDL window
+#[cfg(feature = "sdl")]
+fn run_window(processor: &mut Cpu, options: &Options, rom_hash: u64, movies: (Option<Movie>, Option<Movie>)) -> i32 {
     // Initialize sdl2
     let sdl2_context = sdl2::init().unwrap();
 
-    // Initialize display driver
-    let mut display = Display::new(&sdl2_context);
+    // Initialize display driver, the texture creator outlives the display's texture
+    let canvas = Display::open_window(&sdl2_context);
+    let texture_creator = canvas.texture_creator();
+    let mut display = Display::new(canvas, &texture_creator);
 
     // Initialize audio driver
     let mut audio = Audio::new(&sdl2_context);
@@ -30,31 +98,392 @@ fn main() {
     // Initialize keypad
     let mut event_handler = EventHandler::new(&sdl2_context);
 
-    // Load game
-    let rom = RomReader::new(rom_filename);
+    run(&mut display, &mut audio, &mut event_handler, processor, options, rom_hash, movies)
+}
 
-    // Initialize machine
-    let mut processor = Cpu::new();
+/// Built without SDL, only the terminal and headless frontends are available
+#[cfg(not(feature = "sdl"))]
+fn run_window(_processor: &mut Cpu, _options: &Options, _rom_hash: u64, _movies: (Option<Movie>, Option<Movie>)) -> i32 {
+    eprintln!("rChip8 was built without the `sdl` feature, run with --tty or --headless");
+    1
+}
 
-    // Load game to machine memory
-    processor.read_data_to_memory(&rom.data);
+/// Run in the terminal instead of a window. The terminal is put back in
+/// order before returning the exit code.
+fn run_tty(mode: TtyMode, processor: &mut Cpu, options: &Options, rom_hash: u64, movies: (Option<Movie>, Option<Movie>)) -> i32 {
+    let _raw_mode = match RawMode::enable() {
+        Ok(raw_mode) => raw_mode,
+        Err(error) => {
+            eprintln!("Can't put the terminal in raw mode: {}", error);
+            return 1;
+        }
+    };
+    let mut video = TtyVideo::new(io::stdout(), mode);
+    let mut input = TtyInput::new(Duration::from_millis(options.key_release_ms));
+    run(&mut video, &mut NullAudio, &mut input, processor, options, rom_hash, movies)
+}
+
+/// Emulation loop, runs until the input source quits or the rom exits.
+/// Returns the exit code.
+fn run<V: VideoSink, A: AudioSink, I: InputSource>(
+    video: &mut V,
+    audio: &mut A,
+    input: &mut I,
+    processor: &mut Cpu,
+    options: &Options,
+    rom_hash: u64,
+    (mut playback, mut recording): (Option<Movie>, Option<Movie>),
+) -> i32 {
+    let mut instructions_per_frame = options.instructions_per_frame;
+    let mut slot = 0;
+    let movie_running = playback.is_some() || recording.is_some();
+    let mut frame = 0;
 
-    // Main loop.
-    while let Ok(keys) = event_handler.event_poller() {
-        let cycle_state = processor.cycle(keys);
-        if cycle_state.vram_changed {
-            display.draw(cycle_state.vram);
+    // Debugger reads commands from the terminal while the window keeps running
+    let mut debugger = if options.debug {
+        Some((Debugger::new(instructions_per_frame), read_commands()))
+    } else {
+        None
+    };
+
+    // GDB remote protocol, the rom waits for a client to attach and continue
+    let mut gdb_stub = None;
+    if let Some(port) = options.gdb_port {
+        let stub = match GdbStub::bind(port, instructions_per_frame) {
+            Ok(stub) => stub,
+            Err(error) => {
+                eprintln!("Can't listen for GDB on port {}: {}", port, error);
+                return 1;
+            }
+        };
+        if let Ok(address) = stub.local_addr() {
+            report(video, options, &format!("Waiting for GDB on {}, attach with `target remote {}`", address, address));
         }
+        gdb_stub = Some(stub);
+    }
+    let debugging = debugger.is_some() || gdb_stub.is_some();
+
+    // Snapshots of past frames, played back while the rewind key is held.
+    // Jumping around in time would break movies.
+    let mut rewind = if options.rewind_memory > 0 && !movie_running {
+        Some(Rewind::new(options.rewind_memory, options.rewind_frames))
+    } else {
+        None
+    };
 
-        // Check delay timers and output timers
-        if cycle_state.sound  {
-            audio.start_audio()
-        } else {
-            audio.stop_audio()
+    // Main loop. Runs one frame of instructions per 60 Hz tick
+    let mut frame_timer = FrameTimer::new(FRAMES_PER_SECOND);
+    while let Some(live_keys) = input.poll() {
+        for hotkey in input.take_hotkeys() {
+            let message = match hotkey {
+                Hotkey::Faster | Hotkey::Slower => {
+                    instructions_per_frame = adjust_speed(instructions_per_frame, hotkey);
+                    format!("Instructions per frame: {}", instructions_per_frame)
+                }
+                Hotkey::PreviousSlot | Hotkey::NextSlot => {
+                    slot = change_slot(slot, hotkey);
+                    format!("Save state slot {}", slot)
+                }
+                Hotkey::SaveState | Hotkey::LoadState if movie_running => {
+                    "Save states can't be used while a movie records or plays".to_string()
+                }
+                Hotkey::SaveState => save_to_slot(processor, &options.rom_filename, slot, rom_hash),
+                Hotkey::LoadState => load_from_slot(processor, &options.rom_filename, slot, rom_hash),
+            };
+            report(video, options, &message);
         }
 
-        thread::sleep(time::Duration::from_millis(2))
+        // Movie input replaces the keyboard until it runs out
+        let (keys, frame_ipf) = match playback.as_ref().and_then(|movie| movie.frames().get(frame)) {
+            Some(movie_frame) => (movie_frame.keys, movie_frame.instructions_per_frame),
+            None => (live_keys, instructions_per_frame),
+        };
+
+        let rewinding = input.is_rewinding()
+            && rewind.as_mut().is_some_and(|rewind| rewind.pop(processor));
+        if let Some(rewind) = rewind.as_mut() {
+            if !rewinding && !is_paused(&debugger, &gdb_stub) {
+                rewind.push(processor);
+            }
+        }
+
+        // Ok(false) when the debugger was told to quit
+        let result = match (debugger.as_mut(), gdb_stub.as_mut()) {
+            _ if rewinding => Ok(true),
+            (Some((debugger, commands)), _) => {
+                debugger.set_instructions_per_frame(instructions_per_frame);
+                debug_frame(debugger, commands, processor, keys)
+            }
+            // CPU errors are reported to the GDB client as signals
+            (None, Some(stub)) => {
+                stub.set_instructions_per_frame(instructions_per_frame);
+                if let Err(error) = stub.run_frame(processor, keys) {
+                    report_error(video, options, &format!("GDB connection error: {}", error));
+                }
+                Ok(true)
+            }
+            (None, None) => processor.run_frame(keys, frame_ipf).map(|_| true),
+        };
+        if let Ok(false) = result {
+            break;
+        }
+
+        if let Some(movie) = recording.as_mut() {
+            movie.record(processor, keys, frame_ipf);
+        }
+        if let Some(movie) = playback.as_ref() {
+            let finished = match movie.check(frame, processor) {
+                Ok(()) if frame + 1 == movie.len() => {
+                    report(video, options, &format!("Movie finished after {} frames, continuing with live input", movie.len()));
+                    true
+                }
+                Ok(()) => false,
+                Err(error) => {
+                    report_error(video, options, &format!("Movie stopped: {}", error));
+                    true
+                }
+            };
+            if finished {
+                playback = None;
+            }
+        }
+        frame += 1;
+
+        let paused = rewinding || is_paused(&debugger, &gdb_stub);
+        let cycle_state = match result {
+            Ok(_) => processor.state(),
+            Err(error) => {
+                // Leave the last frame on screen until the window is closed
+                report_error(video, options, &format!("crashed: {}", error));
+                save_movie(video, options, &recording);
+                audio.stop_audio();
+                if let Some((debugger, commands)) = debugger.as_mut() {
+                    // Keep the prompt to look at what went wrong
+                    println!("{}", debugger.execute(Command::Disassemble { address: None, count: 9 }, processor, keys));
+                    prompt();
+                    while input.poll().is_some() {
+                        if debug_commands(debugger, commands, processor, keys) {
+                            break;
+                        }
+                        frame_timer.wait();
+                    }
+                    return 1;
+                }
+                while input.poll().is_some() {
+                    frame_timer.wait();
+                }
+                return 1;
+            }
+        };
+        // Stepping in the debugger can change vram without a full frame
+        frontend::present(video, audio, &cycle_state, debugging, paused);
+
+        // Rom exited with 00FD, debuggers keep the session open
+        if cycle_state.halted && !debugging {
+            break;
+        }
+
+        frame_timer.wait();
     }
-    exit(0)
+    save_movie(video, options, &recording);
+    0
+}
+
+/// Run without SDL and print the final screen and registers. Returns the
+/// exit code: 1 on errors, 2 when the frame limit came before --until.
+fn run_headless(processor: &mut Cpu, options: &Options) -> i32 {
+    let script = match &options.input_filename {
+        Some(filename) => {
+            let script = fs::read_to_string(filename)
+                .map_err(|error| error.to_string())
+                .and_then(|source| InputScript::parse(&source).map_err(|error| error.to_string()));
+            script.unwrap_or_else(|error| {
+                eprintln!("Can't read input script {}: {}", filename, error);
+                exit(1)
+            })
+        }
+        None => InputScript::default(),
+    };
+    let runner = Headless {
+        max_frames: options.frames,
+        instructions_per_frame: options.instructions_per_frame,
+        stop_conditions: options.stop_conditions.clone(),
+    };
 
-}
\ No newline at end of file
+    let mut code = match runner.run(processor, &mut NullVideo, &mut NullAudio, &mut ScriptedInput::new(script)) {
+        Ok((frames, reason)) => {
+            println!("Stopped after {} frames: {}", frames, reason);
+            match reason {
+                StopReason::FrameLimit if !options.stop_conditions.is_empty() => 2,
+                _ => 0,
+            }
+        }
+        Err(error) => {
+            println!("CPU error: {}", error);
+            1
+        }
+    };
+    print!("{}", headless::framebuffer_text(processor.state().vram));
+    println!("{}", debugger::registers(processor));
+
+    if let Some(filename) = &options.screenshot_filename {
+        if let Err(error) = fs::write(filename, headless::framebuffer_ppm(processor.state().vram)) {
+            eprintln!("Can't write screenshot {}: {}", filename, error);
+            code = 1;
+        }
+    }
+    code
+}
+
+/// Stopped in the debugger or by the GDB client
+fn is_paused(debugger: &Option<(Debugger, Receiver<String>)>, gdb_stub: &Option<GdbStub>) -> bool {
+    debugger.as_ref().is_some_and(|(debugger, _)| debugger.is_paused())
+        || gdb_stub.as_ref().is_some_and(|stub| stub.is_stopped())
+}
+
+/// Movie to play, exits if it can't be used with this rom
+fn load_movie(filename: &str, rom_hash: u64) -> Movie {
+    let movie = fs::read(filename)
+        .map_err(|error| error.to_string())
+        .and_then(|data| Movie::from_bytes(&data, rom_hash).map_err(|error| error.to_string()));
+    movie.unwrap_or_else(|error| {
+        eprintln!("Can't play movie {}: {}", filename, error);
+        exit(1)
+    })
+}
+
+/// Write the recorded movie, if there is one
+fn save_movie<V: VideoSink>(video: &mut V, options: &Options, recording: &Option<Movie>) {
+    if let (Some(movie), Some(filename)) = (recording, &options.record_filename) {
+        match fs::write(filename, movie.to_bytes()) {
+            Ok(()) => report(video, options, &format!("Recorded {} frames to {}", movie.len(), filename)),
+            Err(error) => report_error(video, options, &format!("Can't write movie {}: {}", filename, error)),
+        }
+    }
+}
+
+/// Show a message in the title, and on stdout unless the terminal frontend
+/// is drawing there
+fn report<V: VideoSink>(video: &mut V, options: &Options, message: &str) {
+    if options.tty.is_none() {
+        println!("{}", message);
+    }
+    video.set_title(&format!("rChip8 - {}", message));
+}
+
+/// Same as `report`, for errors on stderr
+fn report_error<V: VideoSink>(video: &mut V, options: &Options, message: &str) {
+    if options.tty.is_none() {
+        eprintln!("{}", message);
+    }
+    video.set_title(&format!("rChip8 - {}", message));
+}
+
+/// Rom bytes, or the compiled program for `.8o` Octo sources
+fn load_program(filename: &str) -> Vec<u8> {
+    let path = Path::new(filename);
+    if path.extension().is_some_and(|extension| extension == "8o") {
+        match octo::compile_file(path) {
+            Ok(assembly) => assembly.image,
+            Err(error) => {
+                eprintln!("{}", error);
+                exit(1)
+            }
+        }
+    } else {
+        RomReader::new(filename).data
+    }
+}
+
+/// File of a save state slot, next to the rom
+fn state_path(rom_filename: &str, slot: usize) -> String {
+    format!("{}.state{}", rom_filename, slot)
+}
+
+/// Step through the save state slots, wrapping around
+fn change_slot(slot: usize, hotkey: Hotkey) -> usize {
+    match hotkey {
+        Hotkey::PreviousSlot => (slot + SAVE_STATE_SLOTS - 1) % SAVE_STATE_SLOTS,
+        Hotkey::NextSlot => (slot + 1) % SAVE_STATE_SLOTS,
+        _ => slot,
+    }
+}
+
+/// Write the machine to a slot, returns the message to show
+fn save_to_slot(processor: &Cpu, rom_filename: &str, slot: usize, rom_hash: u64) -> String {
+    let path = state_path(rom_filename, slot);
+    match fs::write(&path, processor.save_state(rom_hash)) {
+        Ok(()) => format!("Saved state slot {}", slot),
+        Err(error) => format!("Can't save state slot {} to {}: {}", slot, path, error),
+    }
+}
+
+/// Restore the machine from a slot, returns the message to show
+fn load_from_slot(processor: &mut Cpu, rom_filename: &str, slot: usize, rom_hash: u64) -> String {
+    let path = state_path(rom_filename, slot);
+    let result = fs::read(&path)
+        .map_err(|error| error.to_string())
+        .and_then(|state| processor.load_state(&state, rom_hash).map_err(|error| error.to_string()));
+    match result {
+        Ok(()) => format!("Loaded state slot {}", slot),
+        Err(error) => format!("Can't load state slot {} from {}: {}", slot, path, error),
+    }
+}
+
+/// Lines typed on the terminal, read on their own thread so the window keeps
+/// handling events while waiting for input
+fn read_commands() -> Receiver<String> {
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        for line in io::stdin().lock().lines() {
+            let Ok(line) = line else { break };
+            if sender.send(line).is_err() {
+                break;
+            }
+        }
+    });
+    println!("Debugger paused at the first instruction, type help for commands");
+    prompt();
+    receiver
+}
+
+fn prompt() {
+    print!("(rchip8) ");
+    io::stdout().flush().unwrap_or_default();
+}
+
+/// Run pending debugger commands. Returns true on quit.
+fn debug_commands(debugger: &mut Debugger, commands: &Receiver<String>, processor: &mut Cpu, keys: [bool; 16]) -> bool {
+    for line in commands.try_iter() {
+        match Command::parse(&line) {
+            Ok(Command::Quit) => return true,
+            Ok(command) => println!("{}", debugger.execute(command, processor, keys)),
+            Err(error) => println!("{}", error),
+        }
+        prompt();
+    }
+    false
+}
+
+/// One frame under the debugger: handle commands, then run unless paused.
+/// Returns false on quit.
+fn debug_frame(debugger: &mut Debugger, commands: &Receiver<String>, processor: &mut Cpu, keys: [bool; 16]) -> Result<bool, CpuError> {
+    if debug_commands(debugger, commands, processor, keys) {
+        return Ok(false);
+    }
+    if let Some(reason) = debugger.run_frame(processor, keys)? {
+        println!("\n{}", reason);
+        prompt();
+    }
+    Ok(true)
+}
+
+/// Change instructions per frame by roughly 10%
+fn adjust_speed(instructions_per_frame: usize, hotkey: Hotkey) -> usize {
+    let step = (instructions_per_frame / 10).max(1);
+    match hotkey {
+        Hotkey::Faster => (instructions_per_frame + step).min(MAX_INSTRUCTIONS_PER_FRAME),
+        Hotkey::Slower => instructions_per_frame.saturating_sub(step).max(1),
+        _ => instructions_per_frame,
+    }
+}
@@ -0,0 +1,181 @@
+use std::error::Error;
+use std::fmt;
+
+use crate::cpu::Cpu;
+use crate::save_state::{self, StateError, StateReader, StateWriter};
+
+/// First bytes of every movie file
+pub const MOVIE_MAGIC: [u8; 4] = *b"RC8M";
+pub const MOVIE_VERSION: u16 = 1;
+/// Frames between the state hashes used to detect desyncs
+pub const CHECKPOINT_INTERVAL: usize = 60;
+
+/// Why a movie can't be loaded or stopped matching the emulator
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MovieError {
+    /// Doesn't start with MOVIE_MAGIC
+    NotAMovie,
+    /// Written by a version of the emulator with a different format
+    UnsupportedVersion { found: u16 },
+    /// Recorded while running a different rom
+    WrongRom { expected: u64, found: u64 },
+    /// Ends early or has bytes left over
+    Corrupt,
+    /// The machine state doesn't match the recording after this frame
+    Desync { frame: usize },
+}
+
+impl fmt::Display for MovieError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            MovieError::NotAMovie => write!(f, "not an rChip8 movie"),
+            MovieError::UnsupportedVersion { found } => write!(
+                f,
+                "movie format version {} is not supported, this build reads version {}",
+                found, MOVIE_VERSION
+            ),
+            MovieError::WrongRom { expected, found } => write!(
+                f,
+                "movie was recorded with a different rom (rom hash {:016x}, movie has {:016x})",
+                expected, found
+            ),
+            MovieError::Corrupt => write!(f, "movie is truncated or corrupt"),
+            MovieError::Desync { frame } => write!(f, "playback desynced at frame {}", frame),
+        }
+    }
+}
+
+impl Error for MovieError {}
+
+impl From<StateError> for MovieError {
+    fn from(_: StateError) -> Self {
+        MovieError::Corrupt
+    }
+}
+
+/// Input for one frame of a movie
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Frame {
+    pub keys: [bool; 16],
+    pub instructions_per_frame: usize,
+}
+
+/// Movie
+/// The input of every frame of a run, with the rom hash and random seed it
+/// started from. Replaying the frames into a machine seeded the same way
+/// repeats the run exactly. A hash of the machine state is kept every
+/// CHECKPOINT_INTERVAL frames to notice when a replay goes differently.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Movie {
+    pub rom_hash: u64,
+    pub seed: u64,
+    frames: Vec<Frame>,
+    checkpoints: Vec<u64>,
+}
+
+impl Movie {
+    /// Empty movie, the machine has to be seeded with `seed` before the
+    /// first frame
+    pub fn new(rom_hash: u64, seed: u64) -> Self {
+        Movie {
+            rom_hash,
+            seed,
+            frames: Vec::new(),
+            checkpoints: Vec::new(),
+        }
+    }
+
+    pub fn frames(&self) -> &[Frame] {
+        &self.frames
+    }
+
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    /// Add a frame after the machine has run it
+    pub fn record(&mut self, cpu: &Cpu, keys: [bool; 16], instructions_per_frame: usize) {
+        self.frames.push(Frame { keys, instructions_per_frame });
+        if self.frames.len().is_multiple_of(CHECKPOINT_INTERVAL) {
+            self.checkpoints.push(state_hash(cpu));
+        }
+    }
+
+    /// Compare the machine against the recording after it ran frame `frame`
+    /// (counted from 0). Frames without a checkpoint always match.
+    pub fn check(&self, frame: usize, cpu: &Cpu) -> Result<(), MovieError> {
+        if !(frame + 1).is_multiple_of(CHECKPOINT_INTERVAL) {
+            return Ok(());
+        }
+        match self.checkpoints.get((frame + 1) / CHECKPOINT_INTERVAL - 1) {
+            Some(&hash) if hash != state_hash(cpu) => Err(MovieError::Desync { frame }),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut writer = StateWriter::default();
+        writer.bytes(&MOVIE_MAGIC);
+        writer.u16(MOVIE_VERSION);
+        writer.u64(self.rom_hash);
+        writer.u64(self.seed);
+        writer.u32(self.frames.len() as u32);
+        for frame in self.frames.iter() {
+            let keys = frame.keys.iter().enumerate().fold(0, |mask, (key, &pressed)| mask | (pressed as u16) << key);
+            writer.u16(keys);
+            writer.u16(frame.instructions_per_frame as u16);
+        }
+        writer.u32(self.checkpoints.len() as u32);
+        for &hash in self.checkpoints.iter() {
+            writer.u64(hash);
+        }
+        writer.into_bytes()
+    }
+
+    /// Read a movie recorded with the rom that has hash `rom_hash`
+    pub fn from_bytes(data: &[u8], rom_hash: u64) -> Result<Self, MovieError> {
+        if data.len() < MOVIE_MAGIC.len() || data[..MOVIE_MAGIC.len()] != MOVIE_MAGIC {
+            return Err(MovieError::NotAMovie);
+        }
+        let mut reader = StateReader::new(&data[MOVIE_MAGIC.len()..]);
+        let version = reader.u16()?;
+        if version != MOVIE_VERSION {
+            return Err(MovieError::UnsupportedVersion { found: version });
+        }
+        let found = reader.u64()?;
+        if found != rom_hash {
+            return Err(MovieError::WrongRom { expected: rom_hash, found });
+        }
+        let mut movie = Movie::new(rom_hash, reader.u64()?);
+        for _ in 0..reader.u32()? {
+            let mask = reader.u16()?;
+            let mut keys = [false; 16];
+            for (key, pressed) in keys.iter_mut().enumerate() {
+                *pressed = mask & 1 << key != 0;
+            }
+            let instructions_per_frame = reader.u16()? as usize;
+            movie.frames.push(Frame { keys, instructions_per_frame });
+        }
+        for _ in 0..reader.u32()? {
+            movie.checkpoints.push(reader.u64()?);
+        }
+        reader.finish()?;
+        if movie.checkpoints.len() != movie.frames.len() / CHECKPOINT_INTERVAL {
+            return Err(MovieError::Corrupt);
+        }
+        Ok(movie)
+    }
+}
+
+/// FNV-1a of the save state, settings like quirks aren't included
+fn state_hash(cpu: &Cpu) -> u64 {
+    save_state::rom_hash(&cpu.save_state(0))
+}
+
+#[cfg(test)]
+#[path = "./movie_tests.rs"]
+mod movie_tests;
@@ -0,0 +1,82 @@
+use super::*;
+use crate::quirks::Quirks;
+
+/// Adds a random number to V1 every frame and counts presses of key 5
+fn game() -> Vec<u8> {
+    let source = "
+        loop:
+            RND V0, 0xFF
+            ADD V1, V0
+            LD V2, 5
+            SKNP V2
+            ADD V3, 1
+            JP loop
+    ";
+    crate::assembler::assemble(source).unwrap().image
+}
+
+fn record(rom: &[u8], seed: u64, frames: usize) -> (Movie, Cpu) {
+    let mut cpu = Cpu::new(Quirks::default());
+    cpu.set_seed(seed);
+    cpu.read_data_to_memory(rom);
+    let mut movie = Movie::new(save_state::rom_hash(rom), seed);
+    for frame in 0..frames {
+        let mut keys = [false; 16];
+        keys[5] = frame % 3 == 0;
+        cpu.run_frame(keys, 6).unwrap();
+        movie.record(&cpu, keys, 6);
+    }
+    (movie, cpu)
+}
+
+fn replay(rom: &[u8], movie: &Movie) -> Result<Cpu, MovieError> {
+    let mut cpu = Cpu::new(Quirks::default());
+    cpu.set_seed(movie.seed);
+    cpu.read_data_to_memory(rom);
+    for (index, frame) in movie.frames().iter().enumerate() {
+        cpu.run_frame(frame.keys, frame.instructions_per_frame).unwrap();
+        movie.check(index, &cpu)?;
+    }
+    Ok(cpu)
+}
+
+#[test]
+fn test_replay() {
+    let rom = game();
+    let (movie, recorded) = record(&rom, 99, 150);
+    let bytes = movie.to_bytes();
+    let loaded = Movie::from_bytes(&bytes, save_state::rom_hash(&rom)).unwrap();
+    assert_eq!(loaded, movie);
+    assert_eq!(loaded.len(), 150);
+
+    let replayed = replay(&rom, &loaded).unwrap();
+    assert_eq!(replayed.registers(), recorded.registers());
+    assert_eq!(replayed.registers()[3], 50);
+}
+
+#[test]
+fn test_desync() {
+    let rom = game();
+    let (movie, _) = record(&rom, 99, 150);
+    let mut changed = movie.clone();
+    changed.frames[70].keys[5] = !changed.frames[70].keys[5];
+    assert_eq!(replay(&rom, &changed).err(), Some(MovieError::Desync { frame: 119 }));
+
+    changed = movie.clone();
+    changed.seed = 100;
+    assert_eq!(replay(&rom, &changed).err(), Some(MovieError::Desync { frame: 59 }));
+}
+
+#[test]
+fn test_movie_errors() {
+    let rom = game();
+    let hash = save_state::rom_hash(&rom);
+    let bytes = record(&rom, 1, 61).0.to_bytes();
+    assert_eq!(Movie::from_bytes(b"RC8S", hash), Err(MovieError::NotAMovie));
+    assert_eq!(Movie::from_bytes(&bytes, hash ^ 1), Err(MovieError::WrongRom { expected: hash ^ 1, found: hash }));
+    assert_eq!(Movie::from_bytes(&bytes[..bytes.len() - 1], hash), Err(MovieError::Corrupt));
+
+    let mut future = bytes.clone();
+    future[4..6].copy_from_slice(&(MOVIE_VERSION + 1).to_be_bytes());
+    assert_eq!(Movie::from_bytes(&future, hash), Err(MovieError::UnsupportedVersion { found: MOVIE_VERSION + 1 }));
+}
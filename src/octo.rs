@@ -0,0 +1,713 @@
+use crate::assembler::{AsmError, Assembly};
+use crate::cpu::PROGRAM_START;
+use crate::instruction::Instruction;
+use crate::rom_reader::MAX_ROM_SIZE;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+/// Macro expansions allowed per program, more means a macro expands itself
+const MAX_EXPANSIONS: usize = 10_000;
+
+/// Compile Octo source text
+pub fn compile(source: &str) -> Result<Assembly, AsmError> {
+    Compiler::new(source, "<source>").compile()
+}
+
+/// Compile an Octo `.8o` file
+pub fn compile_file(path: &Path) -> Result<Assembly, AsmError> {
+    let file = path.display().to_string();
+    let source = fs::read_to_string(path).map_err(|error| AsmError {
+        file: file.clone(),
+        line: 0,
+        message: format!("can't read file: {}", error),
+    })?;
+    Compiler::new(&source, &file).compile()
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct Token {
+    text: String,
+    line: usize,
+}
+
+struct Macro {
+    parameters: Vec<String>,
+    body: Vec<Token>,
+}
+
+/// Address operand whose label wasn't defined yet
+struct Fixup {
+    offset: usize,
+    name: String,
+    line: usize,
+    /// Patch a whole word (i := long) instead of the low 12 bits of an opcode
+    long: bool,
+}
+
+/// Skip instructions for a condition. `setup` runs first, `when_false`
+/// skips the next instruction unless the condition holds and `when_true`
+/// skips it if it does.
+struct Condition {
+    setup: Vec<Instruction>,
+    when_false: Instruction,
+    when_true: Instruction,
+}
+
+struct Compiler {
+    file: String,
+    tokens: Vec<Token>,
+    position: usize,
+    line: usize,
+    image: Vec<u8>,
+    /// Offset into image of the next byte
+    here: usize,
+    labels: BTreeMap<String, u16>,
+    constants: BTreeMap<String, i64>,
+    aliases: BTreeMap<String, u8>,
+    macros: BTreeMap<String, Macro>,
+    expansions: usize,
+    fixups: Vec<Fixup>,
+    /// Jumps of open `begin` and `else` blocks
+    branches: Vec<usize>,
+    /// Start address and `while` jumps of open loops
+    loops: Vec<(usize, Vec<usize>)>,
+}
+
+impl Compiler {
+    fn new(source: &str, file: &str) -> Self {
+        Compiler {
+            file: file.to_string(),
+            tokens: tokenize(source),
+            position: 0,
+            line: 0,
+            image: Vec::new(),
+            here: 0,
+            labels: BTreeMap::new(),
+            constants: BTreeMap::new(),
+            aliases: BTreeMap::new(),
+            macros: BTreeMap::new(),
+            expansions: 0,
+            fixups: Vec::new(),
+            branches: Vec::new(),
+            loops: Vec::new(),
+        }
+    }
+
+    fn compile(mut self) -> Result<Assembly, AsmError> {
+        self.program().map_err(|message| AsmError { file: self.file.clone(), line: self.line, message })?;
+        Ok(Assembly { image: self.image, labels: self.labels })
+    }
+
+    fn program(&mut self) -> Result<(), String> {
+        // Execution starts at PROGRAM_START, which jumps to main
+        self.emit(Instruction::Jp(0));
+        self.fixups.push(Fixup { offset: 0, name: "main".to_string(), line: 1, long: false });
+
+        while self.position < self.tokens.len() {
+            self.statement()?;
+        }
+        if !self.branches.is_empty() {
+            return Err("`begin` without `end`".to_string());
+        }
+        if !self.loops.is_empty() {
+            return Err("`loop` without `again`".to_string());
+        }
+
+        for fixup in std::mem::take(&mut self.fixups) {
+            self.line = fixup.line;
+            let address = match self.labels.get(&fixup.name) {
+                Some(&address) => address as i64,
+                None => match self.constants.get(&fixup.name) {
+                    Some(&value) => value,
+                    None if fixup.name == "main" => return Err("the program has no `: main` label".to_string()),
+                    None => return Err(format!("undefined name `{}`", fixup.name)),
+                },
+            };
+            if fixup.long {
+                self.patch_word(fixup.offset, check(address, 0, 0xFFFF, &fixup.name)? as u16);
+            } else {
+                let address = check(address, 0, 0xFFF, &fixup.name)? as u16;
+                let opcode = (self.image[fixup.offset] as u16) << 8 | self.image[fixup.offset + 1] as u16;
+                self.patch_word(fixup.offset, opcode & 0xF000 | address);
+            }
+        }
+        if self.image.len() > MAX_ROM_SIZE {
+            return Err(format!("program is {} bytes, the limit is {}", self.image.len(), MAX_ROM_SIZE));
+        }
+        Ok(())
+    }
+
+    fn statement(&mut self) -> Result<(), String> {
+        let token = self.next()?;
+        match token.as_str() {
+            ":" => {
+                let name = self.name()?;
+                if self.labels.contains_key(&name) || self.constants.contains_key(&name) {
+                    return Err(format!("`{}` is already defined", name));
+                }
+                self.labels.insert(name, self.address() as u16);
+            }
+            ":const" => {
+                let name = self.name()?;
+                let value = self.value()?;
+                self.constants.insert(name, value);
+            }
+            ":alias" => {
+                let name = self.name()?;
+                let register = self.register()?;
+                self.aliases.insert(name, register);
+            }
+            ":calc" => {
+                let name = self.name()?;
+                let value = self.calc()?;
+                self.constants.insert(name, value);
+            }
+            ":macro" => self.define_macro()?,
+            ":byte" => {
+                let value = if self.peek() == Some("{") { self.calc()? } else { self.value()? };
+                self.emit_byte(check(value, -0x80, 0xFF, ":byte")? as u8);
+            }
+            ":org" => {
+                let address = self.value()?;
+                let address = check(address, PROGRAM_START as i64, (PROGRAM_START + MAX_ROM_SIZE) as i64, ":org")?;
+                self.here = address as usize - PROGRAM_START;
+            }
+            ":call" => self.emit_address(Instruction::Call(0))?,
+            "return" | ";" => self.emit(Instruction::Ret),
+            "clear" => self.emit(Instruction::Cls),
+            "exit" => self.emit(Instruction::Exit),
+            "lores" => self.emit(Instruction::Low),
+            "hires" => self.emit(Instruction::High),
+            "scroll-left" => self.emit(Instruction::Scl),
+            "scroll-right" => self.emit(Instruction::Scr),
+            "audio" => self.emit(Instruction::Audio),
+            "scroll-down" => {
+                let n = self.nibble()?;
+                self.emit(Instruction::Scd(n));
+            }
+            "plane" => {
+                let n = self.nibble()?;
+                self.emit(Instruction::Plane(n));
+            }
+            "jump" => self.emit_address(Instruction::Jp(0))?,
+            "jump0" => self.emit_address(Instruction::JpV0(0))?,
+            "native" => self.emit_address(Instruction::Sys(0))?,
+            "bcd" => {
+                let x = self.register()?;
+                self.emit(Instruction::LdBVx(x));
+            }
+            "saveflags" => {
+                let x = self.register()?;
+                self.emit(Instruction::LdRVx(x));
+            }
+            "loadflags" => {
+                let x = self.register()?;
+                self.emit(Instruction::LdVxR(x));
+            }
+            "save" | "load" => {
+                let x = self.register()?;
+                let range = if self.peek() == Some("-") {
+                    self.next()?;
+                    Some(self.register()?)
+                } else {
+                    None
+                };
+                self.emit(match (token.as_str(), range) {
+                    ("save", Some(y)) => Instruction::Save { x, y },
+                    ("load", Some(y)) => Instruction::Load { x, y },
+                    ("save", None) => Instruction::LdMemVx(x),
+                    _ => Instruction::LdVxMem(x),
+                });
+            }
+            "sprite" => {
+                let x = self.register()?;
+                let y = self.register()?;
+                let n = self.nibble()?;
+                self.emit(Instruction::Drw { x, y, n });
+            }
+            "delay" | "buzzer" | "pitch" => {
+                self.expect(":=")?;
+                let x = self.register()?;
+                self.emit(match token.as_str() {
+                    "delay" => Instruction::LdDtVx(x),
+                    "buzzer" => Instruction::LdStVx(x),
+                    _ => Instruction::Pitch(x),
+                });
+            }
+            "i" => self.index_assignment()?,
+            "if" => self.conditional()?,
+            "else" => {
+                let jump = self.branches.pop().ok_or("`else` without `begin`")?;
+                self.emit(Instruction::Jp(0));
+                self.branches.push(self.here - 2);
+                self.patch_jump(jump);
+            }
+            "end" => {
+                let jump = self.branches.pop().ok_or("`end` without `begin`")?;
+                self.patch_jump(jump);
+            }
+            "loop" => self.loops.push((self.address(), Vec::new())),
+            "while" => {
+                if self.loops.is_empty() {
+                    return Err("`while` outside of a loop".to_string());
+                }
+                let condition = self.condition()?;
+                self.emit_all(&condition.setup);
+                self.emit(condition.when_true);
+                self.emit(Instruction::Jp(0));
+                let jump = self.here - 2;
+                self.loops.last_mut().unwrap().1.push(jump);
+            }
+            "again" => {
+                let (start, breaks) = self.loops.pop().ok_or("`again` without `loop`")?;
+                self.emit(Instruction::Jp(start as u16));
+                for jump in breaks {
+                    self.patch_jump(jump);
+                }
+            }
+            _ if self.is_register(&token) => {
+                self.position -= 1;
+                self.register_assignment()?;
+            }
+            _ if self.macros.contains_key(&token) => self.expand_macro(&token)?,
+            _ if number(&token).is_some() || self.constants.contains_key(&token) => {
+                self.position -= 1;
+                let value = self.value()?;
+                self.emit_byte(check(value, -0x80, 0xFF, &token)? as u8);
+            }
+            _ if token.starts_with(':') || !is_name(&token) => return Err(format!("unexpected `{}`", token)),
+            // A bare label name calls it
+            _ => {
+                self.position -= 1;
+                self.emit_address(Instruction::Call(0))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// vx := vy, vx += 5, vx := random 0xFF, ...
+    fn register_assignment(&mut self) -> Result<(), String> {
+        let x = self.register()?;
+        let operator = self.next()?;
+        let source = self.peek().unwrap_or("").to_string();
+        let y = if self.is_register(&source) { Some(self.register()?) } else { None };
+
+        let instruction = match (operator.as_str(), y) {
+            (":=", Some(y)) => Instruction::LdVxVy { x, y },
+            (":=", None) => match source.as_str() {
+                "random" => {
+                    self.next()?;
+                    Instruction::Rnd { x, kk: self.byte()? }
+                }
+                "key" => {
+                    self.next()?;
+                    Instruction::LdVxK(x)
+                }
+                "delay" => {
+                    self.next()?;
+                    Instruction::LdVxDt(x)
+                }
+                _ => Instruction::LdVxByte { x, kk: self.byte()? },
+            },
+            ("+=", Some(y)) => Instruction::AddVxVy { x, y },
+            ("+=", None) => Instruction::AddVxByte { x, kk: self.byte()? },
+            ("-=", Some(y)) => Instruction::Sub { x, y },
+            ("-=", None) => Instruction::AddVxByte { x, kk: self.byte()?.wrapping_neg() },
+            ("=-", Some(y)) => Instruction::Subn { x, y },
+            ("|=", Some(y)) => Instruction::Or { x, y },
+            ("&=", Some(y)) => Instruction::And { x, y },
+            ("^=", Some(y)) => Instruction::Xor { x, y },
+            (">>=", Some(y)) => Instruction::Shr { x, y },
+            ("<<=", Some(y)) => Instruction::Shl { x, y },
+            _ => return Err(format!("can't use `{}` with `{}`", operator, source)),
+        };
+        self.emit(instruction);
+        Ok(())
+    }
+
+    /// i := address, i := long address, i := hex vx, i += vx, ...
+    fn index_assignment(&mut self) -> Result<(), String> {
+        let operator = self.next()?;
+        match operator.as_str() {
+            "+=" => {
+                let x = self.register()?;
+                self.emit(Instruction::AddIVx(x));
+            }
+            ":=" => match self.peek() {
+                Some("hex") | Some("bighex") => {
+                    let big = self.next()? == "bighex";
+                    let x = self.register()?;
+                    self.emit(if big { Instruction::LdHfVx(x) } else { Instruction::LdFVx(x) });
+                }
+                Some("long") => {
+                    self.next()?;
+                    self.emit(Instruction::LdILong);
+                    let name = self.next()?;
+                    match self.known(&name)? {
+                        Some(address) => self.emit_word(check(address, 0, 0xFFFF, &name)? as u16),
+                        None => {
+                            self.fixups.push(Fixup { offset: self.here, name, line: self.line, long: true });
+                            self.emit_word(0);
+                        }
+                    }
+                }
+                _ => self.emit_address(Instruction::LdI(0))?,
+            },
+            _ => return Err(format!("can't use `{}` with `i`", operator)),
+        }
+        Ok(())
+    }
+
+    /// if ... then statement, if ... begin ... else ... end
+    fn conditional(&mut self) -> Result<(), String> {
+        let condition = self.condition()?;
+        self.emit_all(&condition.setup);
+        match self.next()?.as_str() {
+            "then" => {
+                self.emit(condition.when_false);
+                if self.position >= self.tokens.len() {
+                    return Err("`then` needs a statement".to_string());
+                }
+                self.statement()
+            }
+            "begin" => {
+                self.emit(condition.when_true);
+                self.emit(Instruction::Jp(0));
+                self.branches.push(self.here - 2);
+                Ok(())
+            }
+            other => Err(format!("expected `then` or `begin`, found `{}`", other)),
+        }
+    }
+
+    fn condition(&mut self) -> Result<Condition, String> {
+        let x = self.register()?;
+        let operator = self.next()?;
+        let simple = |when_false, when_true| Condition { setup: Vec::new(), when_false, when_true };
+
+        let condition = match operator.as_str() {
+            "key" => simple(Instruction::Sknp(x), Instruction::Skp(x)),
+            "-key" => simple(Instruction::Skp(x), Instruction::Sknp(x)),
+            "==" | "!=" => {
+                let rhs = self.peek().unwrap_or("").to_string();
+                let (equal, not_equal) = if self.is_register(&rhs) {
+                    let y = self.register()?;
+                    (Instruction::SeVxVy { x, y }, Instruction::SneVxVy { x, y })
+                } else {
+                    let kk = self.byte()?;
+                    (Instruction::SeVxByte { x, kk }, Instruction::SneVxByte { x, kk })
+                };
+                if operator == "==" { simple(not_equal, equal) } else { simple(equal, not_equal) }
+            }
+            "<" | ">" | "<=" | ">=" => {
+                // VF ends up 1 when `lhs >= rhs`, with x and the operand swapped for > and <=
+                let rhs = self.peek().unwrap_or("").to_string();
+                let y = if self.is_register(&rhs) { Some(self.register()?) } else { None };
+                let kk = if y.is_none() { Some(self.byte()?) } else { None };
+                let swapped = operator == ">" || operator == "<=";
+                let setup = match (y, kk, swapped) {
+                    (Some(y), _, false) => vec![Instruction::LdVxVy { x: 0xF, y: x }, Instruction::Sub { x: 0xF, y }],
+                    (Some(y), _, true) => vec![Instruction::LdVxVy { x: 0xF, y }, Instruction::Sub { x: 0xF, y: x }],
+                    (None, Some(kk), false) => vec![Instruction::LdVxByte { x: 0xF, kk }, Instruction::Subn { x: 0xF, y: x }],
+                    (None, Some(kk), true) => vec![Instruction::LdVxByte { x: 0xF, kk }, Instruction::Sub { x: 0xF, y: x }],
+                    _ => unreachable!(),
+                };
+                let flag_set = Instruction::SneVxByte { x: 0xF, kk: 0 };
+                let flag_clear = Instruction::SeVxByte { x: 0xF, kk: 0 };
+                // < and > hold when VF is 0, <= and >= when it is 1
+                if operator == "<" || operator == ">" {
+                    Condition { setup, when_false: flag_set, when_true: flag_clear }
+                } else {
+                    Condition { setup, when_false: flag_clear, when_true: flag_set }
+                }
+            }
+            _ => return Err(format!("unknown comparison `{}`", operator)),
+        };
+        Ok(condition)
+    }
+
+    /// :macro name parameters { body }
+    fn define_macro(&mut self) -> Result<(), String> {
+        let name = self.name()?;
+        let mut parameters = Vec::new();
+        loop {
+            let token = self.next()?;
+            if token == "{" {
+                break;
+            }
+            parameters.push(token);
+        }
+        let mut body = Vec::new();
+        let mut depth = 1;
+        loop {
+            let token = self.tokens.get(self.position).cloned().ok_or("`:macro` without closing `}`")?;
+            self.position += 1;
+            match token.text.as_str() {
+                "{" => depth += 1,
+                "}" => depth -= 1,
+                _ => {}
+            }
+            if depth == 0 {
+                break;
+            }
+            body.push(token);
+        }
+        self.macros.insert(name, Macro { parameters, body });
+        Ok(())
+    }
+
+    /// Replace a macro call with the macro body
+    fn expand_macro(&mut self, name: &str) -> Result<(), String> {
+        self.expansions += 1;
+        if self.expansions > MAX_EXPANSIONS {
+            return Err(format!("too many expansions of `{}`", name));
+        }
+        let count = self.macros[name].parameters.len();
+        let mut arguments = Vec::new();
+        for _ in 0..count {
+            arguments.push(self.next()?);
+        }
+        let line = self.line;
+        let definition = &self.macros[name];
+        let body: Vec<Token> = definition.body.iter().map(|token| {
+            let text = match definition.parameters.iter().position(|parameter| *parameter == token.text) {
+                Some(index) => arguments[index].clone(),
+                None => token.text.clone(),
+            };
+            // Errors inside the expansion point at the call
+            Token { text, line }
+        }).collect();
+        let position = self.position;
+        self.tokens.splice(position..position, body);
+        Ok(())
+    }
+
+    /// { expression }, evaluated right to left without precedence like Octo does
+    fn calc(&mut self) -> Result<i64, String> {
+        self.expect("{")?;
+        let value = self.expression()?;
+        self.expect("}")?;
+        Ok(value)
+    }
+
+    fn expression(&mut self) -> Result<i64, String> {
+        let lhs = self.term()?;
+        let operator = match self.peek() {
+            Some(operator) if ["+", "-", "*", "/", "%", "&", "|", "^", "<<", ">>"].contains(&operator) => operator.to_string(),
+            _ => return Ok(lhs),
+        };
+        self.next()?;
+        let rhs = self.expression()?;
+        Ok(match operator.as_str() {
+            "+" => lhs.wrapping_add(rhs),
+            "-" => lhs.wrapping_sub(rhs),
+            "*" => lhs.wrapping_mul(rhs),
+            "/" | "%" if rhs == 0 => return Err("division by zero".to_string()),
+            "/" => lhs / rhs,
+            "%" => lhs % rhs,
+            "&" => lhs & rhs,
+            "|" => lhs | rhs,
+            "^" => lhs ^ rhs,
+            "<<" => lhs.checked_shl(rhs as u32).unwrap_or(0),
+            _ => lhs.checked_shr(rhs as u32).unwrap_or(0),
+        })
+    }
+
+    fn term(&mut self) -> Result<i64, String> {
+        let token = self.next()?;
+        match token.as_str() {
+            "(" => {
+                let value = self.expression()?;
+                self.expect(")")?;
+                Ok(value)
+            }
+            "-" => Ok(-self.term()?),
+            "~" => Ok(!self.term()?),
+            "!" => Ok((self.term()? == 0) as i64),
+            "HERE" => Ok(self.address() as i64),
+            _ => self.known(&token)?.ok_or_else(|| format!("undefined name `{}`", token)),
+        }
+    }
+
+    /// Number, constant or label that must already be defined
+    fn value(&mut self) -> Result<i64, String> {
+        let token = self.next()?;
+        self.known(&token)?.ok_or_else(|| format!("undefined name `{}`", token))
+    }
+
+    /// Value of a number, constant or label, None for names not defined yet
+    fn known(&self, token: &str) -> Result<Option<i64>, String> {
+        if let Some(value) = number(token) {
+            return Ok(Some(value));
+        }
+        if let Some(&value) = self.constants.get(token) {
+            return Ok(Some(value));
+        }
+        if let Some(&address) = self.labels.get(token) {
+            return Ok(Some(address as i64));
+        }
+        if is_name(token) {
+            Ok(None)
+        } else {
+            Err(format!("expected a value, found `{}`", token))
+        }
+    }
+
+    fn byte(&mut self) -> Result<u8, String> {
+        let value = self.value()?;
+        Ok(check(value, -0x80, 0xFF, "byte")? as u8)
+    }
+
+    fn nibble(&mut self) -> Result<u8, String> {
+        let value = self.value()?;
+        Ok(check(value, 0, 0xF, "nibble")? as u8)
+    }
+
+    fn is_register(&self, token: &str) -> bool {
+        register(token).is_some() || self.aliases.contains_key(token)
+    }
+
+    fn register(&mut self) -> Result<u8, String> {
+        let token = self.next()?;
+        register(&token)
+            .or_else(|| self.aliases.get(&token).cloned())
+            .ok_or_else(|| format!("expected a register, found `{}`", token))
+    }
+
+    fn name(&mut self) -> Result<String, String> {
+        let token = self.next()?;
+        if !is_name(&token) || register(&token).is_some() {
+            return Err(format!("invalid name `{}`", token));
+        }
+        Ok(token)
+    }
+
+    fn expect(&mut self, expected: &str) -> Result<(), String> {
+        let token = self.next()?;
+        if token != expected {
+            return Err(format!("expected `{}`, found `{}`", expected, token));
+        }
+        Ok(())
+    }
+
+    fn next(&mut self) -> Result<String, String> {
+        let token = self.tokens.get(self.position).ok_or("unexpected end of file")?;
+        self.line = token.line;
+        self.position += 1;
+        Ok(token.text.clone())
+    }
+
+    fn peek(&self) -> Option<&str> {
+        self.tokens.get(self.position).map(|token| token.text.as_str())
+    }
+
+    fn address(&self) -> usize {
+        PROGRAM_START + self.here
+    }
+
+    /// Instruction with a 12 bit address read from the next token
+    fn emit_address(&mut self, instruction: Instruction) -> Result<(), String> {
+        let name = self.next()?;
+        match self.known(&name)? {
+            Some(address) => {
+                let address = check(address, 0, 0xFFF, &name)? as u16;
+                self.emit_word(instruction.encode() | address);
+            }
+            None => {
+                self.fixups.push(Fixup { offset: self.here, name, line: self.line, long: false });
+                self.emit(instruction);
+            }
+        }
+        Ok(())
+    }
+
+    /// Point the jump at offset to the current address
+    fn patch_jump(&mut self, offset: usize) {
+        let address = self.address() as u16 & 0x0FFF;
+        self.patch_word(offset, 0x1000 | address);
+    }
+
+    fn emit_all(&mut self, instructions: &[Instruction]) {
+        for &instruction in instructions.iter() {
+            self.emit(instruction);
+        }
+    }
+
+    fn emit(&mut self, instruction: Instruction) {
+        self.emit_word(instruction.encode());
+    }
+
+    fn emit_word(&mut self, word: u16) {
+        self.emit_byte((word >> 8) as u8);
+        self.emit_byte(word as u8);
+    }
+
+    fn emit_byte(&mut self, byte: u8) {
+        if self.image.len() <= self.here {
+            self.image.resize(self.here + 1, 0);
+        }
+        self.image[self.here] = byte;
+        self.here += 1;
+    }
+
+    fn patch_word(&mut self, offset: usize, word: u16) {
+        self.image[offset] = (word >> 8) as u8;
+        self.image[offset + 1] = word as u8;
+    }
+}
+
+/// Whitespace separated tokens, `#` starts a comment
+fn tokenize(source: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    for (index, line) in source.lines().enumerate() {
+        let code = line.split('#').next().unwrap_or("");
+        for text in code.split_whitespace() {
+            tokens.push(Token { text: text.to_string(), line: index + 1 });
+        }
+    }
+    tokens
+}
+
+/// Decimal, 0x hex or 0b binary number, optionally negative
+fn number(token: &str) -> Option<i64> {
+    let (negative, digits) = match token.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, token),
+    };
+    let value = if let Some(hex) = digits.strip_prefix("0x").or_else(|| digits.strip_prefix("0X")) {
+        i64::from_str_radix(hex, 16).ok()?
+    } else if let Some(binary) = digits.strip_prefix("0b").or_else(|| digits.strip_prefix("0B")) {
+        i64::from_str_radix(binary, 2).ok()?
+    } else if digits.starts_with(|c: char| c.is_ascii_digit()) {
+        digits.parse().ok()?
+    } else {
+        return None;
+    };
+    Some(if negative { -value } else { value })
+}
+
+/// Register index for v0-vf
+fn register(token: &str) -> Option<u8> {
+    let mut chars = token.chars();
+    match (chars.next(), chars.next(), chars.next()) {
+        (Some('v'), Some(digit), None) | (Some('V'), Some(digit), None) => digit.to_digit(16).map(|x| x as u8),
+        _ => None,
+    }
+}
+
+fn is_name(token: &str) -> bool {
+    token.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && token.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+fn check(value: i64, min: i64, max: i64, what: &str) -> Result<i64, String> {
+    if value < min || value > max {
+        return Err(format!("{} value {:#X} is out of range", what, value));
+    }
+    Ok(value)
+}
+
+#[cfg(test)]
+#[path = "./octo_tests.rs"]
+mod octo_tests;
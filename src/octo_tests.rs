@@ -0,0 +1,162 @@
+use super::*;
+use crate::cpu::Cpu;
+use crate::quirks::Quirks;
+
+fn image(source: &str) -> Vec<u8> {
+    compile(source).unwrap().image
+}
+
+#[test]
+fn test_main_jump() {
+    assert_eq!(image(": main clear"), vec![0x12, 0x02, 0x00, 0xe0]);
+    assert_eq!(image(": draw ; : main draw"), vec![0x12, 0x04, 0x00, 0xee, 0x22, 0x02]);
+    assert_eq!(compile("clear").err().unwrap().message, "the program has no `: main` label");
+}
+
+#[test]
+fn test_statements() {
+    let source = "
+        : main
+            v1 := 0x05      # comment
+            va := vb
+            v2 += 3
+            v2 -= 1
+            v3 =- v4
+            v5 >>= v5
+            v1 := random 0x0F
+            v0 := key
+            delay := v0
+            i := hex v1
+            i += v2
+            sprite v1 v2 5
+            bcd v3
+            save v3
+            load v1 - v4
+    ";
+    assert_eq!(image(source), vec![
+        0x12, 0x02, 0x61, 0x05, 0x8a, 0xb0, 0x72, 0x03, 0x72, 0xff, 0x83, 0x47,
+        0x85, 0x56, 0xc1, 0x0f, 0xf0, 0x0a, 0xf0, 0x15, 0xf1, 0x29, 0xf2, 0x1e,
+        0xd1, 0x25, 0xf3, 0x33, 0xf3, 0x55, 0x51, 0x43,
+    ]);
+}
+
+#[test]
+fn test_labels_and_data() {
+    let source = "
+        : main
+            i := sprite
+            i := long sprite
+            jump main
+        : sprite
+            0xF0 0b10010000 240
+    ";
+    assert_eq!(image(source), vec![
+        0x12, 0x02, 0xa2, 0x0a, 0xf0, 0x00, 0x02, 0x0a, 0x12, 0x02, 0xf0, 0x90, 0xf0,
+    ]);
+    assert_eq!(compile(source).unwrap().labels["sprite"], 0x20a);
+}
+
+#[test]
+fn test_const_alias_calc() {
+    let source = "
+        :const SPEED 3
+        :alias x v4
+        :calc FAST { SPEED * 2 + 1 }    # right to left, 3 * ( 2 + 1 )
+        : main
+            x := FAST
+            x += SPEED
+            :byte { 1 << 4 }
+    ";
+    assert_eq!(image(source), vec![0x12, 0x02, 0x64, 0x09, 0x74, 0x03, 0x10]);
+}
+
+#[test]
+fn test_conditionals() {
+    let source = "
+        : main
+            if v1 == 5 then v2 := 1
+            if v1 != v2 then clear
+            if v3 key then return
+            if v1 > 3 begin
+                v0 := 1
+            else
+                v0 := 2
+            end
+    ";
+    assert_eq!(image(source), vec![
+        0x12, 0x02, 0x41, 0x05, 0x62, 0x01, 0x51, 0x20, 0x00, 0xe0, 0xe3, 0xa1, 0x00, 0xee,
+        // vf := 3, vf -= v1, if vf == 0 skip the jump to else
+        0x6f, 0x03, 0x8f, 0x15, 0x3f, 0x00, 0x12, 0x1a, 0x60, 0x01, 0x12, 0x1c, 0x60, 0x02,
+    ]);
+}
+
+#[test]
+fn test_loop_and_macro() {
+    let source = "
+        :macro twice reg { reg += 2 }
+        : main
+            loop
+                twice v0
+                while v0 != 10
+            again
+    ";
+    assert_eq!(image(source), vec![
+        0x12, 0x02, 0x70, 0x02, 0x40, 0x0a, 0x12, 0x0a, 0x12, 0x02,
+    ]);
+}
+
+#[test]
+fn test_compile_errors() {
+    assert_eq!(compile(": main\nv0 := missing").err().unwrap().line, 2);
+    assert!(compile(": main jump nowhere").is_err());
+    assert!(compile(": main if v0 == 1 begin").is_err());
+    assert!(compile(": main again").is_err());
+    assert!(compile(":macro m { m } : main m").is_err());
+    assert!(compile(": main v0 := 256").is_err());
+}
+
+#[test]
+fn test_run_compiled_program() {
+    let source = "
+        : main
+            v0 := 0
+            v1 := 10
+            loop
+                v0 += 3
+                v1 -= 1
+                if v1 == 0 then exit
+            again
+    ";
+    let mut cpu = Cpu::new(Quirks::default());
+    cpu.read_data_to_memory(&image(source));
+    let state = cpu.run_frame([false; 16], 100).unwrap();
+    assert!(state.halted);
+}
+
+#[test]
+fn test_run_comparisons() {
+    // Outcome of `v1 <operator> 3` for v1 = 1, 3 and 5
+    let operators = [
+        ("==", [false, true, false]),
+        ("!=", [true, false, true]),
+        ("<", [true, false, false]),
+        (">", [false, false, true]),
+        ("<=", [true, true, false]),
+        (">=", [false, true, true]),
+    ];
+    for &(operator, expected) in operators.iter() {
+        for (&value, &expected) in [1, 3, 5].iter().zip(expected.iter()) {
+            // Against a constant and against a register
+            for rhs in ["3", "v2"].iter() {
+                let source = format!(
+                    ": main v1 := {} v2 := 3 v0 := 0 if v1 {} {} then v0 := 1 exit",
+                    value, operator, rhs
+                );
+                let mut cpu = Cpu::new(Quirks::default());
+                cpu.read_data_to_memory(&image(&source));
+                assert!(cpu.run_frame([false; 16], 100).unwrap().halted);
+                assert_eq!(cpu.registers()[0] == 1, expected, "{}", source);
+            }
+        }
+    }
+}
@@ -0,0 +1,214 @@
+use r_chip8::headless::StopCondition;
+use r_chip8::tty::{TtyInput, TtyMode};
+use r_chip8::{Quirks, UnknownOpcodePolicy};
+
+pub const USAGE: &str = "Usage: r_chip8 <path_to_rom_file> [options]
+
+Roms ending in .8o are compiled as Octo source.
+
+Options:
+    --quirks <profile>  Quirk profile: chip8 (default), vip, chip48, schip or xochip
+    --ipf <number>      Instructions executed per 60 Hz frame (default 10)
+    --unknown-opcodes <ignore|log|halt>
+                        What to do with unimplemented opcodes (default ignore)
+    --strict            Same as --unknown-opcodes halt
+    --seed <number>     Seed for random numbers, runs with the same seed repeat exactly
+    --record <file>     Record the input of every frame to a movie file
+    --play <file>       Replay a movie recorded with --record, then continue live
+    --headless          Run without a window or audio, then print the screen and registers
+    --frames <number>   Frames to run headless (default 600)
+    --until <halt|loop> Stop headless runs early when the rom halts or jumps to itself,
+                        can be given twice
+    --input <file>      Keys for headless runs, lines of `<frame> <hex keys>` or `<frame> -`
+    --screenshot <file> Write the final headless screen as a PPM image
+    --tty <blocks|braille>
+                        Draw in the terminal instead of a window, with half blocks
+                        in color or Braille dots
+    --key-timeout <ms>  Terminals don't report key releases, a key is released this
+                        long after it was last sent (default 250)
+    --debug             Start paused with a debugger prompt on the terminal
+    --gdb <port>        Start paused and wait for a GDB client on localhost:port
+    --rewind-memory <MB>
+                        Memory kept for rewinding, 0 disables it (default 16)
+    --rewind-frames <number>
+                        Most frames that can be rewound (default 3600)
+
+Keys while running:
+    -/+                 Run fewer/more instructions per frame
+    F5/F9               Save/load state in the current slot (<rom>.state<slot>)
+    F6/F7               Previous/next save state slot (0-9)
+    Backspace           Hold to rewind
+    Esc                 Quit";
+
+pub const DEFAULT_INSTRUCTIONS_PER_FRAME: usize = 10;
+pub const MAX_INSTRUCTIONS_PER_FRAME: usize = 10_000;
+pub const DEFAULT_HEADLESS_FRAMES: usize = 600;
+pub const DEFAULT_REWIND_MEMORY: usize = 16 * 1024 * 1024;
+pub const DEFAULT_REWIND_FRAMES: usize = 60 * 60;
+
+/// Command line options
+pub struct Options {
+    pub rom_filename: String,
+    pub quirks: Quirks,
+    pub instructions_per_frame: usize,
+    pub unknown_opcode_policy: UnknownOpcodePolicy,
+    pub seed: Option<u64>,
+    pub record_filename: Option<String>,
+    pub play_filename: Option<String>,
+    pub headless: bool,
+    pub frames: usize,
+    pub stop_conditions: Vec<StopCondition>,
+    pub input_filename: Option<String>,
+    pub screenshot_filename: Option<String>,
+    pub tty: Option<TtyMode>,
+    pub key_release_ms: u64,
+    pub debug: bool,
+    pub gdb_port: Option<u16>,
+    /// Bytes, 0 when rewinding is off
+    pub rewind_memory: usize,
+    pub rewind_frames: usize,
+}
+
+impl Options {
+    pub fn parse(args: &[String]) -> Result<Self, String> {
+        let mut rom_filename = None;
+        let mut quirks = Quirks::default();
+        let mut instructions_per_frame = DEFAULT_INSTRUCTIONS_PER_FRAME;
+        let mut unknown_opcode_policy = UnknownOpcodePolicy::default();
+        let mut seed = None;
+        let mut record_filename = None;
+        let mut play_filename = None;
+        let mut headless = false;
+        let mut frames = None;
+        let mut stop_conditions = Vec::new();
+        let mut input_filename = None;
+        let mut screenshot_filename = None;
+        let mut tty = None;
+        let mut key_release_ms = TtyInput::DEFAULT_RELEASE_TIMEOUT.as_millis() as u64;
+        let mut debug = false;
+        let mut gdb_port = None;
+        let mut rewind_memory = DEFAULT_REWIND_MEMORY;
+        let mut rewind_frames = DEFAULT_REWIND_FRAMES;
+
+        let mut args = args.iter().skip(1);
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--quirks" => {
+                    let name = args.next().ok_or("--quirks needs a profile name")?;
+                    quirks = Quirks::from_name(name)
+                        .ok_or_else(|| format!("Unknown quirk profile: {}", name))?;
+                }
+                "--ipf" => {
+                    let value = args.next().ok_or("--ipf needs a number")?;
+                    instructions_per_frame = match value.parse() {
+                        Ok(ipf) if ipf > 0 && ipf <= MAX_INSTRUCTIONS_PER_FRAME => ipf,
+                        _ => return Err(format!("Invalid instructions per frame: {}", value)),
+                    };
+                }
+                "--unknown-opcodes" => {
+                    let name = args.next().ok_or("--unknown-opcodes needs ignore, log or halt")?;
+                    unknown_opcode_policy = UnknownOpcodePolicy::from_name(name)
+                        .ok_or_else(|| format!("Unknown opcode policy: {}", name))?;
+                }
+                "--strict" => unknown_opcode_policy = UnknownOpcodePolicy::Halt,
+                "--seed" => {
+                    let value = args.next().ok_or("--seed needs a number")?;
+                    seed = Some(value.parse().map_err(|_| format!("Invalid seed: {}", value))?);
+                }
+                "--record" => {
+                    record_filename = Some(args.next().ok_or("--record needs a file name")?.clone());
+                }
+                "--play" => {
+                    play_filename = Some(args.next().ok_or("--play needs a file name")?.clone());
+                }
+                "--headless" => headless = true,
+                "--frames" => {
+                    let value = args.next().ok_or("--frames needs a number")?;
+                    frames = Some(value.parse().map_err(|_| format!("Invalid frame count: {}", value))?);
+                }
+                "--until" => {
+                    let name = args.next().ok_or("--until needs halt or loop")?;
+                    stop_conditions.push(StopCondition::from_name(name)
+                        .ok_or_else(|| format!("Unknown stop condition: {}", name))?);
+                }
+                "--input" => {
+                    input_filename = Some(args.next().ok_or("--input needs a file name")?.clone());
+                }
+                "--screenshot" => {
+                    screenshot_filename = Some(args.next().ok_or("--screenshot needs a file name")?.clone());
+                }
+                "--tty" => {
+                    let name = args.next().ok_or("--tty needs blocks or braille")?;
+                    tty = Some(TtyMode::from_name(name).ok_or_else(|| format!("Unknown terminal mode: {}", name))?);
+                }
+                "--key-timeout" => {
+                    let value = args.next().ok_or("--key-timeout needs milliseconds")?;
+                    key_release_ms = value.parse().map_err(|_| format!("Invalid key timeout: {}", value))?;
+                }
+                "--debug" => debug = true,
+                "--gdb" => {
+                    let value = args.next().ok_or("--gdb needs a port number")?;
+                    gdb_port = Some(value.parse().map_err(|_| format!("Invalid port: {}", value))?);
+                }
+                "--rewind-memory" => {
+                    let value = args.next().ok_or("--rewind-memory needs a size in MB")?;
+                    rewind_memory = match value.parse::<usize>() {
+                        Ok(megabytes) if megabytes <= 4096 => megabytes * 1024 * 1024,
+                        _ => return Err(format!("Invalid rewind memory: {}", value)),
+                    };
+                }
+                "--rewind-frames" => {
+                    let value = args.next().ok_or("--rewind-frames needs a number")?;
+                    rewind_frames = value.parse().map_err(|_| format!("Invalid rewind frames: {}", value))?;
+                }
+                _ if arg.starts_with("--") => return Err(format!("Unknown option: {}", arg)),
+                _ if rom_filename.is_none() => rom_filename = Some(arg.clone()),
+                _ => return Err(format!("Unexpected argument: {}", arg)),
+            }
+        }
+
+        if debug && gdb_port.is_some() {
+            return Err("--debug and --gdb can't be used together".to_string());
+        }
+        if record_filename.is_some() && play_filename.is_some() {
+            return Err("--record and --play can't be used together".to_string());
+        }
+        if (record_filename.is_some() || play_filename.is_some()) && (debug || gdb_port.is_some()) {
+            return Err("Movies can't be recorded or played under a debugger".to_string());
+        }
+
+        let headless_only = frames.is_some() || !stop_conditions.is_empty()
+            || input_filename.is_some() || screenshot_filename.is_some();
+        if headless_only && !headless {
+            return Err("--frames, --until, --input and --screenshot need --headless".to_string());
+        }
+        if headless && (debug || gdb_port.is_some() || record_filename.is_some() || play_filename.is_some()) {
+            return Err("--headless can't be used with debuggers or movies".to_string());
+        }
+
+        if tty.is_some() && (debug || headless) {
+            return Err("--tty can't be used with --debug or --headless".to_string());
+        }
+
+        Ok(Options {
+            rom_filename: rom_filename.ok_or("Missing rom file")?,
+            quirks,
+            instructions_per_frame,
+            unknown_opcode_policy,
+            seed,
+            record_filename,
+            play_filename,
+            headless,
+            frames: frames.unwrap_or(DEFAULT_HEADLESS_FRAMES),
+            stop_conditions,
+            input_filename,
+            screenshot_filename,
+            tty,
+            key_release_ms,
+            debug,
+            gdb_port,
+            rewind_memory,
+            rewind_frames,
+        })
+    }
+}
@@ -0,0 +1,104 @@
+/// Quirks
+/// Some CHIP-8 instructions behave differently depending on which interpreter
+/// a ROM was written for. Each flag picks one interpretation, the presets
+/// below collect the flags for the common platforms.
+/// Reference: https://github.com/chip-8/chip-8-database/blob/master/database/quirks.json
+///
+/// `Quirks::default()` keeps the behaviour rChip8 originally implemented.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Quirks {
+    /// 8xy6/8xyE copy Vy into Vx before shifting
+    pub shift_uses_vy: bool,
+    /// How far Fx55/Fx65 move I
+    pub load_store_increment: IndexIncrement,
+    /// Bnnn jumps to nnn + Vx, where x is the highest nibble of nnn
+    pub jump_uses_vx: bool,
+    /// 8xy1/8xy2/8xy3 set VF to 0
+    pub logic_resets_vf: bool,
+    /// Dxyn clips sprites at the screen edge instead of wrapping them around
+    pub clip_sprites: bool,
+    /// Dxyn waits for the next vertical blank before the cpu continues
+    pub display_wait: bool,
+}
+
+/// What Fx55/Fx65 do to I after copying V0 through Vx
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum IndexIncrement {
+    /// I is left as it was (SUPER-CHIP 1.1)
+    #[default]
+    Unchanged,
+    /// I += x, one short of the last register (CHIP-48)
+    ByX,
+    /// I += x + 1, past the last register (COSMAC VIP, XO-CHIP)
+    ByXPlusOne,
+}
+
+impl IndexIncrement {
+    /// Amount added to I after copying V0 through Vx
+    pub fn amount(self, x: usize) -> usize {
+        match self {
+            IndexIncrement::Unchanged => 0,
+            IndexIncrement::ByX => x,
+            IndexIncrement::ByXPlusOne => x + 1,
+        }
+    }
+}
+
+impl Quirks {
+    /// Original COSMAC VIP interpreter
+    pub fn cosmac_vip() -> Self {
+        Quirks {
+            shift_uses_vy: true,
+            load_store_increment: IndexIncrement::ByXPlusOne,
+            jump_uses_vx: false,
+            logic_resets_vf: true,
+            clip_sprites: true,
+            display_wait: true,
+        }
+    }
+
+    /// CHIP-48 for the HP-48 calculators
+    pub fn chip48() -> Self {
+        Quirks {
+            load_store_increment: IndexIncrement::ByX,
+            ..Quirks::schip()
+        }
+    }
+
+    /// SUPER-CHIP 1.1
+    pub fn schip() -> Self {
+        Quirks {
+            shift_uses_vy: false,
+            load_store_increment: IndexIncrement::Unchanged,
+            jump_uses_vx: true,
+            logic_resets_vf: false,
+            clip_sprites: true,
+            display_wait: false,
+        }
+    }
+
+    /// XO-CHIP as implemented by Octo
+    pub fn xo_chip() -> Self {
+        Quirks {
+            shift_uses_vy: true,
+            load_store_increment: IndexIncrement::ByXPlusOne,
+            jump_uses_vx: false,
+            logic_resets_vf: false,
+            clip_sprites: false,
+            display_wait: false,
+        }
+    }
+
+    /// Look up a preset by name, e.g. from the command line.
+    /// `chip8` is the default profile.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "default" | "chip8" => Some(Quirks::default()),
+            "vip" | "cosmac" | "cosmac-vip" => Some(Quirks::cosmac_vip()),
+            "chip48" | "chip-48" => Some(Quirks::chip48()),
+            "schip" | "superchip" | "super-chip" => Some(Quirks::schip()),
+            "xochip" | "xo-chip" => Some(Quirks::xo_chip()),
+            _ => None,
+        }
+    }
+}
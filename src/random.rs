@@ -0,0 +1,61 @@
+/// Random
+/// Small seedable generator for CXKK (xorshift64*). The whole state is one
+/// u64, so it can go into save states and a seed always gives the same
+/// sequence of random bytes.
+/// Reference: https://en.wikipedia.org/wiki/Xorshift#xorshift*
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Random {
+    state: u64,
+}
+
+impl Random {
+    /// Generator for `seed`. Any seed works, including 0.
+    pub fn new(seed: u64) -> Self {
+        Random { state: splitmix64(seed) }
+    }
+
+    /// Seeded from the operating system
+    pub fn from_entropy() -> Self {
+        Self::new(rand::random())
+    }
+
+    /// Next byte, all 256 values are equally likely
+    pub fn next_u8(&mut self) -> u8 {
+        (self.next_u64() >> 56) as u8
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        self.state.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    /// Internal state, for save states
+    pub fn state(&self) -> u64 {
+        self.state
+    }
+
+    /// Generator continuing from a `state()`. Returns None for 0, which
+    /// xorshift can't leave.
+    pub fn from_state(state: u64) -> Option<Self> {
+        if state == 0 {
+            None
+        } else {
+            Some(Random { state })
+        }
+    }
+}
+
+/// Spreads the seed over all bits and never returns 0 for xorshift
+fn splitmix64(seed: u64) -> u64 {
+    let mut z = seed.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^= z >> 31;
+    if z == 0 { 1 } else { z }
+}
+
+#[cfg(test)]
+#[path = "./random_tests.rs"]
+mod random_tests;
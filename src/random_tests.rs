@@ -0,0 +1,32 @@
+use super::*;
+
+#[test]
+fn test_same_seed_same_sequence() {
+    let mut a = Random::new(1234);
+    let mut b = Random::new(1234);
+    let mut c = Random::new(1235);
+    let first: Vec<u8> = (0..32).map(|_| a.next_u8()).collect();
+    let second: Vec<u8> = (0..32).map(|_| b.next_u8()).collect();
+    let other: Vec<u8> = (0..32).map(|_| c.next_u8()).collect();
+    assert_eq!(first, second);
+    assert_ne!(first, other);
+}
+
+#[test]
+fn test_full_byte_range() {
+    let mut random = Random::new(0);
+    let mut seen = [false; 256];
+    for _ in 0..10_000 {
+        seen[random.next_u8() as usize] = true;
+    }
+    assert!(seen.iter().all(|&seen| seen));
+}
+
+#[test]
+fn test_state() {
+    let mut random = Random::new(0);
+    random.next_u64();
+    let mut copy = Random::from_state(random.state()).unwrap();
+    assert_eq!(copy.next_u64(), random.next_u64());
+    assert_eq!(Random::from_state(0), None);
+}
@@ -0,0 +1,166 @@
+use std::collections::VecDeque;
+use std::convert::TryInto;
+
+use crate::cpu::Cpu;
+
+/// Snapshots are only restored into the machine that took them, so the rom
+/// hash in their header doesn't matter
+const ROM_HASH: u64 = 0;
+
+/// Rewind buffer
+/// Keeps a snapshot of the machine for each frame so it can be stepped
+/// backwards. Every `keyframe_interval` frames a full save state is kept,
+/// the frames in between only store what changed since that keyframe.
+/// The oldest keyframe and its deltas are dropped together once the buffer
+/// goes over its memory budget or holds more than `max_frames` frames.
+pub struct Rewind {
+    groups: VecDeque<Group>,
+    keyframe_interval: usize,
+    memory_budget: usize,
+    max_frames: usize,
+    frames: usize,
+    memory_used: usize,
+}
+
+/// A keyframe and the frames after it, stored as deltas against it
+struct Group {
+    keyframe: Vec<u8>,
+    deltas: Vec<Vec<u8>>,
+}
+
+impl Group {
+    fn size(&self) -> usize {
+        self.keyframe.len() + self.deltas.iter().map(Vec::len).sum::<usize>()
+    }
+}
+
+impl Rewind {
+    pub const DEFAULT_KEYFRAME_INTERVAL: usize = 60;
+
+    /// Buffer using at most `memory_budget` bytes and `max_frames` frames
+    pub fn new(memory_budget: usize, max_frames: usize) -> Self {
+        Rewind {
+            groups: VecDeque::new(),
+            keyframe_interval: Self::DEFAULT_KEYFRAME_INTERVAL,
+            memory_budget,
+            max_frames,
+            frames: 0,
+            memory_used: 0,
+        }
+    }
+
+    /// Frames between full snapshots. Longer intervals save memory but
+    /// drop history in bigger chunks.
+    pub fn set_keyframe_interval(&mut self, frames: usize) {
+        self.keyframe_interval = frames.max(1);
+    }
+
+    /// Frames that can be stepped back
+    pub fn len(&self) -> usize {
+        self.frames
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames == 0
+    }
+
+    /// Bytes used by the stored snapshots
+    pub fn memory_used(&self) -> usize {
+        self.memory_used
+    }
+
+    pub fn clear(&mut self) {
+        self.groups.clear();
+        self.frames = 0;
+        self.memory_used = 0;
+    }
+
+    /// Record the machine as it is now
+    pub fn push(&mut self, cpu: &Cpu) {
+        let state = cpu.save_state(ROM_HASH);
+        match self.groups.back_mut() {
+            Some(group) if group.deltas.len() + 1 < self.keyframe_interval => {
+                let delta = encode_delta(&group.keyframe, &state);
+                self.memory_used += delta.len();
+                group.deltas.push(delta);
+            }
+            _ => {
+                self.memory_used += state.len();
+                self.groups.push_back(Group { keyframe: state, deltas: Vec::new() });
+            }
+        }
+        self.frames += 1;
+
+        // The newest group is kept even if it alone is over the limits
+        while self.groups.len() > 1 && (self.memory_used > self.memory_budget || self.frames > self.max_frames) {
+            if let Some(group) = self.groups.pop_front() {
+                self.memory_used -= group.size();
+                self.frames -= group.deltas.len() + 1;
+            }
+        }
+    }
+
+    /// Restore the most recently recorded frame and forget it.
+    /// Returns false when there is nothing left to rewind.
+    pub fn pop(&mut self, cpu: &mut Cpu) -> bool {
+        let Some(group) = self.groups.back_mut() else { return false };
+        let state = match group.deltas.pop() {
+            Some(delta) => {
+                self.memory_used -= delta.len();
+                decode_delta(&group.keyframe, &delta)
+            }
+            None => {
+                let Some(group) = self.groups.pop_back() else { return false };
+                self.memory_used -= group.keyframe.len();
+                group.keyframe
+            }
+        };
+        self.frames -= 1;
+        cpu.load_state(&state, ROM_HASH).expect("rewind snapshots are taken from the running machine");
+        true
+    }
+}
+
+/// XOR of `state` against `keyframe` as (unchanged count, changed count,
+/// changed bytes) runs. Counts are big endian u16.
+fn encode_delta(keyframe: &[u8], state: &[u8]) -> Vec<u8> {
+    let mut delta = Vec::new();
+    let mut position = 0;
+    while position < state.len() {
+        let unchanged = state[position..].iter().zip(&keyframe[position..])
+            .take(u16::MAX as usize)
+            .take_while(|(a, b)| a == b)
+            .count();
+        position += unchanged;
+        let changed = state[position..].iter().zip(&keyframe[position..])
+            .take(u16::MAX as usize)
+            .take_while(|(a, b)| a != b)
+            .count();
+        delta.extend_from_slice(&(unchanged as u16).to_be_bytes());
+        delta.extend_from_slice(&(changed as u16).to_be_bytes());
+        delta.extend(state[position..position + changed].iter().zip(&keyframe[position..]).map(|(a, b)| a ^ b));
+        position += changed;
+    }
+    delta
+}
+
+fn decode_delta(keyframe: &[u8], delta: &[u8]) -> Vec<u8> {
+    let mut state = keyframe.to_vec();
+    let mut position = 0;
+    let mut runs = delta;
+    while runs.len() >= 4 {
+        let unchanged = u16::from_be_bytes(runs[0..2].try_into().unwrap()) as usize;
+        let changed = u16::from_be_bytes(runs[2..4].try_into().unwrap()) as usize;
+        position += unchanged;
+        for (byte, xor) in state[position..position + changed].iter_mut().zip(&runs[4..4 + changed]) {
+            *byte ^= xor;
+        }
+        position += changed;
+        runs = &runs[4 + changed..];
+    }
+    state
+}
+
+#[cfg(test)]
+#[path = "./rewind_tests.rs"]
+mod rewind_tests;
@@ -0,0 +1,79 @@
+use super::*;
+use crate::test_utils::cpu_with_program;
+
+/// Counts V0 up forever, drawing the digit each frame
+fn counting_cpu() -> Cpu {
+    let source = "
+        loop:
+            CLS
+            LD F, V0
+            DRW V1, V1, 5
+            ADD V0, 1
+            JP loop
+    ";
+    cpu_with_program(source)
+}
+
+#[test]
+fn test_delta_round_trip() {
+    let keyframe = vec![0u8; 100_000];
+    let mut state = keyframe.clone();
+    state[3] = 1;
+    state[4] = 2;
+    state[70_000] = 3;
+    state[99_999] = 4;
+    let delta = encode_delta(&keyframe, &state);
+    assert!(delta.len() < 40);
+    assert_eq!(decode_delta(&keyframe, &delta), state);
+    assert_eq!(decode_delta(&keyframe, &encode_delta(&keyframe, &keyframe)), keyframe);
+}
+
+#[test]
+fn test_rewind_frames() {
+    let mut cpu = counting_cpu();
+    let mut rewind = Rewind::new(usize::MAX, usize::MAX);
+    rewind.set_keyframe_interval(4);
+    let mut states = Vec::new();
+    for _ in 0..10 {
+        rewind.push(&cpu);
+        states.push(cpu.save_state(0));
+        cpu.run_frame([false; 16], 5).unwrap();
+    }
+    assert_eq!(rewind.len(), 10);
+    // Deltas are much smaller than full states
+    assert!(rewind.memory_used() < 4 * states[0].len());
+
+    while let Some(state) = states.pop() {
+        assert!(rewind.pop(&mut cpu));
+        assert_eq!(cpu.save_state(0), state);
+    }
+    assert!(rewind.is_empty());
+    assert_eq!(rewind.memory_used(), 0);
+    assert!(!rewind.pop(&mut cpu));
+}
+
+#[test]
+fn test_rewind_limits() {
+    let mut cpu = counting_cpu();
+    let mut rewind = Rewind::new(usize::MAX, 10);
+    rewind.set_keyframe_interval(4);
+    for _ in 0..20 {
+        rewind.push(&cpu);
+        cpu.run_frame([false; 16], 5).unwrap();
+    }
+    // Whole keyframe groups are dropped, oldest first
+    assert_eq!(rewind.len(), 8);
+    for _ in 0..8 {
+        assert!(rewind.pop(&mut cpu));
+    }
+    assert_eq!(cpu.registers()[0], 12);
+
+    let keyframe_size = cpu.save_state(0).len();
+    let mut rewind = Rewind::new(keyframe_size * 2, usize::MAX);
+    rewind.set_keyframe_interval(1);
+    for _ in 0..5 {
+        rewind.push(&cpu);
+    }
+    assert_eq!(rewind.len(), 2);
+    assert!(rewind.memory_used() <= keyframe_size * 2);
+}
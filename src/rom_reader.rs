@@ -0,0 +1,29 @@
+use crate::cpu::{MEMORY_SIZE, PROGRAM_START};
+use std::fs::File;
+use std::io::Read;
+
+// Max rom size = memory size - 512
+pub const MAX_ROM_SIZE: usize = MEMORY_SIZE - PROGRAM_START;
+
+pub struct RomReader {
+    pub data: Vec<u8>,
+    size: usize,
+}
+
+impl RomReader {
+    pub fn new(rom_file: &str) -> Self {
+        let f = File::open(rom_file).expect("file not found");
+
+        let mut buffer = Vec::new();
+        let bytes = f.take(MAX_ROM_SIZE as u64).read_to_end(&mut buffer).unwrap_or_default();
+        RomReader {
+            data: buffer,
+            size: bytes
+        }
+    }
+
+    /// Number of bytes read from the rom file
+    pub fn size(&self) -> usize {
+        self.size
+    }
+}
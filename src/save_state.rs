@@ -0,0 +1,172 @@
+use std::convert::TryInto;
+use std::error::Error;
+use std::fmt;
+
+/// First bytes of every save state
+pub const STATE_MAGIC: [u8; 4] = *b"RC8S";
+/// Bumped whenever the layout of the saved machine changes
+pub const STATE_VERSION: u16 = 3;
+/// Magic, version and rom hash
+pub const HEADER_SIZE: usize = 4 + 2 + 8;
+
+/// Why a save state can't be restored
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StateError {
+    /// Doesn't start with STATE_MAGIC
+    NotAState,
+    /// Saved by a version of the emulator with a different format
+    UnsupportedVersion { found: u16 },
+    /// Saved while running a different rom
+    WrongRom { expected: u64, found: u64 },
+    /// Ends before the machine state does, or has bytes left over
+    Corrupt,
+}
+
+impl fmt::Display for StateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            StateError::NotAState => write!(f, "not an rChip8 save state"),
+            StateError::UnsupportedVersion { found } => write!(
+                f,
+                "save state format version {} is not supported, this build reads version {}",
+                found, STATE_VERSION
+            ),
+            StateError::WrongRom { expected, found } => write!(
+                f,
+                "save state belongs to a different rom (rom hash {:016x}, state has {:016x})",
+                expected, found
+            ),
+            StateError::Corrupt => write!(f, "save state is truncated or corrupt"),
+        }
+    }
+}
+
+impl Error for StateError {}
+
+/// 64 bit FNV-1a hash identifying a rom in save states
+pub fn rom_hash(rom: &[u8]) -> u64 {
+    rom.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3))
+}
+
+/// Header for a state of the rom with hash `rom_hash`
+pub fn header(rom_hash: u64) -> Vec<u8> {
+    let mut writer = StateWriter::default();
+    writer.bytes(&STATE_MAGIC);
+    writer.u16(STATE_VERSION);
+    writer.u64(rom_hash);
+    writer.into_bytes()
+}
+
+/// Check the header and return the machine state that follows it
+pub fn check_header(state: &[u8], rom_hash: u64) -> Result<&[u8], StateError> {
+    if state.len() < STATE_MAGIC.len() || state[..STATE_MAGIC.len()] != STATE_MAGIC {
+        return Err(StateError::NotAState);
+    }
+    let mut reader = StateReader::new(&state[STATE_MAGIC.len()..]);
+    let version = reader.u16()?;
+    if version != STATE_VERSION {
+        return Err(StateError::UnsupportedVersion { found: version });
+    }
+    let found = reader.u64()?;
+    if found != rom_hash {
+        return Err(StateError::WrongRom { expected: rom_hash, found });
+    }
+    Ok(&state[HEADER_SIZE..])
+}
+
+/// Big endian writer for the fields of a state
+#[derive(Default)]
+pub struct StateWriter {
+    data: Vec<u8>,
+}
+
+impl StateWriter {
+    pub fn u8(&mut self, value: u8) {
+        self.data.push(value);
+    }
+
+    pub fn bool(&mut self, value: bool) {
+        self.data.push(value as u8);
+    }
+
+    pub fn u16(&mut self, value: u16) {
+        self.data.extend_from_slice(&value.to_be_bytes());
+    }
+
+    pub fn u32(&mut self, value: u32) {
+        self.data.extend_from_slice(&value.to_be_bytes());
+    }
+
+    pub fn u64(&mut self, value: u64) {
+        self.data.extend_from_slice(&value.to_be_bytes());
+    }
+
+    pub fn u128(&mut self, value: u128) {
+        self.data.extend_from_slice(&value.to_be_bytes());
+    }
+
+    pub fn bytes(&mut self, bytes: &[u8]) {
+        self.data.extend_from_slice(bytes);
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.data
+    }
+}
+
+/// Reads back what StateWriter wrote, running out of data is `Corrupt`
+pub struct StateReader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> StateReader<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        StateReader { data }
+    }
+
+    pub fn bytes(&mut self, count: usize) -> Result<&'a [u8], StateError> {
+        if self.data.len() < count {
+            return Err(StateError::Corrupt);
+        }
+        let (bytes, rest) = self.data.split_at(count);
+        self.data = rest;
+        Ok(bytes)
+    }
+
+    pub fn u8(&mut self) -> Result<u8, StateError> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    pub fn bool(&mut self) -> Result<bool, StateError> {
+        match self.u8()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(StateError::Corrupt),
+        }
+    }
+
+    pub fn u16(&mut self) -> Result<u16, StateError> {
+        Ok(u16::from_be_bytes(self.bytes(2)?.try_into().unwrap()))
+    }
+
+    pub fn u32(&mut self) -> Result<u32, StateError> {
+        Ok(u32::from_be_bytes(self.bytes(4)?.try_into().unwrap()))
+    }
+
+    pub fn u64(&mut self) -> Result<u64, StateError> {
+        Ok(u64::from_be_bytes(self.bytes(8)?.try_into().unwrap()))
+    }
+
+    pub fn u128(&mut self) -> Result<u128, StateError> {
+        Ok(u128::from_be_bytes(self.bytes(16)?.try_into().unwrap()))
+    }
+
+    /// Fails unless everything was read
+    pub fn finish(self) -> Result<(), StateError> {
+        if self.data.is_empty() { Ok(()) } else { Err(StateError::Corrupt) }
+    }
+}
+
+#[cfg(test)]
+#[path = "./save_state_tests.rs"]
+mod save_state_tests;
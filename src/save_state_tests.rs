@@ -0,0 +1,55 @@
+use super::*;
+
+#[test]
+fn test_rom_hash() {
+    assert_eq!(rom_hash(&[]), 0xcbf2_9ce4_8422_2325);
+    assert_eq!(rom_hash(b"a"), 0xaf63_dc4c_8601_ec8c);
+    assert_ne!(rom_hash(&[0x00, 0xe0]), rom_hash(&[0xe0, 0x00]));
+}
+
+#[test]
+fn test_header() {
+    let mut state = header(42);
+    assert_eq!(state.len(), HEADER_SIZE);
+    state.extend_from_slice(&[1, 2, 3]);
+    assert_eq!(check_header(&state, 42), Ok(&[1u8, 2, 3][..]));
+}
+
+#[test]
+fn test_header_errors() {
+    let state = header(42);
+    assert_eq!(check_header(b"RC8", 42), Err(StateError::NotAState));
+    assert_eq!(check_header(b"not a state", 42), Err(StateError::NotAState));
+    assert_eq!(check_header(&state[..HEADER_SIZE - 1], 42), Err(StateError::Corrupt));
+    assert_eq!(check_header(&state, 7), Err(StateError::WrongRom { expected: 7, found: 42 }));
+
+    let mut future = state.clone();
+    future[4..6].copy_from_slice(&(STATE_VERSION + 1).to_be_bytes());
+    assert_eq!(check_header(&future, 42), Err(StateError::UnsupportedVersion { found: STATE_VERSION + 1 }));
+}
+
+#[test]
+fn test_reader_writer() {
+    let mut writer = StateWriter::default();
+    writer.u8(1);
+    writer.bool(true);
+    writer.u16(0x1234);
+    writer.u32(0x1234_5678);
+    writer.u64(u64::MAX);
+    writer.bytes(b"ab");
+    let data = writer.into_bytes();
+
+    let mut reader = StateReader::new(&data);
+    assert_eq!(reader.u8(), Ok(1));
+    assert_eq!(reader.bool(), Ok(true));
+    assert_eq!(reader.u16(), Ok(0x1234));
+    assert_eq!(reader.u32(), Ok(0x1234_5678));
+    assert_eq!(reader.u64(), Ok(u64::MAX));
+    assert_eq!(reader.bytes(2), Ok(&b"ab"[..]));
+    assert_eq!(reader.u8(), Err(StateError::Corrupt));
+    assert_eq!(reader.finish(), Ok(()));
+
+    let mut reader = StateReader::new(&[2, 0]);
+    assert_eq!(reader.bool(), Err(StateError::Corrupt));
+    assert_eq!(reader.finish(), Err(StateError::Corrupt));
+}
@@ -0,0 +1,11 @@
+//! Fixtures shared by the unit tests
+use crate::assembler::assemble;
+use crate::cpu::Cpu;
+use crate::quirks::Quirks;
+
+/// Cpu with the default quirks and `source` assembled into memory
+pub fn cpu_with_program(source: &str) -> Cpu {
+    let mut cpu = Cpu::new(Quirks::default());
+    cpu.read_data_to_memory(&assemble(source).unwrap().image);
+    cpu
+}
@@ -0,0 +1,335 @@
+use std::io::{self, Read, Write};
+use std::process::{Command, Stdio};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::framebuffer::{Framebuffer, PALETTE};
+use crate::frontend::{Hotkey, InputSource, VideoSink};
+
+/// How pixels are packed into terminal cells
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TtyMode {
+    /// `▀` with the top pixel as foreground and the bottom one as
+    /// background, 1x2 pixels per cell in full color
+    HalfBlocks,
+    /// Braille dots, 2x4 pixels per cell in one color. Fits 128x64 in a
+    /// 64x16 terminal.
+    Braille,
+}
+
+impl TtyMode {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "blocks" => Some(TtyMode::HalfBlocks),
+            "braille" => Some(TtyMode::Braille),
+            _ => None,
+        }
+    }
+
+    /// Pixels per cell
+    fn cell_size(self) -> (usize, usize) {
+        match self {
+            TtyMode::HalfBlocks => (1, 2),
+            TtyMode::Braille => (2, 4),
+        }
+    }
+}
+
+/// One character on the terminal with its colors, as PALETTE indexes
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Cell {
+    character: char,
+    foreground: u8,
+    background: u8,
+}
+
+/// Braille dot bits for the pixel at (x, y) inside a 2x4 cell
+const BRAILLE_DOTS: [[u32; 2]; 4] = [[0x01, 0x08], [0x02, 0x10], [0x04, 0x20], [0x40, 0x80]];
+
+/// Terminal video
+/// Draws frames with ANSI escape codes and 24 bit colors. The cells of the
+/// last frame are kept, so only the ones that changed are written again.
+pub struct TtyVideo<W: Write> {
+    out: W,
+    mode: TtyMode,
+    cells: Vec<Vec<Cell>>,
+    title: String,
+}
+
+impl<W: Write> TtyVideo<W> {
+    pub fn new(out: W, mode: TtyMode) -> Self {
+        TtyVideo {
+            out,
+            mode,
+            cells: Vec::new(),
+            title: String::new(),
+        }
+    }
+
+    fn render(&self, vram: &Framebuffer) -> Vec<Vec<Cell>> {
+        let (cell_width, cell_height) = self.mode.cell_size();
+        let rows: Vec<Vec<u8>> = vram.rows().collect();
+        (0..vram.height() / cell_height)
+            .map(|row| {
+                (0..vram.width() / cell_width)
+                    .map(|column| {
+                        let pixel = |x: usize, y: usize| rows[row * cell_height + y][column * cell_width + x] & 3;
+                        match self.mode {
+                            TtyMode::HalfBlocks => Cell { character: '▀', foreground: pixel(0, 0), background: pixel(0, 1) },
+                            TtyMode::Braille => {
+                                let mut dots = 0;
+                                let mut color = 0;
+                                for (y, row_dots) in BRAILLE_DOTS.iter().enumerate() {
+                                    for (x, &dot) in row_dots.iter().enumerate() {
+                                        if pixel(x, y) != 0 {
+                                            dots |= dot;
+                                            color |= pixel(x, y);
+                                        }
+                                    }
+                                }
+                                let character = char::from_u32(0x2800 + dots).unwrap_or(' ');
+                                Cell { character, foreground: color, background: 0 }
+                            }
+                        }
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    fn write_frame(&mut self, cells: Vec<Vec<Cell>>) -> io::Result<()> {
+        let mut output = String::new();
+        // Resolution changed, start over on a clean screen
+        let full_redraw = self.cells.len() != cells.len() || self.cells[0].len() != cells[0].len();
+        if full_redraw {
+            output.push_str("\x1b[0m\x1b[2J\x1b[?25l");
+        }
+        let mut colors = None;
+        let mut changed = full_redraw;
+        for (row, line) in cells.iter().enumerate() {
+            // Cursor is already in place after writing the cell to the left
+            let mut cursor_ready = false;
+            for (column, &cell) in line.iter().enumerate() {
+                if !full_redraw && self.cells[row][column] == cell {
+                    cursor_ready = false;
+                    continue;
+                }
+                if !cursor_ready {
+                    output.push_str(&format!("\x1b[{};{}H", row + 1, column + 1));
+                }
+                if colors != Some((cell.foreground, cell.background)) {
+                    let (fr, fg, fb) = PALETTE[cell.foreground as usize];
+                    let (br, bg, bb) = PALETTE[cell.background as usize];
+                    output.push_str(&format!("\x1b[38;2;{};{};{};48;2;{};{};{}m", fr, fg, fb, br, bg, bb));
+                    colors = Some((cell.foreground, cell.background));
+                }
+                output.push(cell.character);
+                cursor_ready = true;
+                changed = true;
+            }
+        }
+        self.cells = cells;
+        if !changed {
+            return Ok(());
+        }
+        output.push_str("\x1b[0m");
+        if full_redraw {
+            output.push_str(&format!("\x1b[{};1H{}", self.cells.len() + 1, self.title));
+        }
+        self.out.write_all(output.as_bytes())?;
+        self.out.flush()
+    }
+}
+
+impl<W: Write> VideoSink for TtyVideo<W> {
+    fn draw(&mut self, vram: &Framebuffer) {
+        let cells = self.render(vram);
+        // A closed terminal shows up as quitting on the input side
+        self.write_frame(cells).unwrap_or_default();
+    }
+
+    /// Shown on the line under the picture
+    fn set_title(&mut self, title: &str) {
+        self.title = title.to_string();
+        let line = format!("\x1b[0m\x1b[{};1H\x1b[2K{}", self.cells.len() + 1, title);
+        self.out.write_all(line.as_bytes()).and_then(|_| self.out.flush()).unwrap_or_default();
+    }
+}
+
+impl<W: Write> Drop for TtyVideo<W> {
+    /// Leave the cursor under the picture, visible and with normal colors
+    fn drop(&mut self) {
+        let line = format!("\x1b[0m\x1b[?25h\x1b[{};1H\r\n", self.cells.len() + 2);
+        self.out.write_all(line.as_bytes()).and_then(|_| self.out.flush()).unwrap_or_default();
+    }
+}
+
+/// Terminal input
+/// Reads keys from stdin in raw mode. Terminals only report key presses,
+/// so a key counts as held until `release_timeout` passes without it being
+/// sent again. Set the timeout longer than the keyboard repeat delay to
+/// hold keys down smoothly. Esc or Ctrl-C quits.
+pub struct TtyInput {
+    bytes: Receiver<Vec<u8>>,
+    release_timeout: Duration,
+    pressed: [Option<Instant>; 16],
+    rewind_pressed: Option<Instant>,
+    hotkeys: Vec<Hotkey>,
+    quit: bool,
+}
+
+impl TtyInput {
+    pub const DEFAULT_RELEASE_TIMEOUT: Duration = Duration::from_millis(250);
+
+    /// Start reading stdin on its own thread
+    pub fn new(release_timeout: Duration) -> Self {
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            let mut buffer = [0; 64];
+            let mut stdin = io::stdin();
+            while let Ok(count) = stdin.read(&mut buffer) {
+                if count == 0 || sender.send(buffer[..count].to_vec()).is_err() {
+                    break;
+                }
+            }
+        });
+        Self::from_receiver(receiver, release_timeout)
+    }
+
+    fn from_receiver(bytes: Receiver<Vec<u8>>, release_timeout: Duration) -> Self {
+        TtyInput {
+            bytes,
+            release_timeout,
+            pressed: [None; 16],
+            rewind_pressed: None,
+            hotkeys: Vec::new(),
+            quit: false,
+        }
+    }
+
+    /// Handle one read from stdin. Escape sequences come in one read, so a
+    /// lone Esc is the Esc key.
+    fn handle_bytes(&mut self, bytes: &[u8], now: Instant) {
+        let mut rest = bytes;
+        while let Some((&byte, tail)) = rest.split_first() {
+            rest = tail;
+            match byte {
+                0x1b if rest.first() == Some(&b'[') => {
+                    let end = rest.iter().position(|byte| byte.is_ascii_alphabetic() || *byte == b'~').unwrap_or(rest.len() - 1);
+                    let hotkey = match &rest[..=end] {
+                        b"[15~" => Some(Hotkey::SaveState),
+                        b"[17~" => Some(Hotkey::PreviousSlot),
+                        b"[18~" => Some(Hotkey::NextSlot),
+                        b"[20~" => Some(Hotkey::LoadState),
+                        _ => None,
+                    };
+                    self.hotkeys.extend(hotkey);
+                    rest = &rest[end + 1..];
+                }
+                0x1b | 0x03 => self.quit = true,
+                b'=' | b'+' => self.hotkeys.push(Hotkey::Faster),
+                b'-' => self.hotkeys.push(Hotkey::Slower),
+                0x7f | 0x08 => self.rewind_pressed = Some(now),
+                _ => {
+                    if let Some(key) = keypad_key(byte) {
+                        self.pressed[key] = Some(now);
+                    }
+                }
+            }
+        }
+    }
+
+    fn is_held(&self, pressed: Option<Instant>, now: Instant) -> bool {
+        pressed.is_some_and(|pressed| now.duration_since(pressed) < self.release_timeout)
+    }
+
+    fn keys(&self, now: Instant) -> [bool; 16] {
+        let mut keys = [false; 16];
+        for (key, &pressed) in keys.iter_mut().zip(self.pressed.iter()) {
+            *key = self.is_held(pressed, now);
+        }
+        keys
+    }
+}
+
+impl InputSource for TtyInput {
+    fn poll(&mut self) -> Option<[bool; 16]> {
+        let now = Instant::now();
+        loop {
+            match self.bytes.try_recv() {
+                Ok(bytes) => self.handle_bytes(&bytes, now),
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => return None,
+            }
+        }
+        if self.quit {
+            None
+        } else {
+            Some(self.keys(now))
+        }
+    }
+
+    fn take_hotkeys(&mut self) -> Vec<Hotkey> {
+        std::mem::take(&mut self.hotkeys)
+    }
+
+    fn is_rewinding(&self) -> bool {
+        self.is_held(self.rewind_pressed, Instant::now())
+    }
+}
+
+/// Same layout as the window: 1234/QWER/ASDF/ZXCV
+fn keypad_key(byte: u8) -> Option<usize> {
+    match byte.to_ascii_lowercase() {
+        b'1' => Some(0x1),
+        b'2' => Some(0x2),
+        b'3' => Some(0x3),
+        b'4' => Some(0xc),
+        b'q' => Some(0x4),
+        b'w' => Some(0x5),
+        b'e' => Some(0x6),
+        b'r' => Some(0xd),
+        b'a' => Some(0x7),
+        b's' => Some(0x8),
+        b'd' => Some(0x9),
+        b'f' => Some(0xe),
+        b'z' => Some(0xa),
+        b'x' => Some(0x0),
+        b'c' => Some(0xb),
+        b'v' => Some(0xf),
+        _ => None,
+    }
+}
+
+/// Puts the terminal in raw mode with `stty` and restores the previous
+/// settings when dropped
+pub struct RawMode {
+    saved: String,
+}
+
+impl RawMode {
+    pub fn enable() -> io::Result<Self> {
+        let saved = stty(&["-g"])?;
+        stty(&["raw", "-echo"])?;
+        Ok(RawMode { saved: saved.trim().to_string() })
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        stty(&[&self.saved]).unwrap_or_default();
+    }
+}
+
+fn stty(args: &[&str]) -> io::Result<String> {
+    let output = Command::new("stty").args(args).stdin(Stdio::inherit()).output()?;
+    if !output.status.success() {
+        return Err(io::Error::other(String::from_utf8_lossy(&output.stderr).trim().to_string()));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+#[cfg(test)]
+#[path = "./tty_tests.rs"]
+mod tty_tests;
@@ -0,0 +1,93 @@
+use super::*;
+
+fn input() -> TtyInput {
+    let (_, receiver) = mpsc::channel();
+    TtyInput::from_receiver(receiver, Duration::from_millis(100))
+}
+
+#[test]
+fn test_half_blocks() {
+    let mut vram = Framebuffer::new();
+    vram.set_pixel(0, 0, 1);
+    vram.set_pixel(1, 1, 1);
+    vram.set_pixel(63, 31, 2);
+    let video = TtyVideo::new(Vec::new(), TtyMode::HalfBlocks);
+    let cells = video.render(&vram);
+    assert_eq!((cells.len(), cells[0].len()), (16, 64));
+    assert_eq!(cells[0][0], Cell { character: '▀', foreground: 1, background: 0 });
+    assert_eq!(cells[0][1], Cell { character: '▀', foreground: 0, background: 1 });
+    assert_eq!(cells[15][63], Cell { character: '▀', foreground: 0, background: 2 });
+
+    vram.set_hires(true);
+    assert_eq!(video.render(&vram).len(), 32);
+}
+
+#[test]
+fn test_braille() {
+    let mut vram = Framebuffer::new();
+    vram.set_hires(true);
+    vram.set_pixel(0, 0, 1);
+    vram.set_pixel(1, 3, 1);
+    vram.set_pixel(2, 4, 2);
+    let video = TtyVideo::new(Vec::new(), TtyMode::Braille);
+    let cells = video.render(&vram);
+    assert_eq!((cells.len(), cells[0].len()), (16, 64));
+    assert_eq!(cells[0][0], Cell { character: '⢁', foreground: 1, background: 0 });
+    assert_eq!(cells[1][1], Cell { character: '⠁', foreground: 2, background: 0 });
+    assert_eq!(cells[0][1].character, '⠀');
+}
+
+#[test]
+fn test_redraw_changed_cells() {
+    let mut vram = Framebuffer::new();
+    let mut video = TtyVideo::new(Vec::new(), TtyMode::HalfBlocks);
+    video.draw(&vram);
+    let full = video.out.len();
+    assert!(String::from_utf8_lossy(&video.out).starts_with("\x1b[0m\x1b[2J"));
+    assert_eq!(String::from_utf8_lossy(&video.out).matches('▀').count(), 64 * 16);
+
+    video.out.clear();
+    video.draw(&vram);
+    assert!(video.out.is_empty());
+
+    vram.set_pixel(5, 2, 1);
+    vram.set_pixel(6, 2, 1);
+    vram.set_pixel(0, 30, 1);
+    video.out.clear();
+    video.draw(&vram);
+    let output = String::from_utf8(video.out.clone()).unwrap();
+    assert!(output.len() < full / 10);
+    assert_eq!(output.matches('▀').count(), 3);
+    // Neighbouring cells share one cursor move
+    assert_eq!(output.matches('H').count(), 2);
+    assert!(output.contains("\x1b[2;6H"));
+    assert!(output.contains("\x1b[16;1H"));
+}
+
+#[test]
+fn test_keys_release_after_timeout() {
+    let mut input = input();
+    let start = Instant::now();
+    input.handle_bytes(b"wX", start);
+    let keys = input.keys(start + Duration::from_millis(50));
+    assert!(keys[0x5] && keys[0x0]);
+    assert_eq!(keys.iter().filter(|&&key| key).count(), 2);
+
+    // Key repeat keeps it held
+    input.handle_bytes(b"w", start + Duration::from_millis(90));
+    let keys = input.keys(start + Duration::from_millis(150));
+    assert!(keys[0x5] && !keys[0x0]);
+    assert_eq!(input.keys(start + Duration::from_millis(200)), [false; 16]);
+}
+
+#[test]
+fn test_hotkeys_and_quit() {
+    let mut input = input();
+    let now = Instant::now();
+    input.handle_bytes(b"+-\x1b[15~\x1b[20~\x1b[A", now);
+    assert_eq!(input.take_hotkeys(), vec![Hotkey::Faster, Hotkey::Slower, Hotkey::SaveState, Hotkey::LoadState]);
+    assert!(!input.quit);
+    input.handle_bytes(b"\x1b", now);
+    assert!(input.quit);
+    assert_eq!(input.poll(), None);
+}
@@ -1,8 +1,9 @@
-use sdl2;
 use sdl2::audio::{AudioDevice, AudioCallback, AudioSpecDesired};
+use r_chip8::AudioPattern;
+use r_chip8::frontend::AudioSink;
 
 pub struct Audio {
-    device: AudioDevice<SquareWave>
+    device: AudioDevice<Buzzer>
 }
 
 impl Audio{
@@ -20,44 +21,72 @@ impl Audio{
 
         let device = audio_subsystem.open_playback(None, &desired_spec, |spec| {
             // initialize the audio callback
-            SquareWave {
+            Buzzer {
+                freq: spec.freq as f32,
                 phase_inc: 440.0 / spec.freq as f32,
                 phase: 0.0,
-                volume: 0.05
+                volume: 0.05,
+                pattern: None,
             }
         }).unwrap();
 
         Audio { device }
     }
+}
 
-    pub fn start_audio(&mut self) {
+impl AudioSink for Audio {
+    fn start_audio(&mut self) {
         self.device.resume();
     }
 
-    pub fn stop_audio(&mut self) {
+    fn stop_audio(&mut self) {
         self.device.pause();
     }
+
+    fn set_pattern(&mut self, pattern: Option<AudioPattern>) {
+        let mut buzzer = self.device.lock();
+        if buzzer.pattern != pattern {
+            buzzer.pattern = pattern;
+            buzzer.phase = 0.0;
+        }
+    }
 }
 
 
-struct SquareWave {
+struct Buzzer {
+    freq: f32,
     phase_inc: f32,
     phase: f32,
-    volume: f32
+    volume: f32,
+    pattern: Option<AudioPattern>,
 }
 
-impl AudioCallback for SquareWave {
+impl AudioCallback for Buzzer {
     type Channel = f32;
 
     fn callback(&mut self, out: &mut [f32]) {
-        // Generate a square wave
-        for x in out.iter_mut() {
-            *x = match self.phase {
-                0.0..= 0.5 =>self.volume,
-                _ => -self.volume
-            };
-            self.phase = (self.phase + self.phase_inc) % 1.0;
+        match self.pattern {
+            // Play the 128 bit pattern, phase is the position in the pattern
+            Some(pattern) => {
+                let step = pattern.sample_rate() / self.freq;
+                for x in out.iter_mut() {
+                    *x = match pattern.bit(self.phase as usize) {
+                        1 => self.volume,
+                        _ => -self.volume
+                    };
+                    self.phase = (self.phase + step) % 128.0;
+                }
+            }
+            // Generate a square wave
+            None => {
+                for x in out.iter_mut() {
+                    *x = match self.phase {
+                        0.0..= 0.5 =>self.volume,
+                        _ => -self.volume
+                    };
+                    self.phase = (self.phase + self.phase_inc) % 1.0;
+                }
+            }
         }
     }
 }
-
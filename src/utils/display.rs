@@ -1,21 +1,29 @@
-use sdl2;
-use sdl2::pixels;
-use sdl2::rect::Rect;
-use sdl2::render::WindowCanvas;
-use rand::Rng;
-use std::thread;
-use std::time;
+use sdl2::pixels::{self, PixelFormatEnum};
+use sdl2::render::{Texture, TextureCreator, WindowCanvas};
+use sdl2::video::WindowContext;
+use r_chip8::Framebuffer;
+use r_chip8::framebuffer::PALETTE;
+use r_chip8::frontend::VideoSink;
 
 const SCALE_FACTOR: u32 = 20;
-const W_HEIGHT: u32 = 32 as u32;
-const W_WIDTH: u32 = 64 as u32;
+const W_HEIGHT: u32 = 32;
+const W_WIDTH: u32 = 64;
 
-pub struct Display {
-    canvas: WindowCanvas
+/// Window showing the framebuffer
+/// Frames are uploaded into a streaming texture the size of the CHIP-8
+/// screen and the renderer scales it to the window in one copy. Textures
+/// borrow their creator, so the caller keeps the window's texture creator
+/// alive for as long as the display.
+pub struct Display<'a> {
+    canvas: WindowCanvas,
+    texture_creator: &'a TextureCreator<WindowContext>,
+    texture: Texture<'a>,
+    texture_width: usize,
 }
 
-impl Display {
-    pub fn new(sdl2_context: &sdl2::Sdl) -> Self {
+impl<'a> Display<'a> {
+    /// Window sized for the low resolution screen, cleared to the background color
+    pub fn open_window(sdl2_context: &sdl2::Sdl) -> WindowCanvas {
         let video_subsystem = sdl2_context.video().unwrap();
         let window = video_subsystem.window(
             "rChip8",
@@ -28,48 +36,41 @@ impl Display {
         canvas.set_draw_color(color(0));
         canvas.clear();
         canvas.present();
+        canvas
+    }
 
-        Display { canvas }
+    pub fn new(canvas: WindowCanvas, texture_creator: &'a TextureCreator<WindowContext>) -> Self {
+        let texture = screen_texture(texture_creator, W_WIDTH, W_HEIGHT);
+        Display { canvas, texture_creator, texture, texture_width: W_WIDTH as usize }
     }
+}
 
-    pub fn draw(&mut self, pixels: &[[u8; W_WIDTH as usize]; W_HEIGHT as usize]) {
-        for (y, row) in pixels.iter().enumerate() {
-            for (x, &col) in row.iter().enumerate() {
-                let x = (x as u32) * SCALE_FACTOR;
-                let y = (y as u32) * SCALE_FACTOR;
-                self.canvas.set_draw_color(color(col));
-                self.canvas.fill_rect(
-                    Rect::new(x as i32, y as i32, SCALE_FACTOR, SCALE_FACTOR)
-                ).unwrap_or_else(|e| panic!("Error: {}", e));
-            }
+impl VideoSink for Display<'_> {
+    fn draw(&mut self, pixels: &Framebuffer) {
+        // Switching between low and high resolution changes the texture size
+        if pixels.width() != self.texture_width {
+            self.texture = screen_texture(self.texture_creator, pixels.width() as u32, pixels.height() as u32);
+            self.texture_width = pixels.width();
         }
+        self.texture.update(None, &pixels.rgb24(), pixels.width() * 3)
+            .unwrap_or_else(|e| panic!("Error: {}", e));
+        self.canvas.copy(&self.texture, None, None).unwrap_or_else(|e| panic!("Error: {}", e));
         self.canvas.present();
     }
 
-    // Just for testing display
-    pub fn display_tester(&mut self) {
-        let mut vram: [[u8; 64]; 32] = [[1; 64 as usize]; 32 as usize];
-        let mut rng = rand::thread_rng();
-
-        loop {
-            for _i in 0..100 {
-                let vram2 = vram.clone();
-                for (y, _row) in vram2.iter().enumerate() {
-                    for (x, _row) in vram2[y].iter().enumerate() {
-                        vram[y][x] = rng.gen_range(0, 2) as u8;
-                    }
-                }
-                self.draw(&vram);
-                thread::sleep(time::Duration::from_millis(11))
-            }
-        }
+    fn set_title(&mut self, title: &str) {
+        self.canvas.window_mut().set_title(title).unwrap_or_else(|e| panic!("Error: {}", e));
     }
 }
 
+fn screen_texture(texture_creator: &TextureCreator<WindowContext>, width: u32, height: u32) -> Texture<'_> {
+    texture_creator.create_texture_streaming(PixelFormatEnum::RGB24, width, height)
+        .unwrap_or_else(|e| panic!("Error: {}", e))
+}
+
 pub fn color(input: u8) -> pixels::Color {
-    match input {
-        0 => return pixels::Color::RGB(0, 0, 0),
-        1 => return pixels::Color::RGB(255, 255, 255),
-        _ => panic!("Invalid value")
+    match PALETTE.get(input as usize) {
+        Some(&(r, g, b)) => pixels::Color::RGB(r, g, b),
+        None => panic!("Invalid value")
     }
 }
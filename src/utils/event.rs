@@ -1,42 +1,56 @@
-use std::fs::File;
-use std::io::Read;
-use sdl2;
 use sdl2::event::Event;
 use sdl2::keyboard::Keycode;
-use sdl2::keyboard::Scancode;
+use r_chip8::frontend::{Hotkey, InputSource};
 
 pub struct EventHandler {
     events: sdl2::EventPump,
+    hotkeys: Vec<Hotkey>,
+    rewinding: bool,
 }
 
 impl EventHandler {
     pub fn new(sdl_context: &sdl2::Sdl) -> Self {
         EventHandler {
-            events: sdl_context.event_pump().unwrap()
+            events: sdl_context.event_pump().unwrap(),
+            hotkeys: Vec::new(),
+            rewinding: false,
         }
     }
+}
 
-    pub fn event_poller(&mut self) -> Result<[bool; 16], ()> {
-        /// Method for collecting key events and watching quit events.
-        /// Created mostly using this document: https://rust-sdl2.github.io/rust-sdl2/sdl2/keyboard/struct.KeyboardState.html
-        ///
-        /// Returns:
-        /// Result<[bool; 16], ()>
-        /// Where boolean values indicates different keys pressed
-        ///
-        /// Usage example:
-        ///  let mut events = EventHandler::new(&sdl2_context);
-        ///  while let Ok(keypad) = events.event_poller() {
-        ///       // Do something
-        ///  }
-
-        // Return error which exists main loop if Quit event is found or if
+impl InputSource for EventHandler {
+    /// Method for collecting key events and watching quit events.
+    /// Created mostly using this document: https://rust-sdl2.github.io/rust-sdl2/sdl2/keyboard/struct.KeyboardState.html
+    ///
+    /// Returns:
+    /// Option<[bool; 16]>
+    /// Where boolean values indicates different keys pressed
+    ///
+    /// Usage example:
+    ///  let mut events = EventHandler::new(&sdl2_context);
+    ///  while let Some(keypad) = events.poll() {
+    ///       // Do something
+    ///  }
+    fn poll(&mut self) -> Option<[bool; 16]> {
+        // Return None which exits main loop if Quit event is found or if
         // esc is pressed
         for event in self.events.poll_iter() {
             match event {
                 Event::Quit { .. } |
                 Event::KeyDown { keycode: Some(Keycode::Escape), .. } => {
-                    return Err(())
+                    return None
+                },
+                Event::KeyDown { keycode: Some(keycode), .. } => {
+                    let hotkey = match keycode {
+                        Keycode::Equals | Keycode::Plus | Keycode::KpPlus => Some(Hotkey::Faster),
+                        Keycode::Minus | Keycode::KpMinus => Some(Hotkey::Slower),
+                        Keycode::F5 => Some(Hotkey::SaveState),
+                        Keycode::F9 => Some(Hotkey::LoadState),
+                        Keycode::F6 => Some(Hotkey::PreviousSlot),
+                        Keycode::F7 => Some(Hotkey::NextSlot),
+                        _ => None
+                    };
+                    self.hotkeys.extend(hotkey);
                 },
                 _ => {}
             }
@@ -48,6 +62,8 @@ impl EventHandler {
             .filter_map(Keycode::from_scancode)
             .collect();
 
+        self.rewinding = pressed_keys.contains(&Keycode::Backspace);
+
         // Initialize keystate with false
         let mut key_state = [false; 16];
 
@@ -78,7 +94,14 @@ impl EventHandler {
                 key_state[i] = true;
             }
         }
-        Ok(key_state)
+        Some(key_state)
     }
 
-}
\ No newline at end of file
+    fn is_rewinding(&self) -> bool {
+        self.rewinding
+    }
+
+    fn take_hotkeys(&mut self) -> Vec<Hotkey> {
+        std::mem::take(&mut self.hotkeys)
+    }
+}
@@ -1,9 +1,7 @@
 mod display;
-mod rom_reader;
 mod audio;
 mod event;
 
-pub use self::rom_reader::RomReader;
 pub use self::display::Display;
 pub use self::audio::Audio;
-pub use self::event::EventHandler;
\ No newline at end of file
+pub use self::event::EventHandler;